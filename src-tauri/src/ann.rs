@@ -0,0 +1,284 @@
+//! Approximate nearest-neighbour search over stored voiceprints, so matching a speaker
+//! doesn't score every voiceprint once there are many of them.
+//!
+//! This is an inverted-file (IVF) index: voiceprints are grouped around k-means
+//! centroids, and a query is only scored against the groups whose centroids are closest
+//! to it. One speaker's voiceprints sit close together, so their group is almost always
+//! among those probed.
+//!
+//! The index refers to voiceprints by embedding id, so it outlives any one list of them:
+//! [`crate::db::Db`] keeps one, adds each new voiceprint to it, and only rebuilds it when
+//! the metric or vector length changes.
+
+use std::collections::HashSet;
+
+use crate::embedding::SimilarityMetric;
+
+/// Below this many voiceprints a full scan is fast enough and always exact.
+pub const MIN_INDEXED: usize = 512;
+const KMEANS_ITERATIONS: usize = 8;
+
+pub struct IvfIndex {
+    dim: usize,
+    metric: SimilarityMetric,
+    centroids: Vec<Vec<f32>>,
+    /// Ids of the indexed vectors, per centroid.
+    lists: Vec<Vec<String>>,
+    indexed: HashSet<String>,
+    probes: usize,
+}
+
+impl IvfIndex {
+    /// Groups `vectors`, given with their ids, into about √n clusters. Vectors whose
+    /// length differs from the first one's are left out (they can't match a query of the
+    /// other length anyway). `None` when there are fewer than [`MIN_INDEXED`], so callers
+    /// scan instead.
+    pub fn build<'a>(
+        vectors: impl IntoIterator<Item = (&'a str, &'a [f32])>,
+        metric: SimilarityMetric,
+    ) -> Option<Self> {
+        let vectors: Vec<(&str, &[f32])> = vectors.into_iter().collect();
+        let dim = vectors.first()?.1.len();
+        let vectors: Vec<(&str, &[f32])> = vectors
+            .into_iter()
+            .filter(|(_, v)| v.len() == dim)
+            .collect();
+        let count = vectors.len();
+        if count < MIN_INDEXED {
+            return None;
+        }
+        let clusters = (count as f32).sqrt().round() as usize;
+        // Evenly spaced seeds keep the index the same for the same voiceprints.
+        let mut centroids: Vec<Vec<f32>> = vectors
+            .iter()
+            .step_by(count / clusters)
+            .take(clusters)
+            .map(|(_, v)| normalized(v))
+            .collect();
+        let mut index = Self {
+            dim,
+            metric,
+            lists: vec![Vec::new(); centroids.len()],
+            centroids: Vec::new(),
+            indexed: HashSet::new(),
+            probes: (clusters / 4).max(3),
+        };
+        let mut assignments = vec![0usize; count];
+        for _ in 0..KMEANS_ITERATIONS {
+            index.centroids = centroids;
+            for (assigned, (_, vector)) in assignments.iter_mut().zip(&vectors) {
+                *assigned = index.closest_cluster(vector);
+            }
+            let mut sums = vec![vec![0f32; dim]; index.centroids.len()];
+            let mut sizes = vec![0usize; index.centroids.len()];
+            for (&cluster, (_, vector)) in assignments.iter().zip(&vectors) {
+                for (acc, v) in sums[cluster].iter_mut().zip(normalized(vector)) {
+                    *acc += v;
+                }
+                sizes[cluster] += 1;
+            }
+            centroids = sums
+                .iter()
+                .zip(&sizes)
+                .zip(&index.centroids)
+                // An empty cluster keeps its centroid rather than collapsing to zero.
+                .map(|((sum, &size), previous)| {
+                    if size == 0 {
+                        previous.clone()
+                    } else {
+                        normalized(sum)
+                    }
+                })
+                .collect();
+        }
+        index.centroids = centroids;
+        for (id, vector) in vectors {
+            index.insert(id, vector);
+        }
+        Some(index)
+    }
+
+    pub fn metric(&self) -> SimilarityMetric {
+        self.metric
+    }
+
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// Adds the vector with `id` to its closest cluster; vectors of another length, or
+    /// already indexed, are ignored.
+    pub fn insert(&mut self, id: &str, vector: &[f32]) {
+        if vector.len() != self.dim || self.indexed.contains(id) {
+            return;
+        }
+        let cluster = self.closest_cluster(vector);
+        self.lists[cluster].push(id.to_string());
+        self.indexed.insert(id.to_string());
+    }
+
+    /// Drops the vectors whose ids `keep` rejects, e.g. deleted voiceprints.
+    pub fn retain(&mut self, keep: impl Fn(&str) -> bool) {
+        for list in &mut self.lists {
+            list.retain(|id| keep(id));
+        }
+        self.indexed.retain(|id| keep(id));
+    }
+
+    /// Ids worth scoring for `query`. `None` when the query's length doesn't fit the index.
+    fn candidates(&self, query: &[f32]) -> Option<Vec<&str>> {
+        if query.len() != self.dim {
+            return None;
+        }
+        Some(
+            self.ranked_clusters(query)
+                .into_iter()
+                .take(self.probes)
+                .flat_map(|(cluster, _)| self.lists[cluster].iter().map(String::as_str))
+                .collect(),
+        )
+    }
+
+    /// Positions in `known` worth scoring for `query`, in ascending order so ties resolve
+    /// as in a full scan. Ids the index doesn't know are always included, so a voiceprint
+    /// added behind its back is still found.
+    pub fn candidate_positions<'a>(
+        &self,
+        known: impl IntoIterator<Item = &'a str>,
+        query: &[f32],
+    ) -> Option<Vec<usize>> {
+        let candidates: HashSet<&str> = self.candidates(query)?.into_iter().collect();
+        Some(
+            known
+                .into_iter()
+                .enumerate()
+                .filter(|(_, id)| candidates.contains(id) || !self.indexed.contains(*id))
+                .map(|(position, _)| position)
+                .collect(),
+        )
+    }
+
+    fn closest_cluster(&self, vector: &[f32]) -> usize {
+        self.ranked_clusters(vector)
+            .first()
+            .map_or(0, |(cluster, _)| *cluster)
+    }
+
+    /// Clusters by similarity of their centroid to `vector`, closest first.
+    fn ranked_clusters(&self, vector: &[f32]) -> Vec<(usize, f32)> {
+        let mut ranked: Vec<(usize, f32)> = self
+            .centroids
+            .iter()
+            .enumerate()
+            .map(|(cluster, centroid)| (cluster, self.metric.similarity(vector, centroid)))
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranked
+    }
+}
+
+fn normalized(vector: &[f32]) -> Vec<f32> {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm == 0.0 {
+        return vector.to_vec();
+    }
+    vector.iter().map(|x| x / norm).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    const DIM: usize = 32;
+
+    fn jitter(rng: &mut StdRng, center: &[f32], amount: f32) -> Vec<f32> {
+        center
+            .iter()
+            .map(|v| v + rng.gen_range(-amount..amount))
+            .collect()
+    }
+
+    fn best(query: &[f32], known: &[(String, Vec<f32>)], positions: &[usize]) -> String {
+        let metric = SimilarityMetric::Cosine;
+        let (position, _) = positions
+            .iter()
+            .map(|&i| (i, metric.similarity(query, &known[i].1)))
+            .fold((usize::MAX, f32::MIN), |best, next| {
+                if next.1 > best.1 {
+                    next
+                } else {
+                    best
+                }
+            });
+        known[position].0.clone()
+    }
+
+    #[test]
+    fn indexed_matches_agree_with_a_full_scan() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let centers: Vec<Vec<f32>> = (0..64)
+            .map(|_| (0..DIM).map(|_| rng.gen_range(-1.0..1.0)).collect())
+            .collect();
+        let known: Vec<(String, Vec<f32>)> = (0..10)
+            .flat_map(|round| {
+                centers
+                    .iter()
+                    .enumerate()
+                    .map(move |(c, center)| (round, c, center))
+            })
+            .map(|(round, c, center)| (format!("{c}-{round}"), jitter(&mut rng, center, 0.2)))
+            .collect();
+
+        // The last round arrives after the index is built, as new voiceprints do.
+        let (built, added) = known.split_at(known.len() - centers.len());
+        let mut index = IvfIndex::build(
+            built.iter().map(|(id, v)| (id.as_str(), v.as_slice())),
+            SimilarityMetric::Cosine,
+        )
+        .expect("enough voiceprints to index");
+        for (id, vector) in added {
+            index.insert(id, vector);
+        }
+
+        let all: Vec<usize> = (0..known.len()).collect();
+        for center in &centers {
+            let query = jitter(&mut rng, center, 0.2);
+            let positions = index
+                .candidate_positions(known.iter().map(|(id, _)| id.as_str()), &query)
+                .unwrap();
+            assert!(positions.len() < known.len());
+            assert_eq!(best(&query, &known, &positions), best(&query, &known, &all));
+        }
+    }
+
+    #[test]
+    fn unindexed_and_removed_ids_are_handled() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let known: Vec<(String, Vec<f32>)> = (0..MIN_INDEXED)
+            .map(|i| {
+                (
+                    i.to_string(),
+                    (0..DIM).map(|_| rng.gen_range(-1.0..1.0)).collect(),
+                )
+            })
+            .collect();
+        let mut index = IvfIndex::build(
+            known.iter().map(|(id, v)| (id.as_str(), v.as_slice())),
+            SimilarityMetric::Cosine,
+        )
+        .unwrap();
+        index.retain(|id| id != "0");
+
+        let query = &known[0].1;
+        let mut ids: Vec<&str> = known.iter().map(|(id, _)| id.as_str()).collect();
+        ids.push("new");
+        let positions = index
+            .candidate_positions(ids.iter().copied(), query)
+            .unwrap();
+        // Neither "0" (removed) nor "new" (never indexed) is known to the index, so both
+        // are always scored.
+        assert!(positions.contains(&0));
+        assert!(positions.contains(&known.len()));
+    }
+}
@@ -0,0 +1,507 @@
+//! Sample-level processing applied to recorded audio.
+
+const GATE_RAMP_MS: f32 = 5.0;
+const GATE_ATTENUATION_DB: f32 = -40.0;
+
+pub fn db_to_linear(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+/// Converts a float sample to 16-bit PCM, clamping out-of-range input instead of wrapping.
+pub fn f32_to_i16(sample: f32) -> i16 {
+    (sample.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct GateSettings {
+    pub threshold_db: f32,
+    pub hold_ms: u64,
+}
+
+/// Attenuates interleaved audio once every channel has stayed below the threshold for
+/// longer than the hold time; gain changes are ramped to avoid clicks.
+pub struct NoiseGate {
+    threshold: f32,
+    hold_frames: u64,
+    channels: usize,
+    attenuation: f32,
+    gain_step: f32,
+    quiet_frames: u64,
+    gain: f32,
+}
+
+impl NoiseGate {
+    pub fn new(settings: GateSettings, sample_rate: u32, channels: u16) -> Self {
+        let ramp_frames = (sample_rate as f32 * GATE_RAMP_MS / 1000.0).max(1.0);
+        Self {
+            threshold: db_to_linear(settings.threshold_db),
+            hold_frames: sample_rate as u64 * settings.hold_ms / 1000,
+            channels: std::cmp::max(channels as usize, 1),
+            attenuation: db_to_linear(GATE_ATTENUATION_DB),
+            gain_step: 1.0 / ramp_frames,
+            quiet_frames: 0,
+            gain: 1.0,
+        }
+    }
+
+    pub fn process(&mut self, samples: &mut [f32]) {
+        for frame in samples.chunks_mut(self.channels) {
+            let level = frame.iter().fold(0f32, |acc, s| acc.max(s.abs()));
+            if level >= self.threshold {
+                self.quiet_frames = 0;
+            } else {
+                self.quiet_frames = self.quiet_frames.saturating_add(1);
+            }
+            let target = if self.quiet_frames > self.hold_frames {
+                self.attenuation
+            } else {
+                1.0
+            };
+            if self.gain < target {
+                self.gain = (self.gain + self.gain_step).min(target);
+            } else if self.gain > target {
+                self.gain = (self.gain - self.gain_step).max(target);
+            }
+            frame.iter_mut().for_each(|s| *s *= self.gain);
+        }
+    }
+
+    pub fn process_i16(&mut self, samples: &mut [i16]) {
+        let mut floats: Vec<f32> = samples.iter().map(|s| *s as f32 / 32768.0).collect();
+        self.process(&mut floats);
+        for (dst, src) in samples.iter_mut().zip(floats) {
+            *dst = (src * 32768.0).clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+        }
+    }
+}
+
+/// RMS level of the samples seen since the last reading, taken at a fixed rate.
+pub struct LevelMeter {
+    interval: std::time::Duration,
+    last_emit: std::time::Instant,
+    sum_squares: f64,
+    count: u64,
+}
+
+impl LevelMeter {
+    pub fn new(hz: u32) -> Self {
+        Self {
+            interval: std::time::Duration::from_secs(1) / hz.max(1),
+            last_emit: std::time::Instant::now(),
+            sum_squares: 0.0,
+            count: 0,
+        }
+    }
+
+    pub fn add(&mut self, samples: impl IntoIterator<Item = f32>) {
+        for sample in samples {
+            self.sum_squares += (sample as f64) * (sample as f64);
+            self.count += 1;
+        }
+    }
+
+    /// The RMS since the previous reading once the interval has elapsed, then resets.
+    /// A window without samples reads as silence.
+    pub fn take_if_due(&mut self) -> Option<f32> {
+        if self.last_emit.elapsed() < self.interval {
+            return None;
+        }
+        let rms = if self.count == 0 {
+            0.0
+        } else {
+            (self.sum_squares / self.count as f64).sqrt() as f32
+        };
+        self.last_emit = std::time::Instant::now();
+        self.sum_squares = 0.0;
+        self.count = 0;
+        Some(rms)
+    }
+}
+
+/// Mono min/max buckets for a live waveform, handed out at a fixed rate.
+pub struct WaveformMeter {
+    interval: std::time::Duration,
+    last_emit: std::time::Instant,
+    channels: usize,
+    frames_per_bucket: usize,
+    /// Channel sum of the frame being read and how many of its samples were seen.
+    frame_sum: f32,
+    frame_len: usize,
+    bucket: (f32, f32),
+    bucket_frames: usize,
+    /// Completed buckets as min/max pairs, like `waveform_peaks`.
+    peaks: Vec<f32>,
+}
+
+/// Horizontal resolution of the live waveform.
+const WAVEFORM_BUCKETS_PER_SEC: u32 = 50;
+
+impl WaveformMeter {
+    pub fn new(sample_rate: u32, channels: u16, hz: u32) -> Self {
+        Self {
+            interval: std::time::Duration::from_secs(1) / hz.max(1),
+            last_emit: std::time::Instant::now(),
+            channels: channels.max(1) as usize,
+            frames_per_bucket: (sample_rate / WAVEFORM_BUCKETS_PER_SEC).max(1) as usize,
+            frame_sum: 0.0,
+            frame_len: 0,
+            bucket: (f32::MAX, f32::MIN),
+            bucket_frames: 0,
+            peaks: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, samples: impl IntoIterator<Item = f32>) {
+        for sample in samples {
+            self.frame_sum += sample;
+            self.frame_len += 1;
+            if self.frame_len < self.channels {
+                continue;
+            }
+            let mono = self.frame_sum / self.channels as f32;
+            self.frame_sum = 0.0;
+            self.frame_len = 0;
+            self.bucket = (self.bucket.0.min(mono), self.bucket.1.max(mono));
+            self.bucket_frames += 1;
+            if self.bucket_frames == self.frames_per_bucket {
+                self.peaks.extend([self.bucket.0, self.bucket.1]);
+                self.bucket = (f32::MAX, f32::MIN);
+                self.bucket_frames = 0;
+            }
+        }
+    }
+
+    /// The buckets completed since the previous reading once the interval has elapsed.
+    pub fn take_if_due(&mut self) -> Option<Vec<f32>> {
+        if self.peaks.is_empty() || self.last_emit.elapsed() < self.interval {
+            return None;
+        }
+        self.last_emit = std::time::Instant::now();
+        Some(std::mem::take(&mut self.peaks))
+    }
+}
+
+/// Samples at least this close to full scale count as clipped; converters often stop just
+/// short of the rail.
+pub const CLIP_LEVEL: f32 = 0.99;
+
+/// Percent of `total` samples that were clipped.
+pub fn clipped_percent(clipped: u64, total: u64) -> f32 {
+    if total == 0 {
+        0.0
+    } else {
+        (clipped as f64 * 100.0 / total as f64) as f32
+    }
+}
+
+/// Share of clipped samples per fixed-size window of audio.
+pub struct ClipMeter {
+    window: u64,
+    clipped: u64,
+    count: u64,
+}
+
+impl ClipMeter {
+    /// `window` is in samples, counting every channel.
+    pub fn new(window: u64) -> Self {
+        Self {
+            window: window.max(1),
+            clipped: 0,
+            count: 0,
+        }
+    }
+
+    /// Returns how many of `samples` were clipped.
+    pub fn add(&mut self, samples: impl IntoIterator<Item = f32>) -> u64 {
+        let mut clipped = 0;
+        for sample in samples {
+            if sample.abs() >= CLIP_LEVEL {
+                clipped += 1;
+            }
+            self.count += 1;
+        }
+        self.clipped += clipped;
+        clipped
+    }
+
+    /// The clipped percent of the window once it is full, then starts the next one.
+    pub fn take_if_due(&mut self) -> Option<f32> {
+        if self.count < self.window {
+            return None;
+        }
+        let percent = clipped_percent(self.clipped, self.count);
+        self.clipped = 0;
+        self.count = 0;
+        Some(percent)
+    }
+}
+
+/// Clipped and total samples (all channels) in a WAV or FLAC file.
+pub fn count_clipped(path: &std::path::Path) -> Result<(u64, u64), String> {
+    let mut meter = ClipMeter::new(u64::MAX);
+    if crate::flac::is_flac(path) {
+        let reader = crate::flac::FlacReader::open(path)?;
+        let scale = reader.info().full_scale();
+        let (mut clipped, mut total) = (0, 0);
+        for sample in reader {
+            clipped += meter.add([sample? as f32 / scale]);
+            total += 1;
+        }
+        return Ok((clipped, total));
+    }
+    let mut reader = hound::WavReader::open(path).map_err(|e| e.to_string())?;
+    let spec = reader.spec();
+    let scale = full_scale(&spec)?;
+    let (mut clipped, mut total) = (0, 0);
+    if spec.sample_format == hound::SampleFormat::Float {
+        for sample in reader.samples::<f32>() {
+            clipped += meter.add([sample.map_err(|e| e.to_string())?]);
+            total += 1;
+        }
+    } else {
+        for sample in reader.samples::<i32>() {
+            clipped += meter.add([sample.map_err(|e| e.to_string())? as f32 / scale]);
+            total += 1;
+        }
+    }
+    Ok((clipped, total))
+}
+
+/// Peaks within this many dB of the target are left untouched.
+const NORMALIZE_TOLERANCE_DB: f32 = 1.0;
+
+/// Divisor that maps a sample read as `i32` (or `f32`) to [-1, 1]. hound recentres 8-bit
+/// WAVs, which are unsigned on disk, around zero, so they scale like the signed depths.
+pub fn full_scale(spec: &hound::WavSpec) -> Result<f32, String> {
+    match (spec.sample_format, spec.bits_per_sample) {
+        (hound::SampleFormat::Float, 32) => Ok(1.0),
+        (hound::SampleFormat::Int, bits @ 8..=32) => Ok((1u64 << (bits - 1)) as f32),
+        _ => Err("Unsupported WAV format".into()),
+    }
+}
+
+fn wav_peak(path: &std::path::Path) -> Result<f32, String> {
+    let mut reader = hound::WavReader::open(path).map_err(|e| e.to_string())?;
+    let spec = reader.spec();
+    let scale = full_scale(&spec)?;
+    let mut peak = 0f32;
+    if spec.sample_format == hound::SampleFormat::Float {
+        for sample in reader.samples::<f32>() {
+            peak = peak.max(sample.map_err(|e| e.to_string())?.abs());
+        }
+    } else {
+        for sample in reader.samples::<i32>() {
+            peak = peak.max(sample.map_err(|e| e.to_string())?.unsigned_abs() as f32 / scale);
+        }
+    }
+    Ok(peak)
+}
+
+/// Writes a copy of the WAV rescaled so its peak sits at `target_db` dBFS.
+///
+/// Returns `None` when the file is silent or already within tolerance of the target.
+pub fn normalize_wav(
+    path: &std::path::Path,
+    target_db: f32,
+) -> Result<Option<std::path::PathBuf>, String> {
+    let peak = wav_peak(path)?;
+    if peak <= 0.0 {
+        return Ok(None);
+    }
+    let peak_db = 20.0 * peak.log10();
+    if (peak_db - target_db).abs() < NORMALIZE_TOLERANCE_DB {
+        return Ok(None);
+    }
+    let gain = db_to_linear(target_db) / peak;
+
+    let mut reader = hound::WavReader::open(path).map_err(|e| e.to_string())?;
+    let spec = reader.spec();
+    let scale = full_scale(&spec)?;
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "recording".into());
+    let output = path.with_file_name(format!("{stem}-normalized.wav"));
+    let mut writer = hound::WavWriter::create(&output, spec).map_err(|e| e.to_string())?;
+    if spec.sample_format == hound::SampleFormat::Float {
+        for sample in reader.samples::<f32>() {
+            let s = sample.map_err(|e| e.to_string())?;
+            writer
+                .write_sample((s * gain).clamp(-1.0, 1.0))
+                .map_err(|e| e.to_string())?;
+        }
+    } else {
+        let (min, max) = (-scale, scale - 1.0);
+        for sample in reader.samples::<i32>() {
+            let s = sample.map_err(|e| e.to_string())? as f32;
+            let scaled = (s * gain).round().clamp(min, max) as i32;
+            writer.write_sample(scaled).map_err(|e| e.to_string())?;
+        }
+    }
+    writer.finalize().map_err(|e| e.to_string())?;
+    Ok(Some(output))
+}
+
+/// Writes the frames between `start_ms` and `end_ms` to a `-trimmed` copy of the WAV,
+/// keeping its format. Returns the new file's path.
+pub fn trim_wav(
+    path: &std::path::Path,
+    start_ms: u64,
+    end_ms: u64,
+) -> Result<std::path::PathBuf, String> {
+    let mut reader = hound::WavReader::open(path).map_err(|e| e.to_string())?;
+    let spec = reader.spec();
+    full_scale(&spec)?;
+    let channels = spec.channels.max(1) as usize;
+    let to_sample = |ms: u64| (ms * spec.sample_rate as u64 / 1000) as usize * channels;
+    let skip = to_sample(start_ms);
+    let take = to_sample(end_ms).saturating_sub(skip);
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "recording".into());
+    let output = path.with_file_name(format!("{stem}-trimmed.wav"));
+    let mut writer = hound::WavWriter::create(&output, spec).map_err(|e| e.to_string())?;
+    if spec.sample_format == hound::SampleFormat::Float {
+        for sample in reader.samples::<f32>().skip(skip).take(take) {
+            let s = sample.map_err(|e| e.to_string())?;
+            writer.write_sample(s).map_err(|e| e.to_string())?;
+        }
+    } else {
+        for sample in reader.samples::<i32>().skip(skip).take(take) {
+            let s = sample.map_err(|e| e.to_string())?;
+            writer.write_sample(s).map_err(|e| e.to_string())?;
+        }
+    }
+    writer.finalize().map_err(|e| e.to_string())?;
+    Ok(output)
+}
+
+/// Min/max of the mono mix per bucket, interleaved as `[min0, max0, min1, max1, ..]`.
+///
+/// Reads the file once; buckets that receive no samples are `0.0`.
+pub fn waveform_peaks(path: &std::path::Path, buckets: usize) -> Result<Vec<f32>, String> {
+    if crate::flac::is_flac(path) {
+        let reader = crate::flac::FlacReader::open(path)?;
+        let scale = reader.info().full_scale();
+        let channels = reader.info().channels.max(1) as usize;
+        let frames = reader.info().total_frames as usize;
+        let samples = reader.map(move |s| s.map(|s| s as f32 / scale));
+        return bucket_peaks(samples, channels, frames, buckets);
+    }
+    let mut reader = hound::WavReader::open(path).map_err(|e| e.to_string())?;
+    let spec = reader.spec();
+    let scale = full_scale(&spec)?;
+    let channels = spec.channels.max(1) as usize;
+    let frames = reader.duration() as usize;
+    let samples: Box<dyn Iterator<Item = Result<f32, hound::Error>>> =
+        if spec.sample_format == hound::SampleFormat::Float {
+            Box::new(reader.samples::<f32>())
+        } else {
+            Box::new(
+                reader
+                    .samples::<i32>()
+                    .map(move |s| s.map(|s| s as f32 / scale)),
+            )
+        };
+    bucket_peaks(
+        samples.map(|s| s.map_err(|e| e.to_string())),
+        channels,
+        frames,
+        buckets,
+    )
+}
+
+fn bucket_peaks(
+    samples: impl Iterator<Item = Result<f32, String>>,
+    channels: usize,
+    frames: usize,
+    buckets: usize,
+) -> Result<Vec<f32>, String> {
+    let frames = frames.max(1);
+    let buckets = buckets.max(1);
+    let mut peaks = vec![(f32::MAX, f32::MIN); buckets];
+    let mut frame = 0usize;
+    let mut sum = 0f32;
+    for (i, sample) in samples.enumerate() {
+        sum += sample?;
+        if (i + 1) % channels != 0 {
+            continue;
+        }
+        let mono = sum / channels as f32;
+        let (min, max) = &mut peaks[(frame * buckets / frames).min(buckets - 1)];
+        *min = min.min(mono);
+        *max = max.max(mono);
+        frame += 1;
+        sum = 0.0;
+    }
+    Ok(peaks
+        .into_iter()
+        .flat_map(|(min, max)| if min > max { [0.0, 0.0] } else { [min, max] })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 16_000;
+
+    fn tone(hz: f32, db: f32, ms: u32) -> Vec<f32> {
+        let amplitude = db_to_linear(db);
+        (0..RATE * ms / 1000)
+            .map(|i| amplitude * (2.0 * std::f32::consts::PI * hz * i as f32 / RATE as f32).sin())
+            .collect()
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples.iter().fold(0f32, |acc, s| acc.max(s.abs()))
+    }
+
+    #[test]
+    fn gate_attenuates_hum_and_passes_speech() {
+        let hum = tone(60.0, -50.0, 1000);
+        let speech = tone(300.0, -6.0, 500);
+        let input: Vec<f32> = [&hum[..], &speech[..], &hum[..]].concat();
+        let mut output = input.clone();
+        let settings = GateSettings {
+            threshold_db: -40.0,
+            hold_ms: 100,
+        };
+        NoiseGate::new(settings, RATE, 1).process(&mut output);
+
+        let ms = |t: u32| (RATE * t / 1000) as usize;
+        // Hum is let through for the hold time, then pushed down by the full attenuation.
+        assert_eq!(output[..ms(90)], input[..ms(90)]);
+        let gated = peak(&output[ms(200)..ms(1000)]) / peak(&input[ms(200)..ms(1000)]);
+        assert!(gated < db_to_linear(-35.0), "hum only fell to {gated}");
+        // Speech comes back to full gain within the ramp.
+        for (out, inp) in output[ms(1010)..ms(1500)]
+            .iter()
+            .zip(&input[ms(1010)..ms(1500)])
+        {
+            assert!((out - inp).abs() < 1e-6);
+        }
+        // The hum trailing the speech is kept until the hold runs out again.
+        assert_eq!(output[ms(1500)..ms(1590)], input[ms(1500)..ms(1590)]);
+        assert!(peak(&output[ms(1700)..]) < db_to_linear(-85.0));
+    }
+
+    #[test]
+    fn gate_needs_every_channel_quiet() {
+        let quiet = tone(60.0, -50.0, 500);
+        let loud = tone(300.0, -6.0, 500);
+        let mut interleaved: Vec<f32> = quiet
+            .iter()
+            .zip(&loud)
+            .flat_map(|(q, l)| [*q, *l])
+            .collect();
+        let input = interleaved.clone();
+        let settings = GateSettings {
+            threshold_db: -40.0,
+            hold_ms: 100,
+        };
+        NoiseGate::new(settings, RATE, 2).process(&mut interleaved);
+        assert_eq!(interleaved, input);
+    }
+}
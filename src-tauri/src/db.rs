@@ -1,4 +1,8 @@
-use std::{collections::HashSet, path::Path, time::SystemTime};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::Path,
+    time::SystemTime,
+};
 
 use aes_gcm::{
     aead::{Aead, KeyInit, OsRng},
@@ -8,11 +12,27 @@ use argon2::{password_hash::SaltString, Argon2};
 use base64::{engine::general_purpose, Engine as _};
 use chrono::{DateTime, Utc};
 use rand::RngCore;
-use rusqlite::{params, Connection, OptionalExtension};
-use serde::Serialize;
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 use zeroize::Zeroize;
 
+use crate::{ann::IvfIndex, embedding::SimilarityMetric};
+
+const SELF_TEST_PLAINTEXT: &[u8] = b"recall encryption self-test";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EncryptionSelfTest {
+    /// Whether a key is loaded; nothing is tested otherwise.
+    pub enabled: bool,
+    /// The known plaintext came back unchanged from an encrypt/decrypt round-trip.
+    pub ok: bool,
+    pub algorithm: Option<&'static str>,
+    pub kdf: Option<&'static str>,
+    pub error: Option<String>,
+}
+
 #[derive(Clone)]
 pub struct Crypto {
     key: Option<aes_gcm::Key<Aes256Gcm>>,
@@ -76,6 +96,52 @@ impl Crypto {
         }
     }
 
+    /// Keyed digest of a search token, so an encrypted DB's index doesn't store words.
+    /// Unencrypted DBs keep the token as-is.
+    pub fn blind_token(&self, token: &str) -> String {
+        match &self.key {
+            Some(key) => {
+                let mut hasher = Sha256::new();
+                hasher.update(key.as_slice());
+                hasher.update(token.as_bytes());
+                format!("{:x}", hasher.finalize())
+            }
+            None => token.to_string(),
+        }
+    }
+
+    /// Encrypts a known string and decrypts it again with the loaded key.
+    pub fn self_test(&self) -> EncryptionSelfTest {
+        if self.key.is_none() {
+            return EncryptionSelfTest {
+                enabled: false,
+                ok: false,
+                algorithm: None,
+                kdf: None,
+                error: None,
+            };
+        }
+        let (nonce, ct) = self.encrypt(SELF_TEST_PLAINTEXT);
+        let result = self.decrypt(&nonce, &ct).and_then(|plain| {
+            if plain != SELF_TEST_PLAINTEXT {
+                Err("Decrypted text does not match".to_string())
+            } else if general_purpose::STANDARD.decode(&ct).ok().as_deref()
+                == Some(SELF_TEST_PLAINTEXT)
+            {
+                Err("Ciphertext equals the plaintext".to_string())
+            } else {
+                Ok(())
+            }
+        });
+        EncryptionSelfTest {
+            enabled: true,
+            ok: result.is_ok(),
+            algorithm: Some("AES-256-GCM"),
+            kdf: Some("argon2id"),
+            error: result.err(),
+        }
+    }
+
     pub fn salt(&self) -> Option<String> {
         self.salt.clone()
     }
@@ -85,6 +151,114 @@ pub struct Db {
     conn: std::sync::Mutex<Connection>,
     crypto: Crypto,
     pub encrypted: bool,
+    /// Store new embeddings as int8 with a per-vector scale instead of raw f32.
+    pub compress_embeddings: bool,
+    /// Prune a speaker's embeddings down to this many after each insert; unbounded when `None`.
+    pub max_embeddings_per_speaker: Option<usize>,
+    /// Maintain the `search_index` token table for transcripts (see [`Db::search_sessions`]).
+    pub plaintext_search_index: bool,
+    /// At most this many decrypted vectors are kept in `vector_cache`.
+    pub embedding_cache_size: usize,
+    /// Decrypted embedding vectors by id, so matching doesn't decrypt every voiceprint on
+    /// each transcription. A row's vector never changes under the same id, so inserts and
+    /// deletes are picked up by the next full read without invalidating anything.
+    vector_cache: std::sync::Mutex<VectorCache>,
+    /// Approximate index over the stored voiceprints while `ann_matching` is on; kept
+    /// across transcriptions and extended by [`Db::insert_embedding`].
+    ann_index: std::sync::Mutex<Option<IvfIndex>>,
+}
+
+const VECTOR_FORMAT_F32: &str = "f32";
+const VECTOR_FORMAT_Q8: &str = "q8";
+
+/// Decrypted vectors by embedding id, evicting the least recently used when full.
+#[derive(Default)]
+struct VectorCache {
+    entries: HashMap<String, (Vec<f32>, u64)>,
+    /// Ids by when they were last used, oldest first.
+    by_use: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+impl VectorCache {
+    fn get(&mut self, id: &str) -> Option<&[f32]> {
+        let (vector, used) = self.entries.get_mut(id)?;
+        self.tick += 1;
+        let id = self.by_use.remove(used).unwrap_or_else(|| id.to_string());
+        self.by_use.insert(self.tick, id);
+        *used = self.tick;
+        Some(vector)
+    }
+
+    /// Adds `vector`, first evicting the least recently used entries down to `capacity - 1`.
+    fn insert(&mut self, id: String, vector: Vec<f32>, capacity: usize) {
+        if capacity == 0 {
+            return;
+        }
+        while self.entries.len() >= capacity {
+            let Some((_, oldest)) = self.by_use.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+        self.tick += 1;
+        self.by_use.insert(self.tick, id.clone());
+        self.entries.insert(id, (vector, self.tick));
+    }
+
+    fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.entries.retain(|id, _| keep(id));
+        let entries = &self.entries;
+        self.by_use.retain(|_, id| entries.contains_key(id));
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.by_use.clear();
+    }
+}
+
+/// Layout: little-endian f32 scale followed by one i8 per dimension.
+fn quantize_q8(vector: &[f32]) -> Vec<u8> {
+    let max_abs = vector.iter().fold(0f32, |acc, v| acc.max(v.abs()));
+    let scale = if max_abs > 0.0 { max_abs / 127.0 } else { 1.0 };
+    let mut bytes = Vec::with_capacity(4 + vector.len());
+    bytes.extend_from_slice(&scale.to_le_bytes());
+    bytes.extend(
+        vector
+            .iter()
+            .map(|v| (v / scale).round().clamp(-127.0, 127.0) as i8 as u8),
+    );
+    bytes
+}
+
+/// Lowercased alphanumeric words of `text`, each once.
+fn search_tokens(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn dequantize_q8(bytes: &[u8]) -> Option<Vec<f32>> {
+    if bytes.len() < 4 {
+        return None;
+    }
+    let scale = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    Some(bytes[4..].iter().map(|b| *b as i8 as f32 * scale).collect())
+}
+
+/// Mean cosine similarity over all pairs of same-length vectors; `None` below two.
+fn cohesion(vectors: &[Vec<f32>]) -> Option<f32> {
+    let mut total = 0f32;
+    let mut pairs = 0usize;
+    for (i, a) in vectors.iter().enumerate() {
+        for b in vectors[i + 1..].iter().filter(|b| b.len() == a.len()) {
+            total += crate::embedding::cosine_similarity(a, b);
+            pairs += 1;
+        }
+    }
+    (pairs > 0).then(|| total / pairs as f32)
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -99,6 +273,11 @@ pub struct Speaker {
     pub id: String,
     pub label: Option<String>,
     pub created_at: DateTime<Utc>,
+    /// Stored voiceprints for this speaker.
+    pub embedding_count: usize,
+    /// Mean cosine similarity over pairs of the speaker's voiceprints. A low value with
+    /// many voiceprints suggests the profile mixes up several voices. Only computed on request.
+    pub cohesion: Option<f32>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -110,6 +289,146 @@ pub struct SegmentRecord {
     pub speaker_id: Option<String>,
     pub speaker_label: Option<String>,
     pub text: String,
+    /// Left out of voiceprint learning (music, crosstalk).
+    pub exclude_from_embedding: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SegmentMatch {
+    #[serde(flatten)]
+    pub segment: SegmentRecord,
+    /// When the segment's session was recorded.
+    pub created_at: DateTime<Utc>,
+}
+
+/// Joins the non-empty segment texts with `separator`, each prefixed by `speaker_prefix`
+/// (with `{label}` filled in) when given and the segment has a label.
+pub fn join_segments(
+    segments: &[SegmentRecord],
+    speaker_prefix: Option<&str>,
+    separator: &str,
+) -> String {
+    segments
+        .iter()
+        .filter(|seg| !seg.text.trim().is_empty())
+        .map(|seg| match (&seg.speaker_label, speaker_prefix) {
+            (Some(label), Some(prefix)) => {
+                format!("{}{}", prefix.replace("{label}", label), seg.text.trim())
+            }
+            _ => seg.text.trim().to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TableCounts {
+    pub sessions: i64,
+    pub segments: i64,
+    pub speakers: i64,
+    pub embeddings: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GlobalSpeakerStats {
+    /// `None` for the bucket of segments without a speaker.
+    pub speaker_id: Option<String>,
+    /// `"unknown"` for segments without a speaker.
+    pub label: String,
+    pub talk_time_ms: i64,
+    pub session_count: i64,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionSpeaker {
+    /// `None` for segments that were never matched to a speaker profile.
+    pub speaker_id: Option<String>,
+    pub speaker_label: Option<String>,
+    /// Start of the speaker's first segment in the session.
+    pub first_ms: i64,
+    pub talk_time_ms: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TimelineEntry {
+    pub session_id: String,
+    pub created_at: DateTime<Utc>,
+    /// End of the last segment, i.e. the transcribed length of the session.
+    pub duration_ms: i64,
+    pub segment_count: i64,
+    pub speakers: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UndoSummary {
+    pub session_id: String,
+    pub segments_removed: usize,
+    pub embeddings_removed: usize,
+    pub speakers_removed: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OrphanRepair {
+    /// Segments removed because their session no longer exists.
+    pub segments_deleted: usize,
+    /// Segments whose missing speaker was unset.
+    pub speaker_refs_cleared: usize,
+}
+
+/// How a stored text column is held on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextStorage {
+    /// AES-GCM ciphertext with its nonce.
+    Encrypted,
+    /// Base64 of the plaintext, written while encryption was off.
+    Plaintext,
+    /// No text stored.
+    Empty,
+}
+
+impl TextStorage {
+    fn of(nonce: Option<&str>, ct: &str) -> Self {
+        if ct.is_empty() {
+            TextStorage::Empty
+        } else if nonce.is_some_and(|n| !n.is_empty()) {
+            TextStorage::Encrypted
+        } else {
+            TextStorage::Plaintext
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    /// The SQL keyword; only these fixed strings ever reach a query.
+    fn sql(self) -> &'static str {
+        match self {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionEncryptionInfo {
+    pub session_id: String,
+    /// Whether the open database has a key; rows can still be plaintext if they were
+    /// written before encryption was enabled.
+    pub db_encrypted: bool,
+    pub transcript: TextStorage,
+    pub segments_encrypted: usize,
+    pub segments_plaintext: usize,
+    pub segments_empty: usize,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -118,7 +437,31 @@ pub struct StoredEmbedding {
     pub speaker_id: String,
     pub speaker_label: Option<String>,
     pub vector: Vec<f32>,
-    pub source_session_id: String,
+    /// Session the voiceprint was learned from; `None` for imported ones.
+    pub source_session_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A voice from a session that came close to a known speaker without clearly matching.
+/// Its segments stay unassigned until the user merges it or makes it a new speaker.
+#[derive(Debug, Clone, Serialize)]
+pub struct SpeakerReview {
+    pub id: String,
+    pub session_id: String,
+    /// Label the diarization gave the voice; its segments carry it until resolved.
+    pub speaker_label: String,
+    /// Closest known speaker; `None` once that speaker has been deleted.
+    pub candidate_speaker_id: Option<String>,
+    pub candidate_label: Option<String>,
+    pub score: f32,
+    pub created_at: DateTime<Utc>,
+}
+
+/// One stored voiceprint without its vector, for picking out a bad one.
+#[derive(Debug, Clone, Serialize)]
+pub struct EmbeddingRef {
+    pub id: String,
+    pub source_session_id: Option<String>,
     pub created_at: DateTime<Utc>,
 }
 
@@ -130,12 +473,117 @@ impl Db {
             conn: std::sync::Mutex::new(conn),
             crypto,
             encrypted,
+            compress_embeddings: false,
+            max_embeddings_per_speaker: None,
+            plaintext_search_index: false,
+            embedding_cache_size: 0,
+            vector_cache: std::sync::Mutex::new(VectorCache::default()),
+            ann_index: std::sync::Mutex::new(None),
         };
         db.init_schema()?;
         db.persist_salt_if_missing()?;
         Ok(db)
     }
 
+    /// Opens a second, read-only connection to the same database with this handle's key.
+    ///
+    /// Nothing can be written through it, and reads don't contend with the main connection.
+    pub fn open_readonly(&self, path: impl AsRef<Path>) -> Result<Self, String> {
+        let conn = Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(Db {
+            conn: std::sync::Mutex::new(conn),
+            crypto: self.crypto.clone(),
+            encrypted: self.encrypted,
+            compress_embeddings: self.compress_embeddings,
+            max_embeddings_per_speaker: self.max_embeddings_per_speaker,
+            plaintext_search_index: self.plaintext_search_index,
+            embedding_cache_size: 0,
+            vector_cache: std::sync::Mutex::new(VectorCache::default()),
+            ann_index: std::sync::Mutex::new(None),
+        })
+    }
+
+    /// Writes a consistent copy of the database to `path`, which must not exist yet.
+    /// Text and embeddings stay sealed, and the salt travels with the copy, so an
+    /// encrypted backup opens with the same password.
+    pub fn backup_to(&self, path: &Path) -> Result<(), String> {
+        let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        conn.execute("VACUUM INTO ?1", params![path.to_string_lossy()])
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Reports how a session's transcript and segment text are stored, judged by whether
+    /// each row carries a nonce. Nothing is decrypted.
+    pub fn session_encryption_info(
+        &self,
+        session_id: &str,
+    ) -> Result<SessionEncryptionInfo, String> {
+        let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        let (nonce, ct): (Option<String>, String) = conn
+            .query_row(
+                "SELECT transcript_nonce, transcript_ct FROM sessions WHERE id=?1",
+                params![session_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Session not found: {session_id}"))?;
+        let mut info = SessionEncryptionInfo {
+            session_id: session_id.to_string(),
+            db_encrypted: self.encrypted,
+            transcript: TextStorage::of(nonce.as_deref(), &ct),
+            segments_encrypted: 0,
+            segments_plaintext: 0,
+            segments_empty: 0,
+        };
+        let mut stmt = conn
+            .prepare("SELECT text_nonce, text_ct FROM segments WHERE session_id=?1")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![session_id], |row| {
+                let nonce: Option<String> = row.get(0)?;
+                let ct: String = row.get(1)?;
+                Ok((nonce, ct))
+            })
+            .map_err(|e| e.to_string())?;
+        for row in rows {
+            let (nonce, ct) = row.map_err(|e| e.to_string())?;
+            match TextStorage::of(nonce.as_deref(), &ct) {
+                TextStorage::Encrypted => info.segments_encrypted += 1,
+                TextStorage::Plaintext => info.segments_plaintext += 1,
+                TextStorage::Empty => info.segments_empty += 1,
+            }
+        }
+        Ok(info)
+    }
+
+    pub fn encryption_self_test(&self) -> EncryptionSelfTest {
+        self.crypto.self_test()
+    }
+
+    /// Encrypts transcript/segment text. Empty text is stored as an empty ciphertext, so
+    /// speakers-only sessions keep no encrypted payload at all.
+    fn seal_text(&self, text: &str) -> (String, String) {
+        if text.is_empty() {
+            (String::new(), String::new())
+        } else {
+            self.crypto.encrypt(text.as_bytes())
+        }
+    }
+
+    fn open_text(&self, nonce: &str, ct: &str) -> Result<String, String> {
+        if ct.is_empty() {
+            return Ok(String::new());
+        }
+        let bytes = self.crypto.decrypt(nonce, ct)?;
+        Ok(String::from_utf8(bytes).unwrap_or_default())
+    }
+
     pub fn load_existing_salt(path: impl AsRef<Path>) -> Result<Option<String>, String> {
         let conn = Connection::open(path).map_err(|e| e.to_string())?;
         let mut stmt = match conn.prepare("SELECT value FROM meta WHERE key='salt'") {
@@ -154,6 +602,28 @@ impl Db {
         Ok(salt_opt)
     }
 
+    /// Runs SQLite's `quick_check` on the file at `path` without opening it as a [`Db`],
+    /// so it works before the password is known. `Ok(Some(_))` describes the corruption;
+    /// `Err` is a failure to check at all (busy, unreadable), which says nothing about
+    /// the file's contents.
+    pub fn check_integrity(path: impl AsRef<Path>) -> Result<Option<String>, String> {
+        let result = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .and_then(|conn| conn.query_row("PRAGMA quick_check", [], |row| row.get(0)));
+        match result {
+            Ok(result) if result == "ok" => Ok(None),
+            Ok(result) => Ok(Some(result)),
+            Err(rusqlite::Error::SqliteFailure(err, message))
+                if matches!(
+                    err.code,
+                    rusqlite::ErrorCode::DatabaseCorrupt | rusqlite::ErrorCode::NotADatabase
+                ) =>
+            {
+                Ok(Some(message.unwrap_or_else(|| err.to_string())))
+            }
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
     fn init_schema(&self) -> Result<(), String> {
         let conn_guard = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
         conn_guard
@@ -187,12 +657,59 @@ impl Db {
                     speaker_id TEXT,
                     text_nonce TEXT,
                     text_ct TEXT NOT NULL
+                 );
+                 CREATE TABLE IF NOT EXISTS search_index (
+                    token TEXT NOT NULL,
+                    session_id TEXT NOT NULL,
+                    PRIMARY KEY (token, session_id)
+                 );
+                 CREATE INDEX IF NOT EXISTS idx_search_index_session ON search_index(session_id);
+                 CREATE TABLE IF NOT EXISTS speaker_reviews (
+                    id TEXT PRIMARY KEY,
+                    session_id TEXT NOT NULL,
+                    speaker_label TEXT NOT NULL,
+                    candidate_speaker_id TEXT,
+                    score REAL NOT NULL,
+                    vector_nonce TEXT,
+                    vector_ct TEXT NOT NULL,
+                    created_at TEXT NOT NULL
                  );",
             )
             .map_err(|e| e.to_string())?;
 
         Self::add_column_if_missing(&conn_guard, "segments", "speaker_id", "TEXT")?;
         Self::add_column_if_missing(&conn_guard, "segments", "speaker_label", "TEXT")?;
+        Self::add_column_if_missing(&conn_guard, "embeddings", "vector_format", "TEXT")?;
+        Self::add_column_if_missing(&conn_guard, "sessions", "client_request_id", "TEXT")?;
+        Self::add_column_if_missing(&conn_guard, "sessions", "audio_path", "TEXT")?;
+        Self::add_column_if_missing(&conn_guard, "sessions", "thumbnail_nonce", "TEXT")?;
+        Self::add_column_if_missing(&conn_guard, "sessions", "thumbnail_ct", "TEXT")?;
+        Self::add_column_if_missing(&conn_guard, "sessions", "fingerprint", "TEXT")?;
+        Self::add_column_if_missing(
+            &conn_guard,
+            "sessions",
+            "speaker_prefixed",
+            "INTEGER NOT NULL DEFAULT 0",
+        )?;
+        Self::add_column_if_missing(
+            &conn_guard,
+            "segments",
+            "exclude_from_embedding",
+            "INTEGER NOT NULL DEFAULT 0",
+        )?;
+        conn_guard
+            .execute(
+                "CREATE UNIQUE INDEX IF NOT EXISTS idx_sessions_client_request_id ON sessions(client_request_id)",
+                [],
+            )
+            .map_err(|e| e.to_string())?;
+        // Imports used to record an empty source session.
+        conn_guard
+            .execute(
+                "UPDATE embeddings SET source_session_id=NULL WHERE source_session_id=''",
+                [],
+            )
+            .map_err(|e| e.to_string())?;
         Ok(())
     }
 
@@ -256,19 +773,78 @@ impl Db {
         Ok(salt_opt)
     }
 
-    pub fn insert_session(&self, transcript: &str) -> Result<String, String> {
+    pub fn table_counts(&self) -> Result<TableCounts, String> {
+        let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        let count = |table: &str| -> Result<i64, String> {
+            conn.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+                row.get(0)
+            })
+            .map_err(|e| e.to_string())
+        };
+        Ok(TableCounts {
+            sessions: count("sessions")?,
+            segments: count("segments")?,
+            speakers: count("speakers")?,
+            embeddings: count("embeddings")?,
+        })
+    }
+
+    /// Runs `f` inside a savepoint, so everything it writes is committed together or, if
+    /// it fails, rolled back. Methods called from `f` must not open a transaction.
+    pub fn in_savepoint<T>(&self, f: impl FnOnce(&Db) -> Result<T, String>) -> Result<T, String> {
+        let exec = |sql: &str| {
+            self.conn
+                .lock()
+                .map_err(|_| "lock poisoned".to_string())?
+                .execute_batch(sql)
+                .map_err(|e| e.to_string())
+        };
+        exec("SAVEPOINT pipeline")?;
+        match f(self) {
+            Ok(value) => {
+                exec("RELEASE pipeline")?;
+                Ok(value)
+            }
+            Err(e) => {
+                exec("ROLLBACK TO pipeline; RELEASE pipeline")?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Inserts a session, returning its id and whether a new row was created.
+    ///
+    /// When `client_request_id` matches an existing session, nothing is written and that
+    /// session's id is returned, so retried jobs don't create duplicates.
+    pub fn insert_session(
+        &self,
+        transcript: &str,
+        client_request_id: Option<&str>,
+    ) -> Result<(String, bool), String> {
         let id = Uuid::new_v4().to_string();
         let now: DateTime<Utc> = SystemTime::now().into();
-        let (nonce, ct) = self.crypto.encrypt(transcript.as_bytes());
-        self.conn
-            .lock()
-            .map_err(|_| "lock poisoned".to_string())?
+        let (nonce, ct) = self.seal_text(transcript);
+        let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        let inserted = conn
             .execute(
-                "INSERT INTO sessions(id, created_at, transcript_nonce, transcript_ct) VALUES(?1, ?2, ?3, ?4)",
-                params![id, now.to_rfc3339(), nonce, ct],
+                "INSERT OR IGNORE INTO sessions(id, created_at, transcript_nonce, transcript_ct, client_request_id) VALUES(?1, ?2, ?3, ?4, ?5)",
+                params![id, now.to_rfc3339(), nonce, ct, client_request_id],
             )
             .map_err(|e| e.to_string())?;
-        Ok(id)
+        if inserted > 0 {
+            if self.plaintext_search_index {
+                self.index_transcript(&conn, &id, transcript)?;
+            }
+            return Ok((id, true));
+        }
+        let existing: String = conn
+            .query_row(
+                "SELECT id FROM sessions WHERE client_request_id=?1",
+                params![client_request_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        Ok((existing, false))
     }
 
     pub fn delete_session(&self, session_id: &str) -> Result<(), String> {
@@ -285,191 +861,1594 @@ impl Db {
             params![session_id],
         )
         .map_err(|e| e.to_string())?;
+        conn.execute(
+            "DELETE FROM search_index WHERE session_id=?1",
+            params![session_id],
+        )
+        .map_err(|e| e.to_string())?;
+        conn.execute(
+            "DELETE FROM speaker_reviews WHERE session_id=?1",
+            params![session_id],
+        )
+        .map_err(|e| e.to_string())?;
         Ok(())
     }
 
-    pub fn update_session_transcript(
-        &self,
-        session_id: &str,
-        transcript: &str,
-    ) -> Result<(), String> {
-        let (nonce, ct) = self.crypto.encrypt(transcript.as_bytes());
-        self.conn
-            .lock()
-            .map_err(|_| "lock poisoned".to_string())?
-            .execute(
-                "UPDATE sessions SET transcript_nonce=?1, transcript_ct=?2 WHERE id=?3",
-                params![nonce, ct, session_id],
+    pub fn delete_sessions(&self, session_ids: &[String]) -> Result<usize, String> {
+        let mut conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let mut removed = 0;
+        for session_id in session_ids {
+            removed += tx
+                .execute("DELETE FROM sessions WHERE id=?1", params![session_id])
+                .map_err(|e| e.to_string())?;
+            tx.execute(
+                "DELETE FROM segments WHERE session_id=?1",
+                params![session_id],
             )
             .map_err(|e| e.to_string())?;
-        Ok(())
+            tx.execute(
+                "DELETE FROM embeddings WHERE source_session_id=?1",
+                params![session_id],
+            )
+            .map_err(|e| e.to_string())?;
+            tx.execute(
+                "DELETE FROM search_index WHERE session_id=?1",
+                params![session_id],
+            )
+            .map_err(|e| e.to_string())?;
+            tx.execute(
+                "DELETE FROM speaker_reviews WHERE session_id=?1",
+                params![session_id],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(removed)
     }
 
-    pub fn list_sessions(&self) -> Result<Vec<Session>, String> {
+    /// Ids of sessions created before `cutoff`, oldest first.
+    pub fn sessions_created_before(&self, cutoff: DateTime<Utc>) -> Result<Vec<String>, String> {
         let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
         let mut stmt = conn
-            .prepare("SELECT id, created_at, transcript_nonce, transcript_ct FROM sessions ORDER BY created_at DESC")
+            .prepare("SELECT id, created_at FROM sessions ORDER BY created_at ASC")
             .map_err(|e| e.to_string())?;
         let rows = stmt
             .query_map([], |row| {
-                let id: String = row.get(0)?;
-                let created_at: String = row.get(1)?;
-                let nonce: String = row.get(2)?;
-                let ct: String = row.get(3)?;
-                Ok((id, created_at, nonce, ct))
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
             })
             .map_err(|e| e.to_string())?;
-
-        let mut sessions = Vec::new();
+        let mut ids = Vec::new();
         for row in rows {
-            let (id, created_at, nonce, ct) = row.map_err(|e| e.to_string())?;
+            let (id, created_at) = row.map_err(|e| e.to_string())?;
             let ts = DateTime::parse_from_rfc3339(&created_at)
                 .map_err(|e| e.to_string())?
                 .with_timezone(&Utc);
-            let transcript_bytes = self.crypto.decrypt(&nonce, &ct)?;
-            let transcript = String::from_utf8(transcript_bytes).unwrap_or_default();
-            sessions.push(Session {
-                id,
-                created_at: ts,
-                transcript,
-            });
+            if ts < cutoff {
+                ids.push(id);
+            }
         }
-        Ok(sessions)
+        Ok(ids)
     }
 
-    pub fn insert_segment(
+    /// Sessions created in `[from, to)`, oldest first, with durations and speaker labels
+    /// taken from segment metadata only (nothing is decrypted).
+    pub fn timeline(
         &self,
-        session_id: &str,
-        start_ms: i64,
-        end_ms: i64,
-        speaker_id: Option<&str>,
-        speaker_label: Option<&str>,
-        text: &str,
-    ) -> Result<String, String> {
-        let id = Uuid::new_v4().to_string();
-        let (nonce, ct) = self.crypto.encrypt(text.as_bytes());
-        self.conn
-            .lock()
-            .map_err(|_| "lock poisoned".to_string())?
-            .execute(
-                "INSERT INTO segments(id, session_id, start_ms, end_ms, speaker_label, speaker_id, text_nonce, text_ct) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-                params![id, session_id, start_ms, end_ms, speaker_label, speaker_id, nonce, ct],
-            )
-            .map_err(|e| e.to_string())?;
-        Ok(id)
-    }
-
-    pub fn list_segments(&self, session_id: &str) -> Result<Vec<SegmentRecord>, String> {
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<TimelineEntry>, String> {
         let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
         let mut stmt = conn
-            .prepare("SELECT id, session_id, start_ms, end_ms, speaker_id, speaker_label, text_nonce, text_ct FROM segments WHERE session_id=?1 ORDER BY start_ms ASC")
+            .prepare(
+                "SELECT s.id, s.created_at, COALESCE(MAX(g.end_ms), 0), COUNT(g.id)
+                 FROM sessions s LEFT JOIN segments g ON g.session_id = s.id
+                 GROUP BY s.id
+                 ORDER BY s.created_at ASC",
+            )
             .map_err(|e| e.to_string())?;
         let rows = stmt
-            .query_map(params![session_id], |row| {
-                let id: String = row.get(0)?;
-                let session_id: String = row.get(1)?;
-                let start_ms: i64 = row.get(2)?;
-                let end_ms: i64 = row.get(3)?;
-                let speaker_id: Option<String> = row.get(4)?;
-                let speaker_label: Option<String> = row.get(5)?;
-                let nonce: String = row.get(6)?;
-                let ct: String = row.get(7)?;
-                Ok((id, session_id, start_ms, end_ms, speaker_id, speaker_label, nonce, ct))
-            })
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, i64>(3)?,
+                ))
+            })
+            .map_err(|e| e.to_string())?;
+        let mut entries = Vec::new();
+        for row in rows {
+            let (session_id, created_at, duration_ms, segment_count) =
+                row.map_err(|e| e.to_string())?;
+            let created_at = DateTime::parse_from_rfc3339(&created_at)
+                .map_err(|e| e.to_string())?
+                .with_timezone(&Utc);
+            if created_at < from || created_at >= to {
+                continue;
+            }
+            entries.push(TimelineEntry {
+                session_id,
+                created_at,
+                duration_ms,
+                segment_count,
+                speakers: Vec::new(),
+            });
+        }
+
+        let mut speaker_stmt = conn
+            .prepare(
+                "SELECT DISTINCT speaker_label FROM segments
+                 WHERE session_id=?1 AND speaker_label IS NOT NULL
+                 ORDER BY speaker_label ASC",
+            )
+            .map_err(|e| e.to_string())?;
+        for entry in entries.iter_mut() {
+            let labels = speaker_stmt
+                .query_map(params![entry.session_id], |row| row.get::<_, String>(0))
+                .map_err(|e| e.to_string())?;
+            for label in labels {
+                entry.speakers.push(label.map_err(|e| e.to_string())?);
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Talk time, session count and first/last session date per speaker across all
+    /// sessions, most talk time first. Segments without a speaker form one "unknown" entry.
+    pub fn global_speaker_stats(&self) -> Result<Vec<GlobalSpeakerStats>, String> {
+        let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT g.speaker_id, COALESCE(sp.label, MAX(g.speaker_label)),
+                        SUM(MAX(g.end_ms - g.start_ms, 0)), COUNT(DISTINCT g.session_id),
+                        MIN(s.created_at), MAX(s.created_at)
+                 FROM segments g
+                 JOIN sessions s ON s.id = g.session_id
+                 LEFT JOIN speakers sp ON sp.id = g.speaker_id
+                 GROUP BY g.speaker_id
+                 ORDER BY 3 DESC",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, Option<String>>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, i64>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, String>(5)?,
+                ))
+            })
+            .map_err(|e| e.to_string())?;
+        let parse = |ts: &str| {
+            DateTime::parse_from_rfc3339(ts)
+                .map(|ts| ts.with_timezone(&Utc))
+                .map_err(|e| e.to_string())
+        };
+        let mut stats = Vec::new();
+        for row in rows {
+            let (speaker_id, label, talk_time_ms, session_count, first_seen, last_seen) =
+                row.map_err(|e| e.to_string())?;
+            let label = match (&speaker_id, label) {
+                (Some(_), Some(label)) => label,
+                (Some(id), None) => id.clone(),
+                (None, _) => "unknown".to_string(),
+            };
+            stats.push(GlobalSpeakerStats {
+                speaker_id,
+                label,
+                talk_time_ms,
+                session_count,
+                first_seen: parse(&first_seen)?,
+                last_seen: parse(&last_seen)?,
+            });
+        }
+        Ok(stats)
+    }
+
+    /// Distinct speakers of a session in order of first appearance. Unmatched segments
+    /// are grouped by their label.
+    pub fn session_speakers(&self, session_id: &str) -> Result<Vec<SessionSpeaker>, String> {
+        let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT g.speaker_id, COALESCE(sp.label, MAX(g.speaker_label)), MIN(g.start_ms),
+                        SUM(MAX(g.end_ms - g.start_ms, 0))
+                 FROM segments g
+                 LEFT JOIN speakers sp ON sp.id = g.speaker_id
+                 WHERE g.session_id=?1
+                 GROUP BY g.speaker_id, CASE WHEN g.speaker_id IS NULL THEN g.speaker_label END
+                 ORDER BY 3 ASC",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![session_id], |row| {
+                Ok(SessionSpeaker {
+                    speaker_id: row.get(0)?,
+                    speaker_label: row.get(1)?,
+                    first_ms: row.get(2)?,
+                    talk_time_ms: row.get(3)?,
+                })
+            })
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())
+    }
+
+    /// The matched speaker with the most talk time in a session. Ties go to whoever spoke
+    /// first, then to the lower id. `None` when no segment has a speaker profile.
+    pub fn primary_speaker(&self, session_id: &str) -> Result<Option<SessionSpeaker>, String> {
+        Ok(self
+            .session_speakers(session_id)?
+            .into_iter()
+            .filter(|speaker| speaker.speaker_id.is_some())
+            .min_by(|a, b| {
+                b.talk_time_ms
+                    .cmp(&a.talk_time_ms)
+                    .then(a.first_ms.cmp(&b.first_ms))
+                    .then_with(|| a.speaker_id.cmp(&b.speaker_id))
+            }))
+    }
+
+    /// Removes a session together with what its transcription created: segments, the
+    /// embeddings it contributed and speakers introduced by it that are now unused.
+    pub fn undo_session(&self, session_id: &str) -> Result<UndoSummary, String> {
+        let mut conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        tx.query_row(
+            "SELECT 1 FROM sessions WHERE id=?1",
+            params![session_id],
+            |_| Ok(()),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Session not found".to_string())?;
+        let segments_removed = tx
+            .execute(
+                "DELETE FROM segments WHERE session_id=?1",
+                params![session_id],
+            )
+            .map_err(|e| e.to_string())?;
+        // Speakers the session contributed voiceprints to; any it introduced are among them.
+        let speaker_ids: Vec<String> = {
+            let mut stmt = tx
+                .prepare("SELECT DISTINCT speaker_id FROM embeddings WHERE source_session_id=?1")
+                .map_err(|e| e.to_string())?;
+            let rows = stmt
+                .query_map(params![session_id], |row| row.get(0))
+                .map_err(|e| e.to_string())?;
+            rows.collect::<Result<_, _>>().map_err(|e| e.to_string())?
+        };
+        let embeddings_removed = tx
+            .execute(
+                "DELETE FROM embeddings WHERE source_session_id=?1",
+                params![session_id],
+            )
+            .map_err(|e| e.to_string())?;
+        let mut speakers_removed = 0;
+        for speaker_id in &speaker_ids {
+            speakers_removed += tx
+                .execute(
+                    "DELETE FROM speakers
+                     WHERE id=?1
+                       AND NOT EXISTS (SELECT 1 FROM embeddings e WHERE e.speaker_id = speakers.id)
+                       AND NOT EXISTS (SELECT 1 FROM segments g WHERE g.speaker_id = speakers.id)",
+                    params![speaker_id],
+                )
+                .map_err(|e| e.to_string())?;
+        }
+        tx.execute("DELETE FROM sessions WHERE id=?1", params![session_id])
+            .map_err(|e| e.to_string())?;
+        tx.execute(
+            "DELETE FROM search_index WHERE session_id=?1",
+            params![session_id],
+        )
+        .map_err(|e| e.to_string())?;
+        tx.execute(
+            "DELETE FROM speaker_reviews WHERE session_id=?1",
+            params![session_id],
+        )
+        .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(UndoSummary {
+            session_id: session_id.to_string(),
+            segments_removed,
+            embeddings_removed,
+            speakers_removed,
+        })
+    }
+
+    /// Moves the segments starting at or after `ms` into a new session whose start time is
+    /// shifted by `ms`, rebasing their times onto it. Embeddings stay with the original.
+    ///
+    /// Returns the new session's id; transcripts are left for the caller to rebuild.
+    pub fn split_session(&self, session_id: &str, ms: i64) -> Result<String, String> {
+        let mut conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let created_at: String = tx
+            .query_row(
+                "SELECT created_at FROM sessions WHERE id=?1",
+                params![session_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "Session not found".to_string())?;
+        let (before, after): (i64, i64) = tx
+            .query_row(
+                "SELECT COALESCE(SUM(start_ms < ?2), 0), COALESCE(SUM(start_ms >= ?2), 0)
+                 FROM segments WHERE session_id=?1",
+                params![session_id, ms],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(|e| e.to_string())?;
+        if before == 0 || after == 0 {
+            return Err("Split point must leave segments on both sides".into());
+        }
+        let start = DateTime::parse_from_rfc3339(&created_at)
+            .map_err(|e| e.to_string())?
+            .with_timezone(&Utc)
+            + chrono::Duration::milliseconds(ms);
+        let new_id = Uuid::new_v4().to_string();
+        let (nonce, ct) = self.seal_text("");
+        tx.execute(
+            "INSERT INTO sessions(id, created_at, transcript_nonce, transcript_ct) VALUES(?1, ?2, ?3, ?4)",
+            params![new_id, start.to_rfc3339(), nonce, ct],
+        )
+        .map_err(|e| e.to_string())?;
+        tx.execute(
+            "UPDATE segments SET session_id=?1, start_ms=start_ms-?3, end_ms=end_ms-?3
+             WHERE session_id=?2 AND start_ms >= ?3",
+            params![new_id, session_id, ms],
+        )
+        .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(new_id)
+    }
+
+    /// Remembers where a session's source audio was kept, for waveform rendering.
+    pub fn set_session_audio_path(&self, session_id: &str, path: &str) -> Result<(), String> {
+        self.conn
+            .lock()
+            .map_err(|_| "lock poisoned".to_string())?
+            .execute(
+                "UPDATE sessions SET audio_path=?1 WHERE id=?2",
+                params![path, session_id],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn session_audio_path(&self, session_id: &str) -> Result<Option<String>, String> {
+        self.conn
+            .lock()
+            .map_err(|_| "lock poisoned".to_string())?
+            .query_row(
+                "SELECT audio_path FROM sessions WHERE id=?1",
+                params![session_id],
+                |row| row.get::<_, Option<String>>(0),
+            )
+            .optional()
+            .map(Option::flatten)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Stores a session's spectrogram PNG, encrypted like its transcript.
+    pub fn set_session_thumbnail(&self, session_id: &str, png: &[u8]) -> Result<(), String> {
+        let (nonce, ct) = self.crypto.encrypt(png);
+        self.conn
+            .lock()
+            .map_err(|_| "lock poisoned".to_string())?
+            .execute(
+                "UPDATE sessions SET thumbnail_nonce=?1, thumbnail_ct=?2 WHERE id=?3",
+                params![nonce, ct, session_id],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn session_thumbnail(&self, session_id: &str) -> Result<Option<Vec<u8>>, String> {
+        let row: Option<(Option<String>, Option<String>)> = self
+            .conn
+            .lock()
+            .map_err(|_| "lock poisoned".to_string())?
+            .query_row(
+                "SELECT thumbnail_nonce, thumbnail_ct FROM sessions WHERE id=?1",
+                params![session_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        match row {
+            Some((nonce, Some(ct))) => Ok(Some(
+                self.crypto.decrypt(nonce.as_deref().unwrap_or(""), &ct)?,
+            )),
+            _ => Ok(None),
+        }
+    }
+
+    /// Computes a session's content fingerprint and stores it with the session. `None` if
+    /// the session doesn't exist.
+    ///
+    /// The fingerprint is a MinHash signature, so transcripts that differ by a few words
+    /// still get similar fingerprints. It is the text `v2:<speakers>:<signature>`, where
+    /// `<speakers>` is the number of distinct speakers in the session. To build
+    /// `<signature>`, the text of the session's segments, in order of start time, is
+    /// lowercased and split on every character that isn't a letter or digit. Each run of
+    /// [`FINGERPRINT_SHINGLE_WORDS`] consecutive words, joined with single spaces, is a
+    /// shingle; a transcript with fewer words is a single shingle. For each
+    /// `i` in `0..`[`FINGERPRINT_HASHES`], the first 4 bytes of the SHA-256 of
+    /// `<i>\n<shingle>` are read as a big-endian `u32`, and the smallest over all shingles
+    /// is written as 8 lowercase hex digits (`ffffffff` when there are no words). Timings,
+    /// speaker names and audio play no part, so the same meeting transcribed on two devices
+    /// gets a similar fingerprint.
+    pub fn session_fingerprint(&self, session_id: &str) -> Result<Option<String>, String> {
+        if self.get_session(session_id)?.is_none() {
+            return Ok(None);
+        }
+        let speakers = self.session_speakers(session_id)?.len();
+        let words: Vec<String> = self
+            .list_segments(session_id)?
+            .iter()
+            .flat_map(|segment| {
+                segment
+                    .text
+                    .split(|c: char| !c.is_alphanumeric())
+                    .filter(|word| !word.is_empty())
+                    .map(str::to_lowercase)
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        let fingerprint = format!("v2:{speakers}:{}", minhash_signature(&words));
+        self.conn
+            .lock()
+            .map_err(|_| "lock poisoned".to_string())?
+            .execute(
+                "UPDATE sessions SET fingerprint=?1 WHERE id=?2",
+                params![fingerprint, session_id],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(Some(fingerprint))
+    }
+
+    /// Other sessions whose stored fingerprint is close to `fingerprint`, oldest first:
+    /// the same number of speakers, and at least [`FINGERPRINT_MATCH_SIMILARITY`] of the
+    /// signature's hashes equal, which estimates how many word shingles the transcripts
+    /// share. Sessions without words never match, nor do fingerprints from before v2.
+    pub fn sessions_with_fingerprint(
+        &self,
+        fingerprint: &str,
+        except_session_id: &str,
+    ) -> Result<Vec<String>, String> {
+        let Some((prefix, signature)) = parse_fingerprint(fingerprint) else {
+            return Ok(Vec::new());
+        };
+        let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, fingerprint FROM sessions WHERE fingerprint LIKE ?1 AND id<>?2 ORDER BY created_at ASC",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![format!("{prefix}%"), except_session_id], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(|e| e.to_string())?;
+        let mut duplicates = Vec::new();
+        for row in rows {
+            let (id, other) = row.map_err(|e| e.to_string())?;
+            let Some((_, other)) = parse_fingerprint(&other) else {
+                continue;
+            };
+            if minhash_similarity(&signature, &other) >= FINGERPRINT_MATCH_SIMILARITY {
+                duplicates.push(id);
+            }
+        }
+        Ok(duplicates)
+    }
+
+    pub fn update_session_transcript(
+        &self,
+        session_id: &str,
+        transcript: &str,
+    ) -> Result<(), String> {
+        let (nonce, ct) = self.seal_text(transcript);
+        let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        conn.execute(
+            "UPDATE sessions SET transcript_nonce=?1, transcript_ct=?2 WHERE id=?3",
+            params![nonce, ct, session_id],
+        )
+        .map_err(|e| e.to_string())?;
+        if self.plaintext_search_index {
+            self.index_transcript(&conn, session_id, transcript)?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the transcript with hand-edited text, which is searched as written even
+    /// if it was built with speaker prefixes before.
+    pub fn replace_session_transcript(
+        &self,
+        session_id: &str,
+        transcript: &str,
+    ) -> Result<(), String> {
+        self.conn
+            .lock()
+            .map_err(|_| "lock poisoned".to_string())?
+            .execute(
+                "UPDATE sessions SET speaker_prefixed=0 WHERE id=?1",
+                params![session_id],
+            )
+            .map_err(|e| e.to_string())?;
+        self.update_session_transcript(session_id, transcript)
+    }
+
+    /// The session's transcript without speaker prefixes, as search sees it.
+    pub fn clean_transcript(&self, session: &Session) -> Result<String, String> {
+        let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        self.search_text(&conn, &session.id, &session.transcript)
+    }
+
+    /// Adds `text` to the end of a session's transcript, after `separator` when the
+    /// transcript isn't empty.
+    pub fn append_session_transcript(
+        &self,
+        session_id: &str,
+        text: &str,
+        separator: &str,
+    ) -> Result<(), String> {
+        let session = self
+            .get_session(session_id)?
+            .ok_or_else(|| format!("Session not found: {session_id}"))?;
+        if text.is_empty() {
+            return Ok(());
+        }
+        let transcript = if session.transcript.is_empty() {
+            text.to_string()
+        } else {
+            format!("{}{separator}{text}", session.transcript)
+        };
+        self.update_session_transcript(session_id, &transcript)
+    }
+
+    /// End of the session's last segment in ms, or 0 when it has none.
+    pub fn session_end_ms(&self, session_id: &str) -> Result<i64, String> {
+        self.conn
+            .lock()
+            .map_err(|_| "lock poisoned".to_string())?
+            .query_row(
+                "SELECT COALESCE(MAX(end_ms), 0) FROM segments WHERE session_id=?1",
+                params![session_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())
+    }
+
+    /// The words of a session without speaker prefixes: `transcript` itself, or its
+    /// segments' text when the transcript was built with prefixes (see
+    /// [`Db::rebuild_transcript`]), so speaker names don't match searches.
+    fn search_text(
+        &self,
+        conn: &Connection,
+        session_id: &str,
+        transcript: &str,
+    ) -> Result<String, String> {
+        let prefixed: bool = conn
+            .query_row(
+                "SELECT speaker_prefixed FROM sessions WHERE id=?1",
+                params![session_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+            .unwrap_or(false);
+        if !prefixed {
+            return Ok(transcript.to_string());
+        }
+        let mut stmt = conn
+            .prepare(
+                "SELECT text_nonce, text_ct FROM segments WHERE session_id=?1 ORDER BY start_ms",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![session_id], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(|e| e.to_string())?;
+        let mut words = Vec::new();
+        for row in rows {
+            let (nonce, ct) = row.map_err(|e| e.to_string())?;
+            words.push(self.open_text(&nonce, &ct)?);
+        }
+        Ok(words.join(" "))
+    }
+
+    /// Replaces the index entries of one session with the tokens of `transcript`.
+    fn index_transcript(
+        &self,
+        conn: &Connection,
+        session_id: &str,
+        transcript: &str,
+    ) -> Result<(), String> {
+        conn.execute(
+            "DELETE FROM search_index WHERE session_id=?1",
+            params![session_id],
+        )
+        .map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare("INSERT OR IGNORE INTO search_index(token, session_id) VALUES(?1, ?2)")
+            .map_err(|e| e.to_string())?;
+        for token in search_tokens(&self.search_text(conn, session_id, transcript)?) {
+            stmt.execute(params![self.crypto.blind_token(&token), session_id])
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// Re-indexes every session's transcript from scratch.
+    pub fn rebuild_search_index(&self) -> Result<(), String> {
+        let sessions = self.list_sessions()?;
+        let mut conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        tx.execute("DELETE FROM search_index", [])
+            .map_err(|e| e.to_string())?;
+        for session in &sessions {
+            self.index_transcript(&tx, &session.id, &session.transcript)?;
+        }
+        tx.commit().map_err(|e| e.to_string())
+    }
+
+    /// Builds the index if it is enabled but has no entries while sessions exist, e.g.
+    /// after the option was switched on in `config.json` directly.
+    pub fn ensure_search_index(&self) -> Result<(), String> {
+        if !self.plaintext_search_index {
+            return Ok(());
+        }
+        let missing: bool = self
+            .conn
+            .lock()
+            .map_err(|_| "lock poisoned".to_string())?
+            .query_row(
+                "SELECT NOT EXISTS (SELECT 1 FROM search_index) AND EXISTS (SELECT 1 FROM sessions)",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        if missing {
+            self.rebuild_search_index()?;
+        }
+        Ok(())
+    }
+
+    pub fn clear_search_index(&self) -> Result<(), String> {
+        self.conn
+            .lock()
+            .map_err(|_| "lock poisoned".to_string())?
+            .execute("DELETE FROM search_index", [])
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Sessions whose transcript contains every word of `query` (case-insensitive, whole
+    /// words), newest first. Speaker prefixes in transcripts are not searched.
+    ///
+    /// With `plaintext_search_index` on this is an index lookup; otherwise every transcript
+    /// is decrypted and scanned. The index stores one row per distinct word and session —
+    /// as a keyed digest when the DB is encrypted — so it reveals which sessions share
+    /// words and how many distinct words each has, even though the text stays encrypted.
+    pub fn search_sessions(&self, query: &str) -> Result<Vec<Session>, String> {
+        let tokens = search_tokens(query);
+        if tokens.is_empty() {
+            return Ok(Vec::new());
+        }
+        if !self.plaintext_search_index {
+            let sessions = self.list_sessions()?;
+            let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+            let mut matches = Vec::new();
+            for session in sessions {
+                let text = self.search_text(&conn, &session.id, &session.transcript)?;
+                if search_tokens(&text).is_superset(&tokens) {
+                    matches.push(session);
+                }
+            }
+            return Ok(matches);
+        }
+        let ids: Vec<String> = {
+            let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+            let placeholders = vec!["?"; tokens.len()].join(", ");
+            let mut stmt = conn
+                .prepare(&format!(
+                    "SELECT session_id FROM search_index WHERE token IN ({placeholders})
+                     GROUP BY session_id HAVING COUNT(*) = {}",
+                    tokens.len()
+                ))
+                .map_err(|e| e.to_string())?;
+            let blinded: Vec<String> = tokens.iter().map(|t| self.crypto.blind_token(t)).collect();
+            let rows = stmt
+                .query_map(rusqlite::params_from_iter(blinded.iter()), |row| row.get(0))
+                .map_err(|e| e.to_string())?;
+            rows.collect::<Result<_, _>>().map_err(|e| e.to_string())?
+        };
+        let mut sessions = Vec::with_capacity(ids.len());
+        for id in ids {
+            if let Some(session) = self.get_session(&id)? {
+                sessions.push(session);
+            }
+        }
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.created_at));
+        Ok(sessions)
+    }
+
+    pub fn list_sessions(&self) -> Result<Vec<Session>, String> {
+        let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        let mut stmt = conn
+            .prepare("SELECT id, created_at, transcript_nonce, transcript_ct FROM sessions ORDER BY created_at DESC")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| {
+                let id: String = row.get(0)?;
+                let created_at: String = row.get(1)?;
+                let nonce: String = row.get(2)?;
+                let ct: String = row.get(3)?;
+                Ok((id, created_at, nonce, ct))
+            })
+            .map_err(|e| e.to_string())?;
+
+        let mut sessions = Vec::new();
+        for row in rows {
+            let (id, created_at, nonce, ct) = row.map_err(|e| e.to_string())?;
+            let ts = DateTime::parse_from_rfc3339(&created_at)
+                .map_err(|e| e.to_string())?
+                .with_timezone(&Utc);
+            let transcript = self.open_text(&nonce, &ct)?;
+            sessions.push(Session {
+                id,
+                created_at: ts,
+                transcript,
+            });
+        }
+        Ok(sessions)
+    }
+
+    /// Ids of all sessions, oldest first; nothing is decrypted.
+    pub fn session_ids(&self) -> Result<Vec<String>, String> {
+        let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        let mut stmt = conn
+            .prepare("SELECT id FROM sessions ORDER BY created_at ASC")
+            .map_err(|e| e.to_string())?;
+        let ids = stmt
+            .query_map([], |row| row.get(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<String>, _>>()
+            .map_err(|e| e.to_string())?;
+        Ok(ids)
+    }
+
+    pub fn get_session(&self, session_id: &str) -> Result<Option<Session>, String> {
+        let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        let row = conn
+            .query_row(
+                "SELECT id, created_at, transcript_nonce, transcript_ct FROM sessions WHERE id=?1",
+                params![session_id],
+                |row| {
+                    let id: String = row.get(0)?;
+                    let created_at: String = row.get(1)?;
+                    let nonce: String = row.get(2)?;
+                    let ct: String = row.get(3)?;
+                    Ok((id, created_at, nonce, ct))
+                },
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        let Some((id, created_at, nonce, ct)) = row else {
+            return Ok(None);
+        };
+        let ts = DateTime::parse_from_rfc3339(&created_at)
+            .map_err(|e| e.to_string())?
+            .with_timezone(&Utc);
+        let transcript = self.open_text(&nonce, &ct)?;
+        Ok(Some(Session {
+            id,
+            created_at: ts,
+            transcript,
+        }))
+    }
+
+    pub fn insert_segment(
+        &self,
+        session_id: &str,
+        start_ms: i64,
+        end_ms: i64,
+        speaker_id: Option<&str>,
+        speaker_label: Option<&str>,
+        text: &str,
+    ) -> Result<String, String> {
+        let id = Uuid::new_v4().to_string();
+        let (nonce, ct) = self.seal_text(text);
+        self.conn
+            .lock()
+            .map_err(|_| "lock poisoned".to_string())?
+            .execute(
+                "INSERT INTO segments(id, session_id, start_ms, end_ms, speaker_label, speaker_id, text_nonce, text_ct) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![id, session_id, start_ms, end_ms, speaker_label, speaker_id, nonce, ct],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(id)
+    }
+
+    pub fn list_segments(&self, session_id: &str) -> Result<Vec<SegmentRecord>, String> {
+        self.list_segments_ordered(session_id, SortOrder::Asc)
+    }
+
+    /// A session's segments by start time in the given direction.
+    pub fn list_segments_ordered(
+        &self,
+        session_id: &str,
+        order: SortOrder,
+    ) -> Result<Vec<SegmentRecord>, String> {
+        let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        let mut stmt = conn
+            .prepare(&format!("SELECT id, session_id, start_ms, end_ms, speaker_id, speaker_label, text_nonce, text_ct, exclude_from_embedding FROM segments WHERE session_id=?1 ORDER BY start_ms {}", order.sql()))
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![session_id], |row| {
+                let id: String = row.get(0)?;
+                let session_id: String = row.get(1)?;
+                let start_ms: i64 = row.get(2)?;
+                let end_ms: i64 = row.get(3)?;
+                let speaker_id: Option<String> = row.get(4)?;
+                let speaker_label: Option<String> = row.get(5)?;
+                let nonce: String = row.get(6)?;
+                let ct: String = row.get(7)?;
+                let excluded: bool = row.get(8)?;
+                Ok((
+                    id,
+                    session_id,
+                    start_ms,
+                    end_ms,
+                    speaker_id,
+                    speaker_label,
+                    nonce,
+                    ct,
+                    excluded,
+                ))
+            })
+            .map_err(|e| e.to_string())?;
+
+        let mut segments = Vec::new();
+        for row in rows {
+            let (id, session_id, start_ms, end_ms, speaker_id, speaker_label, nonce, ct, excluded) =
+                row.map_err(|e| e.to_string())?;
+            let text = self.open_text(&nonce, &ct)?;
+            segments.push(SegmentRecord {
+                id,
+                session_id,
+                start_ms,
+                end_ms,
+                speaker_id,
+                speaker_label,
+                text,
+                exclude_from_embedding: excluded,
+            });
+        }
+        Ok(segments)
+    }
+
+    /// Segments whose text contains `query` (case-insensitive), newest session first and
+    /// in order within a session, stopping after `limit` matches. Every segment is
+    /// decrypted to be checked.
+    pub fn search_segments(&self, query: &str, limit: usize) -> Result<Vec<SegmentMatch>, String> {
+        let needle = query.trim().to_lowercase();
+        if needle.is_empty() || limit == 0 {
+            return Ok(Vec::new());
+        }
+        let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT g.id, g.session_id, g.start_ms, g.end_ms, g.speaker_id, g.speaker_label,
+                        g.text_nonce, g.text_ct, s.created_at, g.exclude_from_embedding
+                 FROM segments g JOIN sessions s ON s.id = g.session_id
+                 ORDER BY s.created_at DESC, g.start_ms ASC",
+            )
+            .map_err(|e| e.to_string())?;
+        let mut rows = stmt.query([]).map_err(|e| e.to_string())?;
+        let mut matches = Vec::new();
+        while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+            let nonce: String = row.get(6).map_err(|e| e.to_string())?;
+            let ct: String = row.get(7).map_err(|e| e.to_string())?;
+            let text = self.open_text(&nonce, &ct)?;
+            if !text.to_lowercase().contains(&needle) {
+                continue;
+            }
+            let created_at: String = row.get(8).map_err(|e| e.to_string())?;
+            matches.push(SegmentMatch {
+                segment: SegmentRecord {
+                    id: row.get(0).map_err(|e| e.to_string())?,
+                    session_id: row.get(1).map_err(|e| e.to_string())?,
+                    start_ms: row.get(2).map_err(|e| e.to_string())?,
+                    end_ms: row.get(3).map_err(|e| e.to_string())?,
+                    speaker_id: row.get(4).map_err(|e| e.to_string())?,
+                    speaker_label: row.get(5).map_err(|e| e.to_string())?,
+                    text,
+                    exclude_from_embedding: row.get(9).map_err(|e| e.to_string())?,
+                },
+                created_at: DateTime::parse_from_rfc3339(&created_at)
+                    .map_err(|e| e.to_string())?
+                    .with_timezone(&Utc),
+            });
+            if matches.len() == limit {
+                break;
+            }
+        }
+        Ok(matches)
+    }
+
+    /// Decrypted text of a speaker's earliest segments, oldest session first.
+    pub fn speaker_segment_texts(
+        &self,
+        speaker_id: &str,
+        limit: usize,
+    ) -> Result<Vec<String>, String> {
+        let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT g.text_nonce, g.text_ct FROM segments g
+                 JOIN sessions s ON s.id = g.session_id
+                 WHERE g.speaker_id=?1
+                 ORDER BY s.created_at ASC, g.start_ms ASC
+                 LIMIT ?2",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![speaker_id, limit as i64], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(|e| e.to_string())?;
+        let mut texts = Vec::new();
+        for row in rows {
+            let (nonce, ct) = row.map_err(|e| e.to_string())?;
+            texts.push(self.open_text(&nonce, &ct)?);
+        }
+        Ok(texts)
+    }
+
+    /// Replaces a segment's text, returning its session id (`None` if it doesn't exist).
+    pub fn update_segment_text(
+        &self,
+        segment_id: &str,
+        text: &str,
+    ) -> Result<Option<String>, String> {
+        let (nonce, ct) = self.seal_text(text);
+        let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        let updated = conn
+            .execute(
+                "UPDATE segments SET text_nonce=?1, text_ct=?2 WHERE id=?3",
+                params![nonce, ct, segment_id],
+            )
+            .map_err(|e| e.to_string())?;
+        if updated == 0 {
+            return Ok(None);
+        }
+        conn.query_row(
+            "SELECT session_id FROM segments WHERE id=?1",
+            params![segment_id],
+            |row| row.get(0),
+        )
+        .map(Some)
+        .map_err(|e| e.to_string())
+    }
+
+    /// Marks a segment as left out of (or back in) voiceprint learning, returning its
+    /// session id (`None` if it doesn't exist). Stored embeddings are not touched.
+    pub fn set_segment_excluded(
+        &self,
+        segment_id: &str,
+        excluded: bool,
+    ) -> Result<Option<String>, String> {
+        let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        let updated = conn
+            .execute(
+                "UPDATE segments SET exclude_from_embedding=?1 WHERE id=?2",
+                params![excluded, segment_id],
+            )
+            .map_err(|e| e.to_string())?;
+        if updated == 0 {
+            return Ok(None);
+        }
+        conn.query_row(
+            "SELECT session_id FROM segments WHERE id=?1",
+            params![segment_id],
+            |row| row.get(0),
+        )
+        .map(Some)
+        .map_err(|e| e.to_string())
+    }
+
+    /// Rewrites the session transcript from its segments in `start_ms` order; see
+    /// [`join_segments`]. Whether it got speaker prefixes is remembered, so search can
+    /// skip them.
+    pub fn rebuild_transcript(
+        &self,
+        session_id: &str,
+        speaker_prefix: Option<&str>,
+        separator: &str,
+    ) -> Result<String, String> {
+        let segments = self.list_segments(session_id)?;
+        let transcript = join_segments(&segments, speaker_prefix, separator);
+        self.conn
+            .lock()
+            .map_err(|_| "lock poisoned".to_string())?
+            .execute(
+                "UPDATE sessions SET speaker_prefixed=?1 WHERE id=?2",
+                params![speaker_prefix.is_some(), session_id],
+            )
+            .map_err(|e| e.to_string())?;
+        self.update_session_transcript(session_id, &transcript)?;
+        Ok(transcript)
+    }
+
+    /// Segment covering `ms` (`start_ms <= ms < end_ms`); the earliest-starting one wins on overlap.
+    pub fn segment_at(&self, session_id: &str, ms: i64) -> Result<Option<SegmentRecord>, String> {
+        let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        let row = conn
+            .query_row(
+                "SELECT id, session_id, start_ms, end_ms, speaker_id, speaker_label, text_nonce, text_ct, exclude_from_embedding
+                 FROM segments WHERE session_id=?1 AND start_ms <= ?2 AND end_ms > ?2
+                 ORDER BY start_ms ASC LIMIT 1",
+                params![session_id, ms],
+                |row| {
+                    let id: String = row.get(0)?;
+                    let session_id: String = row.get(1)?;
+                    let start_ms: i64 = row.get(2)?;
+                    let end_ms: i64 = row.get(3)?;
+                    let speaker_id: Option<String> = row.get(4)?;
+                    let speaker_label: Option<String> = row.get(5)?;
+                    let nonce: String = row.get(6)?;
+                    let ct: String = row.get(7)?;
+                    let excluded: bool = row.get(8)?;
+                    Ok((id, session_id, start_ms, end_ms, speaker_id, speaker_label, nonce, ct, excluded))
+                },
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        let Some((
+            id,
+            session_id,
+            start_ms,
+            end_ms,
+            speaker_id,
+            speaker_label,
+            nonce,
+            ct,
+            excluded,
+        )) = row
+        else {
+            return Ok(None);
+        };
+        let text = self.open_text(&nonce, &ct)?;
+        Ok(Some(SegmentRecord {
+            id,
+            session_id,
+            start_ms,
+            end_ms,
+            speaker_id,
+            speaker_label,
+            text,
+            exclude_from_embedding: excluded,
+        }))
+    }
+
+    /// Next automatic `"<prefix> N"` label. N comes from a counter kept in `meta`
+    /// (`speaker_seq`) that only ever grows, whatever prefix earlier labels had, so deleting
+    /// speakers never frees a label for reuse. Databases without the counter start after
+    /// the highest existing `"<prefix> N"`.
+    ///
+    /// Plain statements rather than a transaction, so it works inside [`Db::in_savepoint`].
+    pub fn next_speaker_label(&self, prefix: &str) -> Result<String, String> {
+        let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        let stored: Option<String> = conn
+            .query_row(
+                "SELECT value FROM meta WHERE key='speaker_seq'",
+                [],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        let last = match stored.and_then(|v| v.parse::<u64>().ok()) {
+            Some(seq) => seq,
+            None => {
+                let mut stmt = conn
+                    .prepare("SELECT label FROM speakers WHERE label IS NOT NULL")
+                    .map_err(|e| e.to_string())?;
+                let labels = stmt
+                    .query_map([], |row| row.get::<_, String>(0))
+                    .map_err(|e| e.to_string())?;
+                let mut max = 0u64;
+                for label in labels {
+                    let label = label.map_err(|e| e.to_string())?;
+                    if let Some(n) = label
+                        .strip_prefix(prefix)
+                        .and_then(|rest| rest.strip_prefix(' '))
+                        .and_then(|n| n.parse::<u64>().ok())
+                    {
+                        max = max.max(n);
+                    }
+                }
+                max
+            }
+        };
+        let next = last + 1;
+        conn.execute(
+            "INSERT OR REPLACE INTO meta(key, value) VALUES('speaker_seq', ?1)",
+            params![next.to_string()],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(format!("{prefix} {next}"))
+    }
+
+    pub fn insert_speaker(&self, label: Option<&str>) -> Result<String, String> {
+        let id = Uuid::new_v4().to_string();
+        let now: DateTime<Utc> = SystemTime::now().into();
+        self.conn
+            .lock()
+            .map_err(|_| "lock poisoned".to_string())?
+            .execute(
+                "INSERT INTO speakers(id, label, created_at) VALUES(?1, ?2, ?3)",
+                params![id, label, now.to_rfc3339()],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(id)
+    }
+
+    /// All speakers with their voiceprint counts. `with_cohesion` also decrypts every
+    /// embedding to score each profile, which is slow on large databases.
+    pub fn list_speakers(&self, with_cohesion: bool) -> Result<Vec<Speaker>, String> {
+        let rows = {
+            let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+            let mut stmt = conn
+                .prepare(
+                    "SELECT s.id, s.label, s.created_at, COUNT(e.id)
+                     FROM speakers s
+                     LEFT JOIN embeddings e ON e.speaker_id = s.id
+                     GROUP BY s.id
+                     ORDER BY s.created_at ASC",
+                )
+                .map_err(|e| e.to_string())?;
+            let rows = stmt
+                .query_map([], |row| {
+                    let id: String = row.get(0)?;
+                    let label: Option<String> = row.get(1)?;
+                    let created_at: String = row.get(2)?;
+                    let embedding_count: i64 = row.get(3)?;
+                    Ok((id, label, created_at, embedding_count))
+                })
+                .map_err(|e| e.to_string())?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|e| e.to_string())?
+        };
+
+        let mut vectors: HashMap<String, Vec<Vec<f32>>> = HashMap::new();
+        if with_cohesion {
+            for embedding in self.list_embeddings()? {
+                vectors
+                    .entry(embedding.speaker_id)
+                    .or_default()
+                    .push(embedding.vector);
+            }
+        }
+
+        let mut speakers = Vec::new();
+        for (id, label, created_at, embedding_count) in rows {
+            let created_at = DateTime::parse_from_rfc3339(&created_at)
+                .map_err(|e| e.to_string())?
+                .with_timezone(&Utc);
+            let cohesion = vectors.get(&id).and_then(|v| cohesion(v));
+            speakers.push(Speaker {
+                id,
+                label,
+                created_at,
+                embedding_count: embedding_count as usize,
+                cohesion,
+            });
+        }
+        Ok(speakers)
+    }
+
+    pub fn get_speaker(&self, speaker_id: &str) -> Result<Speaker, String> {
+        let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        let (id, label, created_at, embedding_count): (String, Option<String>, String, i64) = conn
+            .query_row(
+                "SELECT id, label, created_at,
+                        (SELECT COUNT(*) FROM embeddings WHERE speaker_id = speakers.id)
+                 FROM speakers WHERE id=?1",
+                params![speaker_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Speaker not found: {speaker_id}"))?;
+        let created_at = DateTime::parse_from_rfc3339(&created_at)
+            .map_err(|e| e.to_string())?
+            .with_timezone(&Utc);
+        Ok(Speaker {
+            id,
+            label,
+            created_at,
+            embedding_count: embedding_count as usize,
+            cohesion: None,
+        })
+    }
+
+    pub fn rename_speaker(&self, speaker_id: &str, new_label: &str) -> Result<(), String> {
+        let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        conn.execute(
+            "UPDATE speakers SET label=?1 WHERE id=?2",
+            params![new_label, speaker_id],
+        )
+        .map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE segments SET speaker_label=?1 WHERE speaker_id=?2",
+            params![new_label, speaker_id],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Re-derives every segment's `speaker_label` from its speaker row; returns rows changed.
+    pub fn resync_segment_labels(&self) -> Result<usize, String> {
+        self.resync_labels(None)
+    }
+
+    /// [`Db::resync_segment_labels`] for one session's segments.
+    pub fn resync_session_segment_labels(&self, session_id: &str) -> Result<usize, String> {
+        self.resync_labels(Some(session_id))
+    }
+
+    fn resync_labels(&self, session_id: Option<&str>) -> Result<usize, String> {
+        let mut conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let corrected = tx
+            .execute(
+                "UPDATE segments
+                 SET speaker_label = (SELECT s.label FROM speakers s WHERE s.id = segments.speaker_id)
+                 WHERE speaker_id IS NOT NULL
+                   AND (?1 IS NULL OR session_id = ?1)
+                   AND EXISTS (SELECT 1 FROM speakers s WHERE s.id = segments.speaker_id)
+                   AND speaker_label IS NOT (SELECT s.label FROM speakers s WHERE s.id = segments.speaker_id)",
+                params![session_id],
+            )
+            .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(corrected)
+    }
+
+    /// Deletes segments whose session no longer exists and clears speaker references to
+    /// deleted speakers (the label text is kept). Safe to run repeatedly.
+    pub fn repair_orphans(&self) -> Result<OrphanRepair, String> {
+        let mut conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let segments_deleted = tx
+            .execute(
+                "DELETE FROM segments
+                 WHERE NOT EXISTS (SELECT 1 FROM sessions s WHERE s.id = segments.session_id)",
+                [],
+            )
+            .map_err(|e| e.to_string())?;
+        let speaker_refs_cleared = tx
+            .execute(
+                "UPDATE segments SET speaker_id = NULL
+                 WHERE speaker_id IS NOT NULL
+                   AND NOT EXISTS (SELECT 1 FROM speakers s WHERE s.id = segments.speaker_id)",
+                [],
+            )
+            .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(OrphanRepair {
+            segments_deleted,
+            speaker_refs_cleared,
+        })
+    }
+
+    /// Moves one of a session's embeddings, and the session's segments attributed to its
+    /// speaker, to another speaker. The previous speaker is dropped if nothing references it.
+    pub fn reassign_session_speaker(
+        &self,
+        session_id: &str,
+        embedding_id: &str,
+        from_speaker_id: &str,
+        to_speaker_id: &str,
+        to_label: Option<&str>,
+    ) -> Result<usize, String> {
+        let mut conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        // A savepoint rather than a transaction, so callers can batch several moves in
+        // `in_savepoint`.
+        let tx = conn.savepoint().map_err(|e| e.to_string())?;
+        tx.execute(
+            "UPDATE embeddings SET speaker_id=?1 WHERE id=?2",
+            params![to_speaker_id, embedding_id],
+        )
+        .map_err(|e| e.to_string())?;
+        let segments = tx
+            .execute(
+                "UPDATE segments SET speaker_id=?1, speaker_label=?2 WHERE session_id=?3 AND speaker_id=?4",
+                params![to_speaker_id, to_label, session_id, from_speaker_id],
+            )
+            .map_err(|e| e.to_string())?;
+        tx.execute(
+            "DELETE FROM speakers
+             WHERE id=?1
+               AND NOT EXISTS (SELECT 1 FROM embeddings e WHERE e.speaker_id = speakers.id)
+               AND NOT EXISTS (SELECT 1 FROM segments g WHERE g.speaker_id = speakers.id)",
+            params![from_speaker_id],
+        )
+        .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(segments)
+    }
+
+    /// A speaker's voiceprints, oldest first. Nothing is decrypted.
+    pub fn list_speaker_embedding_ids(
+        &self,
+        speaker_id: &str,
+    ) -> Result<Vec<EmbeddingRef>, String> {
+        let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, source_session_id, created_at FROM embeddings
+                 WHERE speaker_id=?1 ORDER BY created_at ASC",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![speaker_id], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })
+            .map_err(|e| e.to_string())?;
+        let mut embeddings = Vec::new();
+        for row in rows {
+            let (id, source_session_id, created_at) = row.map_err(|e| e.to_string())?;
+            let created_at = DateTime::parse_from_rfc3339(&created_at)
+                .map_err(|e| e.to_string())?
+                .with_timezone(&Utc);
+            embeddings.push(EmbeddingRef {
+                id,
+                source_session_id,
+                created_at,
+            });
+        }
+        Ok(embeddings)
+    }
+
+    /// Deletes one voiceprint; returns whether it existed.
+    pub fn delete_embedding(&self, embedding_id: &str) -> Result<bool, String> {
+        let deleted = self
+            .conn
+            .lock()
+            .map_err(|_| "lock poisoned".to_string())?
+            .execute("DELETE FROM embeddings WHERE id=?1", params![embedding_id])
+            .map_err(|e| e.to_string())?;
+        Ok(deleted > 0)
+    }
+
+    /// Number of stored embeddings of each length.
+    pub fn embedding_dimensions(&self) -> Result<HashMap<usize, usize>, String> {
+        let mut dims: HashMap<usize, usize> = HashMap::new();
+        for embedding in self.list_embeddings()? {
+            *dims.entry(embedding.vector.len()).or_default() += 1;
+        }
+        Ok(dims)
+    }
+
+    /// Deletes embeddings that don't have `dim` values, returning how many were removed.
+    pub fn delete_embeddings_not_of_dim(&self, dim: usize) -> Result<usize, String> {
+        let stale: Vec<String> = self
+            .list_embeddings()?
+            .into_iter()
+            .filter(|e| e.vector.len() != dim)
+            .map(|e| e.id)
+            .collect();
+        let mut conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        for id in &stale {
+            tx.execute("DELETE FROM embeddings WHERE id=?1", params![id])
+                .map_err(|e| e.to_string())?;
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(stale.len())
+    }
+
+    /// Replaces the embeddings `session_id` contributed to a speaker with `vector`, or just
+    /// removes them when `None`.
+    pub fn replace_session_embedding(
+        &self,
+        session_id: &str,
+        speaker_id: &str,
+        vector: Option<&[f32]>,
+    ) -> Result<(), String> {
+        self.conn
+            .lock()
+            .map_err(|_| "lock poisoned".to_string())?
+            .execute(
+                "DELETE FROM embeddings WHERE source_session_id=?1 AND speaker_id=?2",
+                params![session_id, speaker_id],
+            )
             .map_err(|e| e.to_string())?;
-
-        let mut segments = Vec::new();
-        for row in rows {
-            let (id, session_id, start_ms, end_ms, speaker_id, speaker_label, nonce, ct) =
-                row.map_err(|e| e.to_string())?;
-            let text_bytes = self.crypto.decrypt(&nonce, &ct)?;
-            let text = String::from_utf8(text_bytes).unwrap_or_default();
-            segments.push(SegmentRecord {
-                id,
-                session_id,
-                start_ms,
-                end_ms,
-                speaker_id,
-                speaker_label,
-                text,
-            });
+        if let Some(vector) = vector {
+            self.insert_embedding(speaker_id, session_id, vector)?;
         }
-        Ok(segments)
+        Ok(())
     }
 
-    pub fn insert_speaker(&self, label: Option<&str>) -> Result<String, String> {
+    pub fn delete_speaker(&self, speaker_id: &str) -> Result<(), String> {
+        let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        conn.execute(
+            "DELETE FROM embeddings WHERE speaker_id=?1",
+            params![speaker_id],
+        )
+        .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM speakers WHERE id=?1", params![speaker_id])
+            .map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE segments SET speaker_id=NULL, speaker_label=NULL WHERE speaker_id=?1",
+            params![speaker_id],
+        )
+        .map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE speaker_reviews SET candidate_speaker_id=NULL WHERE candidate_speaker_id=?1",
+            params![speaker_id],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Records a voice that fell between the ambiguous and match thresholds, keeping its
+    /// embedding (encrypted) for when the user resolves it.
+    pub fn insert_speaker_review(
+        &self,
+        session_id: &str,
+        speaker_label: &str,
+        candidate_speaker_id: &str,
+        score: f32,
+        vector: &[f32],
+    ) -> Result<String, String> {
         let id = Uuid::new_v4().to_string();
         let now: DateTime<Utc> = SystemTime::now().into();
+        let (nonce, ct) = self.crypto.encrypt(bytemuck::cast_slice::<f32, u8>(vector));
         self.conn
             .lock()
             .map_err(|_| "lock poisoned".to_string())?
             .execute(
-                "INSERT INTO speakers(id, label, created_at) VALUES(?1, ?2, ?3)",
-                params![id, label, now.to_rfc3339()],
+                "INSERT INTO speaker_reviews(id, session_id, speaker_label, candidate_speaker_id, score, vector_nonce, vector_ct, created_at) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![id, session_id, speaker_label, candidate_speaker_id, score, nonce, ct, now.to_rfc3339()],
             )
             .map_err(|e| e.to_string())?;
         Ok(id)
     }
 
-    pub fn list_speakers(&self) -> Result<Vec<Speaker>, String> {
+    /// Unresolved reviews, oldest first.
+    pub fn pending_speaker_reviews(&self) -> Result<Vec<SpeakerReview>, String> {
+        self.query_speaker_reviews(None)
+            .map(|reviews| reviews.into_iter().map(|(review, _)| review).collect())
+    }
+
+    /// A review with its embedding.
+    pub fn speaker_review(
+        &self,
+        review_id: &str,
+    ) -> Result<Option<(SpeakerReview, Vec<f32>)>, String> {
+        Ok(self
+            .query_speaker_reviews(Some(review_id))?
+            .into_iter()
+            .next())
+    }
+
+    fn query_speaker_reviews(
+        &self,
+        review_id: Option<&str>,
+    ) -> Result<Vec<(SpeakerReview, Vec<f32>)>, String> {
         let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
         let mut stmt = conn
-            .prepare("SELECT id, label, created_at FROM speakers ORDER BY created_at ASC")
+            .prepare(
+                "SELECT r.id, r.session_id, r.speaker_label, r.candidate_speaker_id, s.label,
+                        r.score, r.vector_nonce, r.vector_ct, r.created_at
+                 FROM speaker_reviews r
+                 LEFT JOIN speakers s ON s.id = r.candidate_speaker_id
+                 WHERE ?1 IS NULL OR r.id = ?1
+                 ORDER BY r.created_at ASC",
+            )
             .map_err(|e| e.to_string())?;
         let rows = stmt
-            .query_map([], |row| {
+            .query_map(params![review_id], |row| {
                 let id: String = row.get(0)?;
-                let label: Option<String> = row.get(1)?;
-                let created_at: String = row.get(2)?;
-                Ok((id, label, created_at))
+                let session_id: String = row.get(1)?;
+                let speaker_label: String = row.get(2)?;
+                let candidate_speaker_id: Option<String> = row.get(3)?;
+                let candidate_label: Option<String> = row.get(4)?;
+                let score: f32 = row.get(5)?;
+                let nonce: Option<String> = row.get(6)?;
+                let ct: String = row.get(7)?;
+                let created_at: String = row.get(8)?;
+                Ok((
+                    id,
+                    session_id,
+                    speaker_label,
+                    candidate_speaker_id,
+                    candidate_label,
+                    score,
+                    nonce,
+                    ct,
+                    created_at,
+                ))
             })
             .map_err(|e| e.to_string())?;
-
-        let mut speakers = Vec::new();
+        let mut reviews = Vec::new();
         for row in rows {
-            let (id, label, created_at) = row.map_err(|e| e.to_string())?;
+            let (
+                id,
+                session_id,
+                speaker_label,
+                candidate_speaker_id,
+                candidate_label,
+                score,
+                nonce,
+                ct,
+                created_at,
+            ) = row.map_err(|e| e.to_string())?;
+            let bytes = self.crypto.decrypt(nonce.as_deref().unwrap_or(""), &ct)?;
+            if bytes.len() % std::mem::size_of::<f32>() != 0 {
+                continue;
+            }
             let created_at = DateTime::parse_from_rfc3339(&created_at)
                 .map_err(|e| e.to_string())?
                 .with_timezone(&Utc);
-            speakers.push(Speaker {
+            let review = SpeakerReview {
                 id,
-                label,
+                session_id,
+                speaker_label,
+                candidate_speaker_id,
+                candidate_label,
+                score,
                 created_at,
-            });
+            };
+            reviews.push((review, bytemuck::cast_slice(&bytes).to_vec()));
         }
-        Ok(speakers)
+        Ok(reviews)
     }
 
-    pub fn rename_speaker(&self, speaker_id: &str, new_label: &str) -> Result<(), String> {
-        let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
-        conn.execute(
-            "UPDATE speakers SET label=?1 WHERE id=?2",
-            params![new_label, speaker_id],
-        )
-        .map_err(|e| e.to_string())?;
-        conn.execute(
-            "UPDATE segments SET speaker_label=?1 WHERE speaker_id=?2",
-            params![new_label, speaker_id],
-        )
-        .map_err(|e| e.to_string())?;
+    pub fn delete_speaker_review(&self, review_id: &str) -> Result<(), String> {
+        self.conn
+            .lock()
+            .map_err(|_| "lock poisoned".to_string())?
+            .execute(
+                "DELETE FROM speaker_reviews WHERE id=?1",
+                params![review_id],
+            )
+            .map_err(|e| e.to_string())?;
         Ok(())
     }
 
-    pub fn delete_speaker(&self, speaker_id: &str) -> Result<(), String> {
-        let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
-        conn.execute(
-            "DELETE FROM embeddings WHERE speaker_id=?1",
-            params![speaker_id],
-        )
-        .map_err(|e| e.to_string())?;
-        conn.execute("DELETE FROM speakers WHERE id=?1", params![speaker_id])
-            .map_err(|e| e.to_string())?;
-        conn.execute(
-            "UPDATE segments SET speaker_id=NULL, speaker_label=NULL WHERE speaker_id=?1",
-            params![speaker_id],
-        )
-        .map_err(|e| e.to_string())?;
-        Ok(())
+    /// Gives a session's unassigned segments labelled `from_label` to `speaker_id`.
+    pub fn assign_unmatched_segments(
+        &self,
+        session_id: &str,
+        from_label: &str,
+        speaker_id: &str,
+        speaker_label: &str,
+    ) -> Result<usize, String> {
+        self.conn
+            .lock()
+            .map_err(|_| "lock poisoned".to_string())?
+            .execute(
+                "UPDATE segments SET speaker_id=?1, speaker_label=?2
+                 WHERE session_id=?3 AND speaker_id IS NULL AND speaker_label=?4",
+                params![speaker_id, speaker_label, session_id, from_label],
+            )
+            .map_err(|e| e.to_string())
     }
 
     pub fn insert_embedding(
@@ -477,40 +2456,294 @@ impl Db {
         speaker_id: &str,
         session_id: &str,
         vector: &[f32],
+    ) -> Result<String, String> {
+        self.store_embedding(speaker_id, Some(session_id), vector)
+    }
+
+    /// Stores a voiceprint that came from outside any session, e.g. a speaker import.
+    pub fn insert_imported_embedding(
+        &self,
+        speaker_id: &str,
+        vector: &[f32],
+    ) -> Result<String, String> {
+        self.store_embedding(speaker_id, None, vector)
+    }
+
+    fn store_embedding(
+        &self,
+        speaker_id: &str,
+        session_id: Option<&str>,
+        vector: &[f32],
     ) -> Result<String, String> {
         let id = Uuid::new_v4().to_string();
         let now: DateTime<Utc> = SystemTime::now().into();
-        let bytes: &[u8] = bytemuck::cast_slice(vector);
-        let (nonce, ct) = self.crypto.encrypt(bytes);
+        let (format, bytes) = if self.compress_embeddings {
+            (VECTOR_FORMAT_Q8, quantize_q8(vector))
+        } else {
+            (
+                VECTOR_FORMAT_F32,
+                bytemuck::cast_slice::<f32, u8>(vector).to_vec(),
+            )
+        };
+        let (nonce, ct) = self.crypto.encrypt(&bytes);
         self.conn
             .lock()
             .map_err(|_| "lock poisoned".to_string())?
             .execute(
-                "INSERT INTO embeddings(id, speaker_id, vector_nonce, vector_ct, source_session_id, created_at) VALUES(?1, ?2, ?3, ?4, ?5, ?6)",
-                params![id, speaker_id, nonce, ct, session_id, now.to_rfc3339()],
+                "INSERT INTO embeddings(id, speaker_id, vector_nonce, vector_ct, source_session_id, created_at, vector_format) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![id, speaker_id, nonce, ct, session_id, now.to_rfc3339(), format],
             )
             .map_err(|e| e.to_string())?;
+        if let Ok(mut index) = self.ann_index.lock() {
+            if let Some(index) = index.as_mut() {
+                index.insert(&id, vector);
+            }
+        }
+        if let Some(max) = self.max_embeddings_per_speaker {
+            self.prune_speaker_embeddings(speaker_id, max)?;
+        }
         Ok(id)
     }
 
+    /// Deletes all but the `max` embeddings of a speaker closest to their centroid, so the
+    /// kept set stays representative; ties go to the newer vector.
+    fn prune_speaker_embeddings(&self, speaker_id: &str, max: usize) -> Result<usize, String> {
+        let mut embeddings = self.query_embeddings(Some(speaker_id))?;
+        if embeddings.len() <= max {
+            return Ok(0);
+        }
+        let dim = embeddings[0].vector.len();
+        let mut centroid = vec![0f32; dim];
+        for embedding in embeddings.iter().filter(|e| e.vector.len() == dim) {
+            for (acc, v) in centroid.iter_mut().zip(&embedding.vector) {
+                *acc += v;
+            }
+        }
+        let score = |e: &StoredEmbedding| {
+            if e.vector.len() == dim {
+                crate::embedding::cosine_similarity(&e.vector, &centroid)
+            } else {
+                f32::MIN
+            }
+        };
+        embeddings.sort_by(|a, b| {
+            score(b)
+                .total_cmp(&score(a))
+                .then_with(|| b.created_at.cmp(&a.created_at))
+        });
+        let mut conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        // A savepoint rather than a transaction, as this also runs inside `in_savepoint`.
+        let sp = conn.savepoint().map_err(|e| e.to_string())?;
+        for embedding in &embeddings[max..] {
+            sp.execute("DELETE FROM embeddings WHERE id=?1", params![embedding.id])
+                .map_err(|e| e.to_string())?;
+        }
+        sp.commit().map_err(|e| e.to_string())?;
+        Ok(embeddings.len() - max)
+    }
+
+    /// Re-encrypts every embedding under a fresh nonce with the same key, in one
+    /// transaction. Each row is decrypted back and compared before anything is committed.
+    pub fn reseal_embeddings(&self) -> Result<usize, String> {
+        if !self.encrypted {
+            return Err("Database is not encrypted".into());
+        }
+        let mut conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let rows: Vec<(String, String, String)> = {
+            let mut stmt = tx
+                .prepare("SELECT id, vector_nonce, vector_ct FROM embeddings")
+                .map_err(|e| e.to_string())?;
+            let rows = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+                .map_err(|e| e.to_string())?;
+            rows.collect::<Result<_, _>>().map_err(|e| e.to_string())?
+        };
+        for (id, nonce, ct) in &rows {
+            let bytes = self.crypto.decrypt(nonce, ct)?;
+            let (new_nonce, new_ct) = self.crypto.encrypt(&bytes);
+            if self.crypto.decrypt(&new_nonce, &new_ct)? != bytes {
+                return Err(format!(
+                    "Embedding {id} did not round-trip; nothing was changed"
+                ));
+            }
+            tx.execute(
+                "UPDATE embeddings SET vector_nonce=?1, vector_ct=?2 WHERE id=?3",
+                params![new_nonce, new_ct, id],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(rows.len())
+    }
+
+    /// Whether `password` derives the key this database was opened with.
+    pub fn password_matches(&self, password: &str) -> bool {
+        let candidate = Crypto::new(Some(password), self.crypto.salt());
+        candidate.key.is_some() && candidate.key == self.crypto.key
+    }
+
+    /// Rewrites every encrypted transcript, segment and embedding as plaintext (empty
+    /// nonce, base64 body) and removes the salt, in one transaction. Each row must read
+    /// back before anything is committed. Keyed search tokens are dropped; reopen with a
+    /// keyless [`Crypto`] afterwards so the index is rebuilt. Returns the rows rewritten.
+    pub fn decrypt_all(&self) -> Result<usize, String> {
+        if !self.encrypted {
+            return Err("Database is not encrypted".into());
+        }
+        let plain = Crypto::new(None, None);
+        let mut conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let mut rewritten = 0;
+        for (table, nonce_col, ct_col) in [
+            ("sessions", "transcript_nonce", "transcript_ct"),
+            ("segments", "text_nonce", "text_ct"),
+            ("embeddings", "vector_nonce", "vector_ct"),
+            ("sessions", "thumbnail_nonce", "thumbnail_ct"),
+            ("speaker_reviews", "vector_nonce", "vector_ct"),
+        ] {
+            let rows: Vec<(String, Option<String>, Option<String>)> = {
+                let mut stmt = tx
+                    .prepare(&format!("SELECT id, {nonce_col}, {ct_col} FROM {table}"))
+                    .map_err(|e| e.to_string())?;
+                let rows = stmt
+                    .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+                    .map_err(|e| e.to_string())?;
+                rows.collect::<Result<_, _>>().map_err(|e| e.to_string())?
+            };
+            for (id, nonce, ct) in rows {
+                // Empty and already-plaintext rows stay as they are.
+                let (Some(nonce), Some(ct)) = (nonce, ct) else {
+                    continue;
+                };
+                if nonce.is_empty() || ct.is_empty() {
+                    continue;
+                }
+                let bytes = self
+                    .crypto
+                    .decrypt(&nonce, &ct)
+                    .map_err(|e| format!("Could not decrypt {table} row {id}: {e}"))?;
+                let (new_nonce, new_ct) = plain.encrypt(&bytes);
+                if plain.decrypt(&new_nonce, &new_ct)? != bytes {
+                    return Err(format!(
+                        "{table} row {id} did not round-trip; nothing was changed"
+                    ));
+                }
+                tx.execute(
+                    &format!("UPDATE {table} SET {nonce_col}=?1, {ct_col}=?2 WHERE id=?3"),
+                    params![new_nonce, new_ct, id],
+                )
+                .map_err(|e| e.to_string())?;
+                rewritten += 1;
+            }
+            let sealed: i64 = tx
+                .query_row(
+                    &format!(
+                        "SELECT COUNT(*) FROM {table} WHERE {nonce_col} != '' AND {ct_col} != ''"
+                    ),
+                    [],
+                    |row| row.get(0),
+                )
+                .map_err(|e| e.to_string())?;
+            if sealed > 0 {
+                return Err(format!(
+                    "{sealed} {table} rows are still encrypted; nothing was changed"
+                ));
+            }
+        }
+        tx.execute("DELETE FROM search_index", [])
+            .map_err(|e| e.to_string())?;
+        tx.execute("DELETE FROM meta WHERE key='salt'", [])
+            .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(rewritten)
+    }
+
     pub fn list_embeddings(&self) -> Result<Vec<StoredEmbedding>, String> {
+        self.query_embeddings(None)
+    }
+
+    /// Changes how many decrypted vectors are cached, dropping the cache when shrunk.
+    pub fn set_embedding_cache_size(&mut self, size: usize) {
+        if size < self.embedding_cache_size {
+            if let Ok(mut cache) = self.vector_cache.lock() {
+                cache.clear();
+            }
+        }
+        self.embedding_cache_size = size;
+    }
+
+    /// Turns the voiceprint index on for `metric`, building it from the stored
+    /// voiceprints unless it's already built for that metric, or drops it with `None`.
+    pub fn set_ann_matching(&self, metric: Option<SimilarityMetric>) -> Result<(), String> {
+        let mut index = self.ann_index.lock().map_err(|_| "lock poisoned")?;
+        match metric {
+            Some(metric) if index.as_ref().is_some_and(|index| index.metric() == metric) => {}
+            Some(metric) => {
+                let embeddings = self.list_embeddings()?;
+                let vectors = embeddings
+                    .iter()
+                    .map(|e| (e.id.as_str(), e.vector.as_slice()));
+                *index = IvfIndex::build(vectors, metric);
+            }
+            None => *index = None,
+        }
+        Ok(())
+    }
+
+    /// Positions in `known`, the result of [`Db::list_embeddings`], worth scoring against
+    /// `query`; `None` means all of them. Brings the index up to date with `known` first,
+    /// and rebuilds it when the metric or vector length has changed or it wasn't big
+    /// enough to build before.
+    pub fn ann_candidates(
+        &self,
+        known: &[StoredEmbedding],
+        query: &[f32],
+        metric: SimilarityMetric,
+    ) -> Result<Option<Vec<usize>>, String> {
+        let mut index = self.ann_index.lock().map_err(|_| "lock poisoned")?;
+        match index.as_mut() {
+            Some(index) if index.metric() == metric && index.dim() == query.len() => {
+                let live: HashSet<&str> = known.iter().map(|e| e.id.as_str()).collect();
+                index.retain(|id| live.contains(id));
+                for embedding in known {
+                    index.insert(&embedding.id, &embedding.vector);
+                }
+            }
+            _ => {
+                *index = IvfIndex::build(
+                    known.iter().map(|e| (e.id.as_str(), e.vector.as_slice())),
+                    metric,
+                );
+            }
+        }
+        let ids = known.iter().map(|e| e.id.as_str());
+        Ok(index
+            .as_ref()
+            .and_then(|index| index.candidate_positions(ids, query)))
+    }
+
+    /// Embeddings of one speaker, or of everyone when `speaker_id` is `None`.
+    fn query_embeddings(&self, speaker_id: Option<&str>) -> Result<Vec<StoredEmbedding>, String> {
         let conn = self.conn.lock().map_err(|_| "lock poisoned".to_string())?;
         let mut stmt = conn
             .prepare(
-                "SELECT e.id, e.speaker_id, s.label, e.vector_nonce, e.vector_ct, e.source_session_id, e.created_at
+                "SELECT e.id, e.speaker_id, s.label, e.vector_nonce, e.vector_ct, e.source_session_id, e.created_at, e.vector_format
                  FROM embeddings e
-                 LEFT JOIN speakers s ON e.speaker_id = s.id",
+                 LEFT JOIN speakers s ON e.speaker_id = s.id
+                 WHERE ?1 IS NULL OR e.speaker_id = ?1",
             )
             .map_err(|e| e.to_string())?;
         let rows = stmt
-            .query_map([], |row| {
+            .query_map(params![speaker_id], |row| {
                 let id: String = row.get(0)?;
                 let speaker_id: String = row.get(1)?;
                 let speaker_label: Option<String> = row.get(2)?;
                 let nonce: String = row.get(3)?;
                 let ct: String = row.get(4)?;
-                let source_session_id: String = row.get(5)?;
+                let source_session_id: Option<String> = row.get(5)?;
                 let created_at: String = row.get(6)?;
+                let format: Option<String> = row.get(7)?;
                 Ok((
                     id,
                     speaker_id,
@@ -519,26 +2752,38 @@ impl Db {
                     ct,
                     source_session_id,
                     created_at,
+                    format,
                 ))
             })
             .map_err(|e| e.to_string())?;
 
+        let mut cache = self
+            .vector_cache
+            .lock()
+            .map_err(|_| "lock poisoned".to_string())?;
         let mut embeddings = Vec::new();
         for row in rows {
-            let (
-                id,
-                speaker_id,
-                speaker_label,
-                nonce,
-                ct,
-                source_session_id,
-                created_at,
-            ) = row.map_err(|e| e.to_string())?;
-            let bytes = self.crypto.decrypt(&nonce, &ct)?;
-            if bytes.len() % std::mem::size_of::<f32>() != 0 {
-                continue;
-            }
-            let floats: Vec<f32> = bytemuck::cast_slice(&bytes).to_vec();
+            let (id, speaker_id, speaker_label, nonce, ct, source_session_id, created_at, format) =
+                row.map_err(|e| e.to_string())?;
+            let floats = match cache.get(&id) {
+                Some(floats) => floats.to_vec(),
+                None => {
+                    let bytes = self.crypto.decrypt(&nonce, &ct)?;
+                    let floats: Vec<f32> = if format.as_deref() == Some(VECTOR_FORMAT_Q8) {
+                        match dequantize_q8(&bytes) {
+                            Some(floats) => floats,
+                            None => continue,
+                        }
+                    } else {
+                        if bytes.len() % std::mem::size_of::<f32>() != 0 {
+                            continue;
+                        }
+                        bytemuck::cast_slice(&bytes).to_vec()
+                    };
+                    cache.insert(id.clone(), floats.clone(), self.embedding_cache_size);
+                    floats
+                }
+            };
             let created_at = DateTime::parse_from_rfc3339(&created_at)
                 .map_err(|e| e.to_string())?
                 .with_timezone(&Utc);
@@ -551,6 +2796,239 @@ impl Db {
                 created_at,
             });
         }
+        if speaker_id.is_none() {
+            // Everything was read, so whatever wasn't seen has been deleted.
+            let live: HashSet<&str> = embeddings.iter().map(|e| e.id.as_str()).collect();
+            cache.retain(|id| live.contains(id));
+        }
         Ok(embeddings)
     }
 }
+
+/// Hashes in a session fingerprint's MinHash signature.
+pub const FINGERPRINT_HASHES: usize = 64;
+/// Words per shingle hashed into a session fingerprint.
+pub const FINGERPRINT_SHINGLE_WORDS: usize = 3;
+/// Share of equal signature hashes at which two sessions count as the same meeting.
+pub const FINGERPRINT_MATCH_SIMILARITY: f32 = 0.8;
+
+/// The MinHash signature of `words` described on [`Db::session_fingerprint`].
+fn minhash_signature(words: &[String]) -> String {
+    let mut minima = [u32::MAX; FINGERPRINT_HASHES];
+    if !words.is_empty() {
+        let width = FINGERPRINT_SHINGLE_WORDS.min(words.len());
+        for shingle in words.windows(width) {
+            let shingle = shingle.join(" ");
+            for (i, min) in minima.iter_mut().enumerate() {
+                let digest = Sha256::digest(format!("{i}\n{shingle}"));
+                let hash = u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]);
+                *min = (*min).min(hash);
+            }
+        }
+    }
+    minima.iter().map(|hash| format!("{hash:08x}")).collect()
+}
+
+/// Splits a v2 fingerprint into its `v2:<speakers>:` prefix and signature hashes. `None`
+/// for older or malformed fingerprints and for sessions without words.
+fn parse_fingerprint(fingerprint: &str) -> Option<(&str, Vec<u32>)> {
+    let split = fingerprint.rfind(':')? + 1;
+    let (prefix, hex) = fingerprint.split_at(split);
+    if !prefix.starts_with("v2:") || hex.len() != FINGERPRINT_HASHES * 8 {
+        return None;
+    }
+    let signature = (0..FINGERPRINT_HASHES)
+        .map(|i| u32::from_str_radix(hex.get(i * 8..i * 8 + 8)?, 16).ok())
+        .collect::<Option<Vec<_>>>()?;
+    if signature.iter().all(|hash| *hash == u32::MAX) {
+        return None;
+    }
+    Some((prefix, signature))
+}
+
+/// Share of positions where two signatures agree, an estimate of the Jaccard similarity
+/// of the shingle sets they were built from.
+fn minhash_similarity(a: &[u32], b: &[u32]) -> f32 {
+    let equal = a.iter().zip(b).filter(|(x, y)| x == y).count();
+    equal as f32 / FINGERPRINT_HASHES as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_integrity_passes_a_healthy_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("recall.db");
+        Db::open(&path, Crypto::new(None, None)).unwrap();
+        assert_eq!(Db::check_integrity(&path).unwrap(), None);
+    }
+
+    #[test]
+    fn check_integrity_reports_a_non_database_as_corrupt() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("recall.db");
+        std::fs::write(&path, vec![0x5a; 8192]).unwrap();
+        assert!(Db::check_integrity(&path).unwrap().is_some());
+    }
+
+    #[test]
+    fn check_integrity_returns_errors_that_are_not_corruption() {
+        let dir = tempfile::tempdir().unwrap();
+        // A directory can't be opened as a database at all.
+        assert!(Db::check_integrity(dir.path()).is_err());
+    }
+
+    #[test]
+    fn embedding_cache_picks_up_inserted_embeddings() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Db::open(dir.path().join("recall.db"), Crypto::new(None, None)).unwrap();
+        db.set_embedding_cache_size(10);
+        let speaker = db.insert_speaker(None).unwrap();
+        db.insert_imported_embedding(&speaker, &[1.0, 0.0]).unwrap();
+        assert_eq!(db.list_embeddings().unwrap().len(), 1);
+
+        let id = db.insert_imported_embedding(&speaker, &[0.0, 1.0]).unwrap();
+        let embeddings = db.list_embeddings().unwrap();
+        assert_eq!(embeddings.len(), 2);
+        let inserted = embeddings.iter().find(|e| e.id == id).unwrap();
+        assert_eq!(inserted.vector, vec![0.0, 1.0]);
+        assert!(db.vector_cache.lock().unwrap().entries.contains_key(&id));
+    }
+
+    #[test]
+    fn pruning_to_one_keeps_the_embedding_just_stored() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Db::open(dir.path().join("recall.db"), Crypto::new(None, None)).unwrap();
+        db.max_embeddings_per_speaker = Some(1);
+        let speaker = db.insert_speaker(None).unwrap();
+        db.insert_imported_embedding(&speaker, &[1.0, 0.0]).unwrap();
+        let latest = db.insert_imported_embedding(&speaker, &[0.0, 1.0]).unwrap();
+        let kept = db.list_embeddings().unwrap();
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].id, latest);
+    }
+
+    #[test]
+    fn speaker_labels_are_never_reused_after_deletions() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Db::open(dir.path().join("recall.db"), Crypto::new(None, None)).unwrap();
+        let create = || {
+            let label = db.next_speaker_label("Speaker").unwrap();
+            let id = db.insert_speaker(Some(&label)).unwrap();
+            (id, label)
+        };
+        let mut seen = HashSet::new();
+        for round in 0..3 {
+            let created: Vec<(String, String)> = (0..3).map(|_| create()).collect();
+            for (_, label) in &created {
+                assert!(
+                    seen.insert(label.clone()),
+                    "{label} reused in round {round}"
+                );
+            }
+            // Deleting the newest speakers is what let a count-based label come back.
+            for (id, _) in &created[1..] {
+                db.delete_speaker(id).unwrap();
+            }
+        }
+        assert_eq!(create().1, "Speaker 10");
+    }
+
+    #[test]
+    fn speaker_labels_continue_after_existing_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Db::open(dir.path().join("recall.db"), Crypto::new(None, None)).unwrap();
+        db.insert_speaker(Some("Speaker 7")).unwrap();
+        db.insert_speaker(Some("Speaker 12b")).unwrap();
+        db.insert_speaker(Some("Alice")).unwrap();
+        assert_eq!(db.next_speaker_label("Speaker").unwrap(), "Speaker 8");
+        assert_eq!(db.next_speaker_label("Speaker").unwrap(), "Speaker 9");
+    }
+
+    #[test]
+    fn vector_cache_evicts_the_least_recently_used() {
+        let mut cache = VectorCache::default();
+        cache.insert("a".into(), vec![1.0], 2);
+        cache.insert("b".into(), vec![2.0], 2);
+        assert!(cache.get("a").is_some());
+        cache.insert("c".into(), vec![3.0], 2);
+        assert!(cache.get("b").is_none());
+        assert_eq!(cache.get("a"), Some(&[1.0][..]));
+        assert_eq!(cache.get("c"), Some(&[3.0][..]));
+        cache.retain(|id| id != "a");
+        assert_eq!(cache.entries.len(), 1);
+        assert_eq!(cache.by_use.len(), 1);
+    }
+
+    #[test]
+    fn compressed_embeddings_rank_matches_like_f32() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Db::open(dir.path().join("recall.db"), Crypto::new(None, None)).unwrap();
+        db.compress_embeddings = true;
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut originals = HashMap::new();
+        for _ in 0..40 {
+            let vector: Vec<f32> = (0..192).map(|_| rng.gen_range(-1.0..1.0)).collect();
+            let speaker = db.insert_speaker(None).unwrap();
+            let id = db.insert_imported_embedding(&speaker, &vector).unwrap();
+            originals.insert(id, vector);
+        }
+        let stored = db.list_embeddings().unwrap();
+        assert_eq!(stored.len(), originals.len());
+
+        let cosine = crate::embedding::cosine_similarity;
+        for _ in 0..20 {
+            let target = &stored[rng.gen_range(0..stored.len())].id;
+            let query: Vec<f32> = originals[target]
+                .iter()
+                .map(|v| v + rng.gen_range(-0.5..0.5))
+                .collect();
+            let best = |score: &dyn Fn(&StoredEmbedding) -> f32| {
+                stored
+                    .iter()
+                    .max_by(|a, b| score(a).total_cmp(&score(b)))
+                    .map(|e| e.id.clone())
+                    .unwrap()
+            };
+            let exact = best(&|e| cosine(&query, &originals[&e.id]));
+            let quantized = best(&|e| cosine(&query, &e.vector));
+            assert_eq!(exact, quantized);
+            for e in &stored {
+                let drift = cosine(&query, &originals[&e.id]) - cosine(&query, &e.vector);
+                assert!(drift.abs() < 0.01, "cosine drifted by {drift}");
+            }
+        }
+    }
+
+    #[test]
+    fn fingerprints_match_transcripts_that_differ_by_a_few_words() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Db::open(dir.path().join("recall.db"), Crypto::new(None, None)).unwrap();
+        let words: Vec<String> = (0..200).map(|i| format!("word{i}")).collect();
+        let mut edited = words.clone();
+        edited[100] = "changed".into();
+        let session = |text: &str| {
+            let (id, _) = db.insert_session(text, None).unwrap();
+            db.insert_segment(&id, 0, 1000, None, None, text).unwrap();
+            id
+        };
+        let original = session(&words.join(" "));
+        let other_device = session(&edited.join(", "));
+        let unrelated = session("an entirely different meeting about something else");
+
+        let fingerprint = db.session_fingerprint(&original).unwrap().unwrap();
+        db.session_fingerprint(&unrelated).unwrap().unwrap();
+        assert_ne!(
+            fingerprint,
+            db.session_fingerprint(&other_device).unwrap().unwrap()
+        );
+        let duplicates = db
+            .sessions_with_fingerprint(&fingerprint, &original)
+            .unwrap();
+        assert_eq!(duplicates, vec![other_device]);
+    }
+}
@@ -0,0 +1,113 @@
+//! Server-free diarization: energy-based speech detection and clustering of window embeddings.
+
+use crate::embedding::SimilarityMetric;
+
+const FRAME_MS: u64 = 30;
+/// Frames this far above the recording's noise floor count as speech.
+const SPEECH_MARGIN_DB: f32 = 10.0;
+/// Frames quieter than this are never speech, however quiet the floor is.
+const SPEECH_MIN_DB: f32 = -55.0;
+/// Pauses shorter than this do not split a speech region.
+const MAX_GAP_MS: u64 = 300;
+const MIN_REGION_MS: u64 = 250;
+/// Length of the windows embedded within a speech region.
+pub const WINDOW_MS: u64 = 1_500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub start_ms: u64,
+    pub end_ms: u64,
+}
+
+/// Finds speech in mono `samples` by comparing frame levels to the recording's noise floor.
+pub fn speech_regions(samples: &[f32], sample_rate: u32) -> Vec<Region> {
+    let frame_len = ((sample_rate as u64 * FRAME_MS) / 1000).max(1) as usize;
+    let levels: Vec<f32> = samples
+        .chunks(frame_len)
+        .map(|frame| {
+            let rms = (frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32).sqrt();
+            20.0 * rms.max(1e-9).log10()
+        })
+        .collect();
+    if levels.is_empty() {
+        return Vec::new();
+    }
+    let mut sorted = levels.clone();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let noise_floor = sorted[sorted.len() / 10];
+    let threshold = (noise_floor + SPEECH_MARGIN_DB).max(SPEECH_MIN_DB);
+
+    let mut regions: Vec<Region> = Vec::new();
+    for (i, level) in levels.iter().enumerate() {
+        if *level < threshold {
+            continue;
+        }
+        let start_ms = i as u64 * FRAME_MS;
+        let end_ms = start_ms + FRAME_MS;
+        match regions.last_mut() {
+            Some(last) if start_ms - last.end_ms <= MAX_GAP_MS => last.end_ms = end_ms,
+            _ => regions.push(Region { start_ms, end_ms }),
+        }
+    }
+    let duration_ms = samples.len() as u64 * 1000 / sample_rate.max(1) as u64;
+    regions.retain(|r| r.end_ms - r.start_ms >= MIN_REGION_MS);
+    for region in regions.iter_mut() {
+        region.end_ms = region.end_ms.min(duration_ms);
+    }
+    regions
+}
+
+/// Midpoints of the pauses between regions lasting at least `min_gap_ms`.
+pub fn long_gaps(regions: &[Region], min_gap_ms: u64) -> Vec<u64> {
+    regions
+        .windows(2)
+        .filter(|pair| pair[1].start_ms - pair[0].end_ms >= min_gap_ms)
+        .map(|pair| (pair[0].end_ms + pair[1].start_ms) / 2)
+        .collect()
+}
+
+/// Cuts regions into windows of about [`WINDOW_MS`]; a short remainder joins the last window.
+pub fn windows(regions: &[Region]) -> Vec<Region> {
+    let mut out = Vec::new();
+    for region in regions {
+        let mut start_ms = region.start_ms;
+        while start_ms < region.end_ms {
+            let mut end_ms = (start_ms + WINDOW_MS).min(region.end_ms);
+            if region.end_ms - end_ms < WINDOW_MS / 2 {
+                end_ms = region.end_ms;
+            }
+            out.push(Region { start_ms, end_ms });
+            start_ms = end_ms;
+        }
+    }
+    out
+}
+
+/// Greedily assigns each embedding to the most similar cluster centroid, opening a new
+/// cluster when none reaches `threshold`. Returns a cluster index per embedding.
+pub fn cluster(embeddings: &[Vec<f32>], metric: SimilarityMetric, threshold: f32) -> Vec<usize> {
+    let mut sums: Vec<Vec<f32>> = Vec::new();
+    let mut labels = Vec::with_capacity(embeddings.len());
+    for embedding in embeddings {
+        let best = sums
+            .iter()
+            .enumerate()
+            .filter(|(_, sum)| sum.len() == embedding.len())
+            .map(|(i, sum)| (i, metric.similarity(embedding, sum)))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        let label = match best {
+            Some((i, score)) if score >= threshold => {
+                for (acc, v) in sums[i].iter_mut().zip(embedding) {
+                    *acc += v;
+                }
+                i
+            }
+            _ => {
+                sums.push(embedding.clone());
+                sums.len() - 1
+            }
+        };
+        labels.push(label);
+    }
+    labels
+}
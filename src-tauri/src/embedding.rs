@@ -1,33 +1,382 @@
+use ort::execution_providers::{
+    CPUExecutionProvider, CUDAExecutionProvider, CoreMLExecutionProvider,
+    DirectMLExecutionProvider, ExecutionProvider, OpenVINOExecutionProvider, ROCmExecutionProvider,
+    TensorRTExecutionProvider, XNNPACKExecutionProvider,
+};
 use ort::session::builder::GraphOptimizationLevel;
 use ort::session::Session;
-use ort::value::Tensor;
+use ort::tensor::TensorElementType;
+use ort::value::{Tensor, ValueType};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+const DEFAULT_N_MELS: usize = 80;
+
+/// SHA-256 of the bundled `models/spkrec-ecapa-voxceleb.onnx`.
+pub const BUNDLED_MODEL_SHA256: &str =
+    "d1eab5f9b8b91aa1686881cbbca3997046e000b9e42e19f25212cb624d5d6d09";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelChecksum {
+    pub path: std::path::PathBuf,
+    pub expected: String,
+    pub actual: String,
+    pub matches: bool,
+}
+
+impl ModelChecksum {
+    pub fn compute(path: &std::path::Path, expected: &str) -> Result<Self, String> {
+        let mut file = std::fs::File::open(path)
+            .map_err(|e| format!("Failed to open model {}: {e}", path.display()))?;
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut file, &mut hasher).map_err(|e| e.to_string())?;
+        let actual = format!("{:x}", hasher.finalize());
+        let expected = expected.trim().to_lowercase();
+        Ok(Self {
+            path: path.to_path_buf(),
+            matches: actual == expected,
+            expected,
+            actual,
+        })
+    }
+}
+
+/// How PCM is fed to the model's first input.
+///
+/// The bundled WeSpeaker ECAPA export takes `feats` shaped `[B, T, 80]`, i.e. [`InputLayout::Fbank`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InputLayout {
+    /// Raw waveform shaped `[1, N]`.
+    Waveform,
+    /// Raw waveform shaped `[1, 1, N]`.
+    WaveformChannel,
+    /// Log-mel filterbank features shaped `[1, n_frames, n_mels]`.
+    Fbank { n_mels: usize },
+}
+
+impl InputLayout {
+    /// Guesses the layout from the declared input shape, falling back to a raw waveform.
+    pub fn infer(input: &TensorInfo) -> Self {
+        match input.shape.as_slice() {
+            [_, 1, _] => InputLayout::WaveformChannel,
+            [_, _, n_mels] if *n_mels > 1 => InputLayout::Fbank {
+                n_mels: *n_mels as usize,
+            },
+            [_, _, _] => InputLayout::Fbank {
+                n_mels: DEFAULT_N_MELS,
+            },
+            _ => InputLayout::Waveform,
+        }
+    }
+}
+
+/// Which model output holds the embedding, by position or by name.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum OutputSelector {
+    Index(usize),
+    Name(String),
+}
 
 pub struct Embedder {
     session: Session,
+    layout: InputLayout,
+    /// Index into the session outputs the embedding is read from.
+    output_index: usize,
+    /// Longest audio passed to the model, in seconds; longer input is cut down to its
+    /// most energetic window.
+    max_input_secs: f32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TensorInfo {
+    pub name: String,
+    pub data_type: String,
+    /// Dynamic dimensions are reported as `-1`.
+    pub shape: Vec<i64>,
+}
+
+/// Stored voiceprints whose length differs from what the loaded model produces.
+#[derive(Debug, Clone, Serialize)]
+pub struct EmbeddingMismatch {
+    /// Most common length among the mismatched voiceprints.
+    pub stored_dim: usize,
+    pub model_dim: usize,
+    /// Stored voiceprints that don't have `model_dim` values.
+    pub mismatched: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelInfo {
+    pub inputs: Vec<TensorInfo>,
+    pub outputs: Vec<TensorInfo>,
+    pub input_layout: InputLayout,
+    /// Index of the output embeddings are read from.
+    pub embedding_output: usize,
+}
+
+fn tensor_info(name: &str, value_type: &ValueType) -> TensorInfo {
+    let data_type = match value_type.tensor_type() {
+        Some(ty) => ty.to_string(),
+        None => value_type.to_string(),
+    };
+    let shape = value_type
+        .tensor_shape()
+        .map(|shape| shape.to_vec())
+        .unwrap_or_default();
+    TensorInfo {
+        name: name.to_string(),
+        data_type,
+        shape,
+    }
+}
+
+/// Resolves the embedding output (the first when unset) and checks it is a float tensor.
+fn resolve_output(session: &Session, output: Option<&OutputSelector>) -> Result<usize, String> {
+    let index = match output {
+        None => 0,
+        Some(OutputSelector::Index(index)) => *index,
+        Some(OutputSelector::Name(name)) => session
+            .outputs
+            .iter()
+            .position(|o| &o.name == name)
+            .ok_or_else(|| format!("Model has no output named {name:?}"))?,
+    };
+    let selected = session.outputs.get(index).ok_or_else(|| {
+        format!(
+            "Model output {index} out of range (model has {})",
+            session.outputs.len()
+        )
+    })?;
+    if selected.output_type.tensor_type() != Some(TensorElementType::Float32) {
+        let info = tensor_info(&selected.name, &selected.output_type);
+        return Err(format!(
+            "Model output {:?} is {}, expected a float32 tensor",
+            info.name, info.data_type
+        ));
+    }
+    Ok(index)
 }
 
 impl Embedder {
-    pub fn new(model_path: &str) -> Result<Self, String> {
+    pub fn new(
+        model_path: &str,
+        layout: Option<InputLayout>,
+        output: Option<&OutputSelector>,
+        max_input_secs: f32,
+    ) -> Result<Self, String> {
         let session = Session::builder()
             .map_err(|e| e.to_string())?
             .with_optimization_level(GraphOptimizationLevel::Level3)
             .map_err(|e| e.to_string())?
             .commit_from_file(model_path)
             .map_err(|e| e.to_string())?;
-        Ok(Self { session })
+        let layout = match layout {
+            Some(layout) => layout,
+            None => {
+                let input = session.inputs.first().ok_or("Model declares no inputs")?;
+                InputLayout::infer(&tensor_info(&input.name, &input.input_type))
+            }
+        };
+        let output_index = resolve_output(&session, output)?;
+        Ok(Self {
+            session,
+            layout,
+            output_index,
+            max_input_secs,
+        })
     }
 
-    pub fn embed(&mut self, pcm: &[f32]) -> Result<Vec<f32>, String> {
-        let input = Tensor::from_array(([1, pcm.len() as i64], pcm.to_vec()))
-            .map_err(|e| format!("tensor error: {e}"))?;
+    pub fn model_info(&self) -> ModelInfo {
+        ModelInfo {
+            inputs: self
+                .session
+                .inputs
+                .iter()
+                .map(|input| tensor_info(&input.name, &input.input_type))
+                .collect(),
+            outputs: self
+                .session
+                .outputs
+                .iter()
+                .map(|output| tensor_info(&output.name, &output.output_type))
+                .collect(),
+            input_layout: self.layout,
+            embedding_output: self.output_index,
+        }
+    }
+
+    /// Length of the vectors this model produces: the declared output size, or when that
+    /// is dynamic, the length of an embedding of one second of silence.
+    pub fn output_dim(&mut self) -> Result<usize, String> {
+        let declared = self
+            .session
+            .outputs
+            .get(self.output_index)
+            .and_then(|output| output.output_type.tensor_shape())
+            .and_then(|shape| shape.last().copied())
+            .filter(|dim| *dim > 0);
+        match declared {
+            Some(dim) => Ok(dim as usize),
+            None => Ok(self.embed(&[0.0; 16_000], 16_000)?.len()),
+        }
+    }
+
+    pub fn embed(&mut self, pcm: &[f32], sample_rate: u32) -> Result<Vec<f32>, String> {
+        let max_samples = (self.max_input_secs * sample_rate as f32) as usize;
+        let pcm = most_energetic_window(pcm, max_samples);
+        let input = match self.layout {
+            InputLayout::Waveform => Tensor::from_array(([1, pcm.len() as i64], pcm.to_vec())),
+            InputLayout::WaveformChannel => {
+                Tensor::from_array(([1, 1, pcm.len() as i64], pcm.to_vec()))
+            }
+            InputLayout::Fbank { n_mels } => {
+                let (n_frames, feats) = crate::fbank::compute_fbank(pcm, sample_rate, n_mels)?;
+                Tensor::from_array(([1, n_frames as i64, n_mels as i64], feats))
+            }
+        }
+        .map_err(|e| format!("tensor error: {e}"))?;
         let outputs = self
             .session
             .run(ort::inputs![input])
             .map_err(|e| format!("ort run error: {e}"))?;
-        let output = outputs[0]
+        let output = outputs[self.output_index]
             .try_extract_array::<f32>()
             .map_err(|e| format!("extract error: {e}"))?;
-        Ok(output.iter().cloned().collect())
+        let values: Vec<f32> = output.iter().cloned().collect();
+        Ok(pool_frames(output.shape(), values))
+    }
+}
+
+/// The `len`-sample stretch of `pcm` with the most energy, or all of it when it is no
+/// longer than `len`.
+fn most_energetic_window(pcm: &[f32], len: usize) -> &[f32] {
+    if len == 0 || pcm.len() <= len {
+        return pcm;
+    }
+    let energy = |s: &f32| (*s as f64) * (*s as f64);
+    let mut sum: f64 = pcm[..len].iter().map(energy).sum();
+    let (mut best, mut best_start) = (sum, 0);
+    for end in len..pcm.len() {
+        sum += energy(&pcm[end]) - energy(&pcm[end - len]);
+        if sum > best {
+            best = sum;
+            best_start = end + 1 - len;
+        }
+    }
+    &pcm[best_start..best_start + len]
+}
+
+/// Mean-pools frame-level output shaped `[1, T, D]` (any leading size-1 dims) into one
+/// `D`-length vector; already pooled outputs (`[1, D]`, `[D]`) pass through unchanged.
+fn pool_frames(shape: &[usize], values: Vec<f32>) -> Vec<f32> {
+    let dims: Vec<usize> = shape.iter().copied().skip_while(|d| *d == 1).collect();
+    let dim = match dims.as_slice() {
+        [.., _, dim] if *dim > 0 => *dim,
+        _ => return values,
+    };
+    let frames = values.len() / dim;
+    let mut pooled = vec![0f32; dim];
+    for frame in values.chunks_exact(dim) {
+        for (acc, v) in pooled.iter_mut().zip(frame) {
+            *acc += v;
+        }
+    }
+    pooled.iter_mut().for_each(|v| *v /= frames as f32);
+    pooled
+}
+
+/// Smallest valid ONNX graph (one float `Identity` node) used to probe execution providers.
+const PROBE_MODEL: &[u8] = &[
+    8, 7, 66, 4, 10, 0, 16, 13, 58, 59, 10, 16, 10, 1, 120, 18, 1, 121, 34, 8, 73, 100, 101, 110,
+    116, 105, 116, 121, 18, 5, 112, 114, 111, 98, 101, 90, 15, 10, 1, 120, 18, 10, 10, 8, 8, 1, 18,
+    4, 10, 2, 8, 1, 98, 15, 10, 1, 121, 18, 10, 10, 8, 8, 1, 18, 4, 10, 2, 8, 1,
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExecutionProviderStatus {
+    pub name: &'static str,
+    pub supported_by_platform: bool,
+    /// Whether the linked ONNX Runtime was built with this provider.
+    pub compiled: bool,
+    /// Whether a session using the provider could actually be created.
+    pub initializes: bool,
+    pub error: Option<String>,
+}
+
+/// Whether ONNX Runtime works at all here, by building a CPU session for the probe
+/// model. On some platforms `ort` fails, or panics, before any real model is involved.
+pub fn check_runtime() -> Result<(), String> {
+    std::panic::catch_unwind(|| {
+        Session::builder()
+            .and_then(|builder| builder.commit_from_memory(PROBE_MODEL))
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    })
+    .unwrap_or_else(|_| Err("ONNX Runtime panicked while initializing".into()))
+}
+
+pub fn available_execution_providers() -> Vec<ExecutionProviderStatus> {
+    let providers: Vec<Box<dyn ExecutionProvider>> = vec![
+        Box::new(CPUExecutionProvider::default()),
+        Box::new(CUDAExecutionProvider::default()),
+        Box::new(TensorRTExecutionProvider::default()),
+        Box::new(CoreMLExecutionProvider::default()),
+        Box::new(DirectMLExecutionProvider::default()),
+        Box::new(ROCmExecutionProvider::default()),
+        Box::new(OpenVINOExecutionProvider::default()),
+        Box::new(XNNPACKExecutionProvider::default()),
+    ];
+    providers
+        .iter()
+        .map(|provider| probe_execution_provider(provider.as_ref()))
+        .collect()
+}
+
+fn probe_execution_provider(provider: &dyn ExecutionProvider) -> ExecutionProviderStatus {
+    let supported_by_platform = provider.supported_by_platform();
+    let compiled = provider.is_available().unwrap_or(false);
+    let result = if !supported_by_platform {
+        Err("Not supported on this platform".to_string())
+    } else if !compiled {
+        Err("Not included in this ONNX Runtime build".to_string())
+    } else {
+        Session::builder()
+            .map_err(|e| e.to_string())
+            .and_then(|mut builder| {
+                provider.register(&mut builder).map_err(|e| e.to_string())?;
+                builder
+                    .commit_from_memory(PROBE_MODEL)
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())
+            })
+    };
+    ExecutionProviderStatus {
+        name: provider.name(),
+        supported_by_platform,
+        compiled,
+        initializes: result.is_ok(),
+        error: result.err(),
+    }
+}
+
+/// How two embeddings are compared when matching speakers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SimilarityMetric {
+    #[default]
+    Cosine,
+    /// [`euclidean_similarity`] on L2-normalized vectors.
+    Euclidean,
+}
+
+impl SimilarityMetric {
+    pub fn similarity(self, a: &[f32], b: &[f32]) -> f32 {
+        match self {
+            SimilarityMetric::Cosine => cosine_similarity(a, b),
+            SimilarityMetric::Euclidean => euclidean_similarity(a, b),
+        }
     }
 }
 
@@ -41,3 +390,78 @@ pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
         dot / (norm_a * norm_b)
     }
 }
+
+/// Euclidean distance between the L2-normalized vectors, mapped from `[0, 2]` onto
+/// `[1, 0]` so higher means more similar, as with [`cosine_similarity`].
+pub fn euclidean_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    let distance = a
+        .iter()
+        .zip(b.iter())
+        .map(|(x, y)| (x / norm_a - y / norm_b).powi(2))
+        .sum::<f32>()
+        .sqrt();
+    1.0 - distance / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    const METRICS: [SimilarityMetric; 2] = [SimilarityMetric::Cosine, SimilarityMetric::Euclidean];
+
+    #[test]
+    fn metrics_rank_voices_by_closeness() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let speakers: Vec<Vec<f32>> = (0..8)
+            .map(|_| (0..192).map(|_| rng.gen_range(-1.0..1.0)).collect())
+            .collect();
+        for (expected, speaker) in speakers.iter().enumerate() {
+            // A louder, noisier take of the same voice.
+            let query: Vec<f32> = speaker
+                .iter()
+                .map(|v| 3.0 * (v + rng.gen_range(-0.3..0.3)))
+                .collect();
+            for metric in METRICS {
+                let scores: Vec<f32> = speakers
+                    .iter()
+                    .map(|s| metric.similarity(&query, s))
+                    .collect();
+                let best = (0..scores.len())
+                    .max_by(|&a, &b| scores[a].total_cmp(&scores[b]))
+                    .unwrap();
+                assert_eq!(best, expected, "{metric:?} picked the wrong speaker");
+            }
+        }
+    }
+
+    #[test]
+    fn metrics_agree_on_order_and_bounds() {
+        let query = [1.0, 0.0];
+        let candidates: Vec<[f32; 2]> = (0..=8)
+            .map(|step| {
+                let angle = std::f32::consts::PI * step as f32 / 8.0;
+                [2.0 * angle.cos(), 2.0 * angle.sin()]
+            })
+            .collect();
+        for metric in METRICS {
+            let scores: Vec<f32> = candidates
+                .iter()
+                .map(|c| metric.similarity(&query, c))
+                .collect();
+            assert!((scores[0] - 1.0).abs() < 1e-6, "{metric:?}");
+            assert!(scores.windows(2).all(|w| w[0] > w[1]), "{metric:?}");
+            assert_eq!(metric.similarity(&query, &[0.0, 0.0]), 0.0);
+        }
+        assert!(cosine_similarity(&query, &candidates[4]).abs() < 1e-6);
+        assert!((cosine_similarity(&query, &candidates[8]) + 1.0).abs() < 1e-6);
+        let orthogonal = euclidean_similarity(&query, &candidates[4]);
+        assert!((orthogonal - (1.0 - std::f32::consts::SQRT_2 / 2.0)).abs() < 1e-6);
+        assert!(euclidean_similarity(&query, &candidates[8]).abs() < 1e-6);
+    }
+}
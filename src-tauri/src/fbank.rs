@@ -0,0 +1,165 @@
+//! Minimal Kaldi-compatible log-mel filterbank frontend.
+//!
+//! Mirrors `torchaudio.compliance.kaldi.fbank` with the settings WeSpeaker uses for its
+//! exported models (25ms hamming window, 10ms shift, no dither, pre-emphasis 0.97,
+//! int16-scaled input) followed by per-utterance mean normalization.
+
+const FEATURE_SAMPLE_RATE: u32 = 16_000;
+const FRAME_LENGTH: usize = 400;
+const FRAME_SHIFT: usize = 160;
+const FFT_SIZE: usize = 512;
+const PREEMPHASIS: f32 = 0.97;
+const LOW_FREQ: f32 = 20.0;
+
+/// Computes `[n_frames, n_mels]` features (row-major) for mono PCM in `[-1, 1]`.
+pub fn compute_fbank(
+    pcm: &[f32],
+    sample_rate: u32,
+    n_mels: usize,
+) -> Result<(usize, Vec<f32>), String> {
+    if n_mels == 0 {
+        return Err("Feature frontend needs at least one mel bin".into());
+    }
+    let samples = resample_linear(pcm, sample_rate, FEATURE_SAMPLE_RATE);
+    if samples.len() < FRAME_LENGTH {
+        return Err("Audio too short for feature extraction".into());
+    }
+    let n_frames = 1 + (samples.len() - FRAME_LENGTH) / FRAME_SHIFT;
+    let window = hamming_window(FRAME_LENGTH);
+    let filters = mel_filterbank(n_mels, FEATURE_SAMPLE_RATE as f32);
+
+    let mut feats = Vec::with_capacity(n_frames * n_mels);
+    let mut frame = vec![0f32; FRAME_LENGTH];
+    let mut re = vec![0f32; FFT_SIZE];
+    let mut im = vec![0f32; FFT_SIZE];
+    for f in 0..n_frames {
+        let start = f * FRAME_SHIFT;
+        for (dst, src) in frame.iter_mut().zip(&samples[start..start + FRAME_LENGTH]) {
+            *dst = src * 32768.0;
+        }
+        let mean = frame.iter().sum::<f32>() / FRAME_LENGTH as f32;
+        frame.iter_mut().for_each(|s| *s -= mean);
+        for i in (1..FRAME_LENGTH).rev() {
+            frame[i] -= PREEMPHASIS * frame[i - 1];
+        }
+        frame[0] -= PREEMPHASIS * frame[0];
+
+        re.iter_mut().for_each(|v| *v = 0.0);
+        im.iter_mut().for_each(|v| *v = 0.0);
+        for i in 0..FRAME_LENGTH {
+            re[i] = frame[i] * window[i];
+        }
+        fft(&mut re, &mut im);
+
+        for filter in &filters {
+            let energy: f32 = filter
+                .iter()
+                .map(|(bin, weight)| weight * (re[*bin] * re[*bin] + im[*bin] * im[*bin]))
+                .sum();
+            feats.push(energy.max(f32::EPSILON).ln());
+        }
+    }
+
+    for m in 0..n_mels {
+        let mean = (0..n_frames).map(|f| feats[f * n_mels + m]).sum::<f32>() / n_frames as f32;
+        for f in 0..n_frames {
+            feats[f * n_mels + m] -= mean;
+        }
+    }
+    Ok((n_frames, feats))
+}
+
+fn resample_linear(pcm: &[f32], from: u32, to: u32) -> Vec<f32> {
+    if from == to || from == 0 || pcm.is_empty() {
+        return pcm.to_vec();
+    }
+    let ratio = from as f64 / to as f64;
+    let out_len = ((pcm.len() as f64) / ratio).floor() as usize;
+    (0..out_len)
+        .map(|i| {
+            let pos = i as f64 * ratio;
+            let idx = pos.floor() as usize;
+            let frac = (pos - idx as f64) as f32;
+            let a = pcm[idx.min(pcm.len() - 1)];
+            let b = pcm[(idx + 1).min(pcm.len() - 1)];
+            a + (b - a) * frac
+        })
+        .collect()
+}
+
+pub(crate) fn hamming_window(len: usize) -> Vec<f32> {
+    let denom = (len - 1) as f32;
+    (0..len)
+        .map(|i| 0.54 - 0.46 * (2.0 * std::f32::consts::PI * i as f32 / denom).cos())
+        .collect()
+}
+
+fn mel_scale(freq: f32) -> f32 {
+    1127.0 * (1.0 + freq / 700.0).ln()
+}
+
+/// Triangular filters as sparse `(fft_bin, weight)` lists, spaced evenly on the mel scale.
+fn mel_filterbank(n_mels: usize, sample_rate: f32) -> Vec<Vec<(usize, f32)>> {
+    let nyquist = sample_rate / 2.0;
+    let mel_low = mel_scale(LOW_FREQ);
+    let mel_high = mel_scale(nyquist);
+    let mel_delta = (mel_high - mel_low) / (n_mels + 1) as f32;
+    let fft_bin_width = sample_rate / FFT_SIZE as f32;
+
+    (0..n_mels)
+        .map(|m| {
+            let left = mel_low + m as f32 * mel_delta;
+            let center = left + mel_delta;
+            let right = center + mel_delta;
+            (0..FFT_SIZE / 2)
+                .filter_map(|bin| {
+                    let mel = mel_scale(fft_bin_width * bin as f32);
+                    let weight = if mel > left && mel <= center {
+                        (mel - left) / (center - left)
+                    } else if mel > center && mel < right {
+                        (right - mel) / (right - center)
+                    } else {
+                        0.0
+                    };
+                    (weight > 0.0).then_some((bin, weight))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// In-place iterative radix-2 FFT; `re.len()` must be a power of two.
+pub(crate) fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * std::f32::consts::PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let a = start + k;
+                let b = a + len / 2;
+                let tr = re[b] * cos - im[b] * sin;
+                let ti = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - tr;
+                im[b] = im[a] - ti;
+                re[a] += tr;
+                im[a] += ti;
+            }
+        }
+        len <<= 1;
+    }
+}
@@ -0,0 +1,336 @@
+//! FLAC storage for retained recordings: `flacenc` encodes straight from the WAV reader and
+//! `claxon` decodes frame by frame.
+
+use std::path::Path;
+
+use flacenc::component::BitRepr;
+use flacenc::error::{SourceError, SourceErrorReason, Verify};
+use flacenc::source::{Fill, Source};
+
+const BLOCK_SIZE: usize = 4096;
+
+pub fn is_flac(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("flac"))
+}
+
+/// Feeds the encoder from a WAV reader one block at a time, already converted to the
+/// stored bit depth.
+struct WavSource<'a> {
+    samples: Box<dyn Iterator<Item = Result<i32, hound::Error>> + 'a>,
+    channels: usize,
+    bits_per_sample: usize,
+    sample_rate: usize,
+    block: Vec<i32>,
+}
+
+impl Source for WavSource<'_> {
+    fn channels(&self) -> usize {
+        self.channels
+    }
+
+    fn bits_per_sample(&self) -> usize {
+        self.bits_per_sample
+    }
+
+    fn sample_rate(&self) -> usize {
+        self.sample_rate
+    }
+
+    fn read_samples<F: Fill>(
+        &mut self,
+        block_size: usize,
+        dest: &mut F,
+    ) -> Result<usize, SourceError> {
+        self.block.clear();
+        for sample in self.samples.by_ref().take(block_size * self.channels) {
+            self.block.push(sample.map_err(SourceError::from_io_error)?);
+        }
+        if !self.block.len().is_multiple_of(self.channels) {
+            return Err(SourceError::by_reason(SourceErrorReason::InvalidFormat));
+        }
+        dest.fill_interleaved(&self.block)?;
+        Ok(self.block.len() / self.channels)
+    }
+}
+
+/// Encodes a WAV file as FLAC. Integer PCM up to 24 bits is kept exactly; 32-bit integer
+/// and float audio is stored at 24 bits.
+pub fn encode_wav(wav: &Path, output: &Path) -> Result<(), String> {
+    let mut reader = hound::WavReader::open(wav).map_err(|e| e.to_string())?;
+    let spec = reader.spec();
+    let channels = spec.channels as usize;
+    if !(1..=8).contains(&channels) {
+        return Err(format!("FLAC supports 1-8 channels, got {channels}"));
+    }
+    let (bits_per_sample, samples): (usize, Box<dyn Iterator<Item = _>>) =
+        match (spec.sample_format, spec.bits_per_sample) {
+            (hound::SampleFormat::Int, bits @ 8..=24) => {
+                (bits as usize, Box::new(reader.samples::<i32>()))
+            }
+            (hound::SampleFormat::Int, 32) => (
+                24,
+                Box::new(reader.samples::<i32>().map(|s| s.map(|s| s >> 8))),
+            ),
+            (hound::SampleFormat::Float, 32) => (
+                24,
+                Box::new(
+                    reader
+                        .samples::<f32>()
+                        .map(|s| s.map(|s| (s.clamp(-1.0, 1.0) * 8_388_607.0).round() as i32)),
+                ),
+            ),
+            _ => return Err("Unsupported WAV format".into()),
+        };
+    let source = WavSource {
+        samples,
+        channels,
+        bits_per_sample,
+        sample_rate: spec.sample_rate as usize,
+        block: Vec::with_capacity(BLOCK_SIZE * channels),
+    };
+
+    let mut config = flacenc::config::Encoder::default();
+    config.block_size = BLOCK_SIZE;
+    let config = config.into_verified().map_err(|(_, e)| e.to_string())?;
+    let mut stream = flacenc::encode_with_fixed_block_size(&config, source, BLOCK_SIZE)
+        .map_err(|e| e.to_string())?;
+    // flacenc counts the short last block as the minimum, which the spec excludes and
+    // claxon refuses when it's under 16 samples.
+    stream
+        .stream_info_mut()
+        .set_block_sizes(BLOCK_SIZE, BLOCK_SIZE)
+        .map_err(|e| e.to_string())?;
+    let mut sink = flacenc::bitsink::ByteSink::new();
+    stream.write(&mut sink).map_err(|e| e.to_string())?;
+    std::fs::write(output, sink.as_slice()).map_err(|e| e.to_string())
+}
+
+/// Stream parameters from the STREAMINFO block.
+pub struct StreamInfo {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub bits_per_sample: u32,
+    /// Zero when the encoder didn't record the length.
+    pub total_frames: u64,
+}
+
+impl StreamInfo {
+    /// Divisor that maps the decoded samples to [-1, 1].
+    pub fn full_scale(&self) -> f32 {
+        (1u64 << (self.bits_per_sample - 1)) as f32
+    }
+}
+
+impl From<claxon::metadata::StreamInfo> for StreamInfo {
+    fn from(info: claxon::metadata::StreamInfo) -> Self {
+        Self {
+            sample_rate: info.sample_rate,
+            channels: info.channels as u16,
+            bits_per_sample: info.bits_per_sample,
+            total_frames: info.samples.unwrap_or(0),
+        }
+    }
+}
+
+/// Reads only the metadata blocks, without decoding any audio.
+pub fn read_info(path: &Path) -> Result<StreamInfo, String> {
+    let options = claxon::FlacReaderOptions {
+        metadata_only: true,
+        read_vorbis_comment: false,
+    };
+    let reader = claxon::FlacReader::open_ext(path, options).map_err(|e| e.to_string())?;
+    Ok(reader.streaminfo().into())
+}
+
+/// Decodes a FLAC file frame by frame into interleaved samples at the stream's own bit
+/// depth, holding one frame of audio at a time.
+pub struct FlacReader {
+    inner: claxon::FlacReader<std::fs::File>,
+    info: StreamInfo,
+    /// The current frame, interleaved.
+    frame: Vec<i32>,
+    next: usize,
+    /// Planar buffer `claxon` decodes into, reused across frames.
+    block: Vec<i32>,
+    done: bool,
+}
+
+impl FlacReader {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let inner = claxon::FlacReader::open(path).map_err(|e| e.to_string())?;
+        let info = inner.streaminfo().into();
+        Ok(Self {
+            inner,
+            info,
+            frame: Vec::new(),
+            next: 0,
+            block: Vec::new(),
+            done: false,
+        })
+    }
+
+    pub fn info(&self) -> &StreamInfo {
+        &self.info
+    }
+
+    /// Decodes the next frame into `self.frame`; `false` once the stream is done.
+    fn next_frame(&mut self) -> Result<bool, String> {
+        self.frame.clear();
+        self.next = 0;
+        let buffer = std::mem::take(&mut self.block);
+        let Some(block) = self
+            .inner
+            .blocks()
+            .read_next_or_eof(buffer)
+            .map_err(|e| e.to_string())?
+        else {
+            return Ok(false);
+        };
+        self.frame.reserve_exact(block.len() as usize);
+        for i in 0..block.duration() {
+            self.frame
+                .extend((0..block.channels()).map(|ch| block.sample(ch, i)));
+        }
+        self.block = block.into_buffer();
+        Ok(true)
+    }
+}
+
+impl Iterator for FlacReader {
+    type Item = Result<i32, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.next == self.frame.len() {
+            if self.done {
+                return None;
+            }
+            match self.next_frame() {
+                Ok(true) => {}
+                Ok(false) => self.done = true,
+                Err(e) => {
+                    // Stop after reporting the error rather than resyncing.
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        self.next += 1;
+        Some(Ok(self.frame[self.next - 1]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn sine(len: usize, amplitude: f64) -> Vec<i64> {
+        (0..len)
+            .map(|i| ((i as f64 * 0.05).sin() * amplitude).round() as i64)
+            .collect()
+    }
+
+    fn noise(len: usize, bps: u32, seed: u64) -> Vec<i64> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let max = (1i64 << (bps - 1)) - 1;
+        (0..len).map(|_| rng.gen_range(-max - 1..=max)).collect()
+    }
+
+    fn write_wav(path: &Path, channels: u16, bits: u16, interleaved: &[i32]) {
+        let spec = hound::WavSpec {
+            channels,
+            sample_rate: 16_000,
+            bits_per_sample: bits,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        for &s in interleaved {
+            writer.write_sample(s).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    fn file_round_trip(channels: Vec<Vec<i64>>, bits: u16) {
+        let dir = tempfile::tempdir().unwrap();
+        let (wav, flac) = (dir.path().join("in.wav"), dir.path().join("out.flac"));
+        let frames = channels[0].len();
+        let interleaved: Vec<i32> = (0..frames)
+            .flat_map(|i| channels.iter().map(move |c| c[i] as i32))
+            .collect();
+        write_wav(&wav, channels.len() as u16, bits, &interleaved);
+        encode_wav(&wav, &flac).unwrap();
+
+        let info = read_info(&flac).unwrap();
+        assert_eq!(info.total_frames, frames as u64);
+        let reader = FlacReader::open(&flac).unwrap();
+        assert_eq!(reader.info().sample_rate, 16_000);
+        assert_eq!(reader.info().channels as usize, channels.len());
+        assert_eq!(reader.info().bits_per_sample, bits as u32);
+        let samples = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(samples, interleaved);
+    }
+
+    #[test]
+    fn mono_16_bit_with_partial_last_block_round_trips() {
+        file_round_trip(vec![sine(2 * BLOCK_SIZE + 123, 20_000.0)], 16);
+    }
+
+    #[test]
+    fn stereo_16_bit_round_trips() {
+        let frames = BLOCK_SIZE + 1;
+        let mut left = vec![0; frames];
+        left[BLOCK_SIZE..].copy_from_slice(&noise(1, 16, 1));
+        file_round_trip(vec![left, noise(frames, 16, 2)], 16);
+        file_round_trip(vec![sine(frames, 30_000.0), vec![7; frames]], 16);
+    }
+
+    #[test]
+    fn stereo_24_bit_round_trips() {
+        let frames = 3 * BLOCK_SIZE - 1;
+        file_round_trip(vec![sine(frames, 8_000_000.0), noise(frames, 24, 3)], 24);
+    }
+
+    #[test]
+    fn float_wav_is_stored_at_24_bits() {
+        let dir = tempfile::tempdir().unwrap();
+        let (wav, flac) = (dir.path().join("float.wav"), dir.path().join("float.flac"));
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 16_000,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let mut writer = hound::WavWriter::create(&wav, spec).unwrap();
+        for s in [0.0f32, 0.5, -1.0, 2.0] {
+            writer.write_sample(s).unwrap();
+        }
+        writer.finalize().unwrap();
+        encode_wav(&wav, &flac).unwrap();
+
+        let reader = FlacReader::open(&flac).unwrap();
+        assert_eq!(reader.info().bits_per_sample, 24);
+        let samples = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(samples, [0, 4_194_304, -8_388_607, 8_388_607]);
+    }
+
+    #[test]
+    fn reader_holds_one_frame_at_a_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let (wav, flac) = (dir.path().join("long.wav"), dir.path().join("long.flac"));
+        let frames = 60 * 16_000;
+        let samples: Vec<i32> = noise(frames, 16, 4).into_iter().map(|s| s as i32).collect();
+        write_wav(&wav, 1, 16, &samples);
+        encode_wav(&wav, &flac).unwrap();
+        drop(samples);
+
+        let mut reader = FlacReader::open(&flac).unwrap();
+        let mut count = 0;
+        while let Some(sample) = reader.next() {
+            sample.unwrap();
+            count += 1;
+            assert!(reader.frame.capacity() <= BLOCK_SIZE);
+        }
+        assert_eq!(count, frames);
+    }
+}
@@ -0,0 +1,62 @@
+//! Bounded in-memory log of recent errors, so failures can be inspected and shared.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+const LOG_CAPACITY: usize = 200;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LogEntry {
+    pub timestamp: DateTime<Utc>,
+    /// Command or subsystem that reported the error.
+    pub source: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LogBuffer {
+    entries: Arc<Mutex<VecDeque<LogEntry>>>,
+}
+
+impl LogBuffer {
+    /// Records an error, also echoing it to stderr; the oldest entry is evicted when full.
+    pub fn error(&self, source: &str, message: impl Into<String>) {
+        let entry = LogEntry {
+            timestamp: Utc::now(),
+            source: source.to_string(),
+            message: message.into(),
+        };
+        eprintln!("{}: {}", entry.source, entry.message);
+        if let Ok(mut entries) = self.entries.lock() {
+            if entries.len() == LOG_CAPACITY {
+                entries.pop_front();
+            }
+            entries.push_back(entry);
+        }
+    }
+
+    /// Logs the error of a failed result and passes the result through.
+    pub fn check<T>(&self, source: &str, result: Result<T, String>) -> Result<T, String> {
+        if let Err(e) = &result {
+            self.error(source, e.clone());
+        }
+        result
+    }
+
+    /// Runs a command's body, logging its error under `source`.
+    pub fn run<T>(&self, source: &str, f: impl FnOnce() -> Result<T, String>) -> Result<T, String> {
+        self.check(source, f())
+    }
+
+    /// The newest `limit` entries (all when `None`), oldest first.
+    pub fn recent(&self, limit: Option<usize>) -> Vec<LogEntry> {
+        let Ok(entries) = self.entries.lock() else {
+            return Vec::new();
+        };
+        let skip = limit.map_or(0, |limit| entries.len().saturating_sub(limit));
+        entries.iter().skip(skip).cloned().collect()
+    }
+}
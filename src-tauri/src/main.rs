@@ -1,26 +1,45 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     path::PathBuf,
     sync::{
-        atomic::{AtomicBool, Ordering},
-        mpsc, Arc, Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex,
     },
     thread::{self, JoinHandle},
-    time::{Duration, SystemTime, UNIX_EPOCH},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use cpal::{
     traits::{DeviceTrait, HostTrait, StreamTrait},
     SampleFormat, StreamConfig,
 };
+mod ann;
+mod audio;
 mod db;
+mod diarize;
 mod embedding;
+mod fbank;
+mod flac;
+mod logs;
+mod merge;
+mod naming;
+mod perf;
+mod pool;
 mod config;
 mod state;
+mod thumbnail;
+mod transcriber;
+use config::{AppConfig, InputDeviceSelection, RecordingFormat};
+use embedding::SimilarityMetric;
+use perf::Perf;
 use state::AppState;
-use db::{Crypto, Db, SegmentRecord, Session, Speaker, StoredEmbedding};
+use db::{
+    Crypto, Db, GlobalSpeakerStats, SegmentRecord, Session, Speaker, StoredEmbedding,
+    TimelineEntry, UndoSummary,
+};
 use chrono::Utc;
 use reqwest::blocking::{multipart, Client};
 use reqwest::Url;
@@ -29,6 +48,7 @@ use tauri::{
     image::Image,
     menu::{MenuBuilder, MenuId, MenuItem},
     tray::TrayIconBuilder,
+    webview::PageLoadEvent,
     Emitter, Manager, State,
 };
 
@@ -40,13 +60,50 @@ enum SampleChunk {
 
 const TARGET_SPEAKER_MS: u64 = 10_000;
 const MATCH_THRESHOLD: f32 = 0.78;
+/// Same operating point as `MATCH_THRESHOLD` (cosine 0.78 on unit vectors).
+const EUCLIDEAN_MATCH_THRESHOLD: f32 = 0.67;
+const BENCHMARK_CLIP_MS: u64 = 3_000;
+const BENCHMARK_SAMPLE_RATE: u32 = 16_000;
+const BENCHMARK_MAX_SECONDS: u64 = 60;
+const UNDO_WINDOW_SECS: i64 = 15 * 60;
+/// Introductions usually happen early, so only a speaker's first segments are scanned.
+const NAME_SUGGESTION_SEGMENTS: usize = 20;
+const RETENTION_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+const BACKUP_CHECK_INTERVAL: Duration = Duration::from_secs(10 * 60);
+const BACKUP_PREFIX: &str = "recall-backup-";
+/// Callback chunks buffered ahead of the WAV writer (roughly 10s at typical buffer sizes).
+const CHUNK_QUEUE_CAPACITY: usize = 1024;
+const WATCHDOG_INTERVAL: Duration = Duration::from_millis(250);
+const WATCHDOG_RESTART_ATTEMPTS: u32 = 3;
+const WATCHDOG_RESTART_DELAY: Duration = Duration::from_millis(500);
+/// How long the writer waits for more audio after stop before giving up on the callback.
+const STOP_DRAIN_TIMEOUT: Duration = Duration::from_millis(200);
+const MAX_DOWNLOAD_BYTES: u64 = 500 * 1024 * 1024;
+const MODEL_DOWNLOAD_RETRY_DELAY: Duration = Duration::from_secs(2);
+const NOTHING_TRANSCRIBED: &str = "NothingTranscribed: the server returned no transcript or segments. The audio may be silent, or transcription failed on the server. No session was created.";
+/// Audio kept either side of the detected speech when trimming silence.
+const TRIM_PAD_MS: u64 = 250;
+/// How much of a multichannel file is checked for channels cancelling out when averaged.
+const DOWNMIX_PROBE_SECS: usize = 30;
+const MAX_WAVEFORM_BUCKETS: usize = 100_000;
+const DEFAULT_SEGMENT_SEARCH_LIMIT: usize = 100;
+/// Cached waveforms kept before the cache is emptied.
+const WAVEFORM_CACHE_ENTRIES: usize = 64;
+const DEFAULT_HISTOGRAM_BINS: usize = 40;
+/// Embeddings compared at most by `similarity_histogram` (about two million pairs); larger
+/// sets are sampled evenly.
+const MAX_HISTOGRAM_EMBEDDINGS: usize = 2_000;
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 struct ApiSegment {
     speaker: String,
     start_ms: u64,
     end_ms: u64,
     text: String,
+    /// Stored, but not used to learn voiceprints (music, crosstalk).
+    #[serde(default)]
+    exclude_from_embedding: bool,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -58,12 +115,184 @@ struct ApiTranscribeResponse {
     audio_url: Option<String>,
 }
 
+impl ApiTranscribeResponse {
+    /// Neither transcript text nor segments: silent audio or a failed server job.
+    fn is_empty(&self) -> bool {
+        self.transcript.trim().is_empty() && self.segments.as_ref().is_none_or(Vec::is_empty)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct TranscribeEvent {
+    path: String,
+    transcript: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct TranscribeProgress {
+    path: String,
+    percent: f32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct TranscribeWarning {
+    path: String,
+    warning: String,
+}
+
+/// One line of a streamed (`application/x-ndjson`) transcription response: any number of
+/// `{"progress": <0-100>}` lines, then a single `{"result": <response>}` carrying the
+/// usual JSON body, or `{"error": "<message>"}` if the job failed.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum TranscribeStreamLine {
+    Progress { progress: f32 },
+    Result { result: serde_json::Value },
+    Error { error: String },
+}
+
 #[derive(Debug, Serialize)]
+struct TranscriptionDebug {
+    raw: serde_json::Value,
+    /// Segments after normalization, as they would be stored.
+    normalized_segments: Option<Vec<ApiSegment>>,
+    /// Why the body doesn't match `ApiTranscribeResponse`, if it doesn't.
+    decode_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
 struct AppStatus {
     encryption_enabled: bool,
     db_open: bool,
     needs_password: bool,
     api_base: Option<String>,
+    /// False when ONNX Runtime failed to start; speakers aren't matched until it does.
+    embeddings_available: bool,
+    embeddings_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Diagnostics {
+    app_version: &'static str,
+    os: &'static str,
+    arch: &'static str,
+    data_dir: PathBuf,
+    config_path: PathBuf,
+    db_path: PathBuf,
+    db_exists: bool,
+    model_path: PathBuf,
+    model_exists: bool,
+    encryption_enabled: bool,
+    db_open: bool,
+    api_base: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct SessionExport {
+    session: Session,
+    segments: Vec<SegmentRecord>,
+    speakers: Vec<Speaker>,
+}
+
+const SPEAKER_DIRECTORY_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SpeakerDirectoryFile {
+    version: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SpeakerDirectoryEntry {
+    label: Option<String>,
+    embeddings: Vec<Vec<f32>>,
+}
+
+/// One speaker in the `manifest.json` written by `export_embeddings_npy`.
+#[derive(Debug, Serialize)]
+struct NpyManifestEntry {
+    speaker_id: String,
+    label: Option<String>,
+    file: String,
+    count: usize,
+    dim: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct SpeakerImportSummary {
+    merged: usize,
+    created: usize,
+    embeddings: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct StorageStats {
+    db_bytes: u64,
+    models_bytes: u64,
+    temp_bytes: u64,
+    sessions: i64,
+    segments: i64,
+    speakers: i64,
+    embeddings: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct RematchChange {
+    /// The session's embeddings that moved, all of one speaker.
+    embedding_ids: Vec<String>,
+    from_label: Option<String>,
+    to_label: Option<String>,
+    /// Similarity to the matched speaker; `None` when split off into a new speaker.
+    score: Option<f32>,
+    segments_updated: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct RematchReport {
+    session_id: String,
+    threshold: f32,
+    changes: Vec<RematchChange>,
+}
+
+/// Pair counts per similarity bin; bin `i` covers
+/// `[-1 + 2i/bins, -1 + 2(i+1)/bins)`, with the last bin closed at 1.
+#[derive(Debug, Serialize)]
+struct SimilarityHistogram {
+    metric: SimilarityMetric,
+    /// The threshold matching currently uses for `metric`.
+    threshold: f32,
+    bins: usize,
+    same_speaker: Vec<u64>,
+    different_speaker: Vec<u64>,
+    embeddings_total: usize,
+    /// Fewer than `embeddings_total` when the set was sampled down.
+    embeddings_used: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct RetentionReport {
+    retention_days: u32,
+    cutoff: chrono::DateTime<Utc>,
+    session_ids: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct LocalDiarization {
+    session_id: String,
+    segments: usize,
+    speakers: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct BenchmarkReport {
+    clips: u64,
+    clip_ms: u64,
+    elapsed_ms: u64,
+    clips_per_sec: f64,
+    avg_latency_ms: f64,
 }
 
 #[derive(Debug)]
@@ -82,10 +311,473 @@ impl AudioClip {
     }
 }
 
+/// Audio kept for embedding: at most `TARGET_SPEAKER_MS` per diarized speaker.
+#[derive(Debug)]
+struct SpeakerAudio {
+    by_speaker: HashMap<String, Vec<f32>>,
+    sample_rate: u32,
+}
+
+/// A stretch of one speaker's audio to embed, in mono sample indices.
+struct SpeakerPiece {
+    speaker: String,
+    start: usize,
+    end: usize,
+    samples: Vec<f32>,
+}
+
+/// Samples in `[-1, 1]`, decoded as the file is read.
+type Samples = Box<dyn Iterator<Item = Result<f32, String>>>;
+
 #[derive(Debug)]
 struct Recorder {
     stop_tx: Option<mpsc::Sender<()>>,
     handle: Option<JoinHandle<Result<PathBuf, String>>>,
+    path: PathBuf,
+    counters: Arc<RecordingCounters>,
+    sample_rate: u32,
+    /// Set before stopping to delete the recording instead of keeping it.
+    discard: Arc<AtomicBool>,
+}
+
+#[derive(Debug, Default)]
+struct RecordingCounters {
+    /// Set by the stream error callback when the device went away.
+    device_lost: AtomicBool,
+    restarts: AtomicU64,
+    /// Callback chunks that never reached the writer.
+    dropped_chunks: AtomicU64,
+    /// Samples written, counting every channel, and how many of them were clipped.
+    samples: AtomicU64,
+    clipped_samples: AtomicU64,
+    log: logs::LogBuffer,
+}
+
+impl RecordingCounters {
+    fn snapshot(&self, path: PathBuf) -> RecordingStats {
+        RecordingStats {
+            path,
+            restarts: self.restarts.load(Ordering::Relaxed),
+            dropped_chunks: self.dropped_chunks.load(Ordering::Relaxed),
+            clipped_percent: audio::clipped_percent(
+                self.clipped_samples.load(Ordering::Relaxed),
+                self.samples.load(Ordering::Relaxed),
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct RecordingStats {
+    path: PathBuf,
+    restarts: u64,
+    dropped_chunks: u64,
+    clipped_percent: f32,
+}
+
+#[derive(Debug, Serialize)]
+struct RecordingStatus {
+    recording: bool,
+    stats: Option<RecordingStats>,
+    /// Rate the active recording actually runs at.
+    sample_rate: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct LevelEvent {
+    rms: f32,
+    /// `rms` in dBFS, floored at -100 for silence.
+    db: f32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ClippingEvent {
+    /// Share of the last second's samples that were clipped.
+    percent: f32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct WaveformEvent {
+    /// Min/max pairs, oldest first, continuing where the previous event ended.
+    peaks: Vec<f32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct RecordingErrorEvent {
+    error: String,
+    /// Stats for what was captured before the stream could not be recovered.
+    #[serde(flatten)]
+    stats: RecordingStats,
+}
+
+#[derive(Debug, Clone, Default)]
+struct RecordingOptions {
+    device: Option<InputDeviceSelection>,
+    noise_gate: Option<audio::GateSettings>,
+    /// Target peak in dBFS for post-recording gain normalization.
+    normalize_target_db: Option<f32>,
+    /// 16 or 32; only affects float devices, integer input is always written as 16-bit.
+    bit_depth: u16,
+    level_meter_hz: u32,
+    live_waveform: bool,
+    clipping_warn_percent: f32,
+    /// Sample rate to ask the device for instead of its default, if it supports it.
+    preferred_sample_rate: Option<u32>,
+    /// Session the finished recording is appended to when it gets transcribed.
+    append_to: Option<String>,
+    log: logs::LogBuffer,
+}
+
+impl RecordingOptions {
+    fn from_config(cfg: &AppConfig, log: logs::LogBuffer) -> Self {
+        Self {
+            device: cfg.input_device.clone(),
+            noise_gate: cfg.noise_gate_enabled.then(|| audio::GateSettings {
+                threshold_db: cfg.noise_gate_threshold_db(),
+                hold_ms: cfg.noise_gate_hold_ms(),
+            }),
+            normalize_target_db: cfg.normalize_gain.then(|| cfg.normalize_target_db()),
+            bit_depth: cfg.recording_bit_depth(),
+            level_meter_hz: cfg.level_meter_hz(),
+            live_waveform: cfg.live_waveform,
+            clipping_warn_percent: cfg.clipping_warn_percent(),
+            preferred_sample_rate: cfg.preferred_sample_rate,
+            append_to: None,
+            log,
+        }
+    }
+}
+
+/// cpal can only record an output device's mix (loopback) through WASAPI.
+const LOOPBACK_SUPPORTED: bool = cfg!(target_os = "windows");
+const LOOPBACK_UNSUPPORTED: &str = "Loopback capture is only supported on Windows. On macOS route system audio through a virtual input device (e.g. BlackHole); on Linux select a PulseAudio/PipeWire monitor source as the input.";
+
+#[derive(Debug, Serialize)]
+struct InputDeviceInfo {
+    name: String,
+    /// Output device captured via loopback.
+    loopback: bool,
+    /// Input whose name suggests it carries system audio (monitor source, virtual device).
+    system_audio: bool,
+    is_default: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct BufferSizeRange {
+    min_frames: u32,
+    max_frames: u32,
+}
+
+#[derive(Debug, Serialize)]
+struct SupportedConfigInfo {
+    channels: u16,
+    min_sample_rate: u32,
+    max_sample_rate: u32,
+    sample_format: String,
+    /// `None` when the platform can't report it before a stream is opened.
+    buffer_size: Option<BufferSizeRange>,
+}
+
+#[derive(Debug, Serialize)]
+struct AudioDeviceInfo {
+    name: String,
+    loopback: bool,
+    /// The config recordings are opened with.
+    sample_rate: u32,
+    channels: u16,
+    sample_format: String,
+    buffer_size: Option<BufferSizeRange>,
+    supported_configs: Vec<SupportedConfigInfo>,
+}
+
+fn buffer_size_range(size: &cpal::SupportedBufferSize) -> Option<BufferSizeRange> {
+    match size {
+        cpal::SupportedBufferSize::Range { min, max } => Some(BufferSizeRange {
+            min_frames: *min,
+            max_frames: *max,
+        }),
+        cpal::SupportedBufferSize::Unknown => None,
+    }
+}
+
+/// Name fragments of inputs that typically carry system audio.
+const SYSTEM_AUDIO_HINTS: &[&str] = &[
+    "monitor",
+    "loopback",
+    "blackhole",
+    "stereo mix",
+    "soundflower",
+];
+
+fn looks_like_system_audio(name: &str) -> bool {
+    let name = name.to_lowercase();
+    SYSTEM_AUDIO_HINTS.iter().any(|hint| name.contains(hint))
+}
+
+fn find_device<I>(devices: I, name: &str) -> Option<cpal::Device>
+where
+    I: Iterator<Item = cpal::Device>,
+{
+    devices
+        .into_iter()
+        .find(|device| device.name().map(|n| n == name).unwrap_or(false))
+}
+
+fn open_capture_device(
+    host: &cpal::Host,
+    selection: Option<&InputDeviceSelection>,
+) -> Result<(cpal::Device, cpal::SupportedStreamConfig), String> {
+    match selection {
+        Some(sel) if sel.loopback => {
+            if !LOOPBACK_SUPPORTED {
+                return Err(LOOPBACK_UNSUPPORTED.into());
+            }
+            let devices = host
+                .output_devices()
+                .map_err(|e| format!("Failed to list output devices: {e}"))?;
+            let device = find_device(devices, &sel.name)
+                .ok_or_else(|| format!("Output device not found: {}", sel.name))?;
+            let config = device
+                .default_output_config()
+                .map_err(|e| format!("Failed to get loopback config: {e}"))?;
+            Ok((device, config))
+        }
+        Some(sel) => {
+            let devices = host
+                .input_devices()
+                .map_err(|e| format!("Failed to list input devices: {e}"))?;
+            let device = find_device(devices, &sel.name)
+                .ok_or_else(|| format!("Input device not found: {}", sel.name))?;
+            let config = device
+                .default_input_config()
+                .map_err(|e| format!("Failed to get input config: {e}"))?;
+            Ok((device, config))
+        }
+        None => {
+            let device = host
+                .default_input_device()
+                .ok_or_else(|| "No input device found".to_string())?;
+            let config = device
+                .default_input_config()
+                .map_err(|e| format!("Failed to get input config: {e}"))?;
+            Ok((device, config))
+        }
+    }
+}
+
+/// A supported input config running at `rate`, preferring the default config's sample
+/// format and channel count; `default` when the device can't record at that rate.
+fn with_preferred_rate(
+    device: &cpal::Device,
+    default: cpal::SupportedStreamConfig,
+    rate: u32,
+) -> cpal::SupportedStreamConfig {
+    if default.sample_rate().0 == rate {
+        return default;
+    }
+    let Ok(ranges) = device.supported_input_configs() else {
+        return default;
+    };
+    ranges
+        .filter(|range| range.min_sample_rate().0 <= rate && rate <= range.max_sample_rate().0)
+        .filter(|range| {
+            matches!(
+                range.sample_format(),
+                SampleFormat::F32 | SampleFormat::I16 | SampleFormat::U16
+            )
+        })
+        .max_by_key(|range| {
+            (
+                range.sample_format() == default.sample_format(),
+                range.channels() == default.channels(),
+            )
+        })
+        .map(|range| range.with_sample_rate(cpal::SampleRate(rate)))
+        .unwrap_or(default)
+}
+
+/// Builds and starts a capture stream feeding `data_tx`, converting to `SampleChunk`s and
+/// applying the noise gate. Device loss is flagged in `counters` for the watchdog.
+fn build_input_stream(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    sample_format: SampleFormat,
+    data_tx: mpsc::SyncSender<SampleChunk>,
+    stop_flag: Arc<AtomicBool>,
+    mut gate: Option<audio::NoiseGate>,
+    counters: Arc<RecordingCounters>,
+) -> Result<cpal::Stream, String> {
+    let err_counters = counters.clone();
+    let err_fn = move |err: cpal::StreamError| {
+        err_counters.log.error("recording", err.to_string());
+        if matches!(err, cpal::StreamError::DeviceNotAvailable) {
+            err_counters.device_lost.store(true, Ordering::SeqCst);
+        }
+    };
+    let send = move |chunk: SampleChunk| {
+        // Never block the audio callback; a full queue means the writer fell behind.
+        if data_tx.try_send(chunk).is_err() {
+            counters.dropped_chunks.fetch_add(1, Ordering::Relaxed);
+        }
+    };
+
+    let stream = match sample_format {
+        SampleFormat::F32 => device.build_input_stream(
+            config,
+            move |data: &[f32], _| {
+                if !stop_flag.load(Ordering::Relaxed) {
+                    let mut chunk = data.to_vec();
+                    if let Some(gate) = gate.as_mut() {
+                        gate.process(&mut chunk);
+                    }
+                    send(SampleChunk::F32(chunk));
+                }
+            },
+            err_fn,
+            None,
+        ),
+        SampleFormat::I16 => device.build_input_stream(
+            config,
+            move |data: &[i16], _| {
+                if !stop_flag.load(Ordering::Relaxed) {
+                    let mut chunk = data.to_vec();
+                    if let Some(gate) = gate.as_mut() {
+                        gate.process_i16(&mut chunk);
+                    }
+                    send(SampleChunk::I16(chunk));
+                }
+            },
+            err_fn,
+            None,
+        ),
+        SampleFormat::U16 => device.build_input_stream(
+            config,
+            move |data: &[u16], _| {
+                if !stop_flag.load(Ordering::Relaxed) {
+                    let mut converted: Vec<i16> =
+                        data.iter().map(|s| (*s as i32 - 32768) as i16).collect();
+                    if let Some(gate) = gate.as_mut() {
+                        gate.process_i16(&mut converted);
+                    }
+                    send(SampleChunk::I16(converted));
+                }
+            },
+            err_fn,
+            None,
+        ),
+        _ => return Err("Unsupported sample format".into()),
+    }
+    .map_err(|e| format!("Failed to build input stream: {e}"))?;
+
+    stream
+        .play()
+        .map_err(|e| format!("Failed to start input stream: {e}"))?;
+    Ok(stream)
+}
+
+/// WAV layout for a recording from `sample_format` input. Float input is written as
+/// 16-bit PCM when `bit_depth` is 16; integer input is always 16-bit.
+fn recording_wav_spec(
+    sample_format: SampleFormat,
+    channels: u16,
+    sample_rate: u32,
+    bit_depth: u16,
+) -> Result<hound::WavSpec, String> {
+    let (bits_per_sample, sample_format) = match sample_format {
+        SampleFormat::F32 if bit_depth == 16 => (16, hound::SampleFormat::Int),
+        SampleFormat::F32 => (32, hound::SampleFormat::Float),
+        SampleFormat::I16 | SampleFormat::U16 => (16, hound::SampleFormat::Int),
+        _ => return Err("Unsupported sample format".into()),
+    };
+    Ok(hound::WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample,
+        sample_format,
+    })
+}
+
+/// Appends `chunk` to `writer`, converting float samples to 16-bit PCM when the file was
+/// opened with an integer [`recording_wav_spec`].
+fn write_chunk<W: std::io::Write + std::io::Seek>(
+    writer: &mut hound::WavWriter<W>,
+    chunk: SampleChunk,
+) -> Result<(), String> {
+    let to_i16 = writer.spec().sample_format == hound::SampleFormat::Int;
+    match chunk {
+        SampleChunk::F32(data) if to_i16 => {
+            for sample in data {
+                writer
+                    .write_sample(audio::f32_to_i16(sample))
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+        SampleChunk::F32(data) => {
+            for sample in data {
+                writer.write_sample(sample).map_err(|e| e.to_string())?;
+            }
+        }
+        SampleChunk::I16(data) => {
+            for sample in data {
+                writer.write_sample(sample).map_err(|e| e.to_string())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Next chunk for the writer. The callback's sender going away is the signal that no more
+/// audio is coming, so everything it queued before stopping gets written; the timeout only
+/// matters for a backend that keeps the callback alive after its stream is dropped.
+fn next_chunk<T>(data_rx: &mpsc::Receiver<T>, stop_flag: &AtomicBool) -> Option<T> {
+    loop {
+        match data_rx.recv_timeout(STOP_DRAIN_TIMEOUT) {
+            Ok(chunk) => return Some(chunk),
+            Err(RecvTimeoutError::Disconnected) => return None,
+            Err(RecvTimeoutError::Timeout) if stop_flag.load(Ordering::SeqCst) => return None,
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+    }
+}
+
+/// Normalizes a finished recording if configured, then files the path that will actually
+/// be transcribed under the session it's appended to.
+fn finish_recording(
+    output: PathBuf,
+    options: &RecordingOptions,
+    append_targets: &Mutex<HashMap<PathBuf, String>>,
+) -> Result<PathBuf, String> {
+    let mut output = output;
+    if let Some(target_db) = options.normalize_target_db {
+        if let Some(normalized) = audio::normalize_wav(&output, target_db)? {
+            let _ = std::fs::remove_file(&output);
+            output = normalized;
+        }
+    }
+    if let Some(session_id) = &options.append_to {
+        append_targets
+            .lock()
+            .map_err(|_| "append targets lock")?
+            .insert(output.clone(), session_id.clone());
+    }
+    Ok(output)
+}
+
+/// Retries `build` a few times after the device dropped out.
+fn restart_input_stream(
+    device: &cpal::Device,
+    mut build: impl FnMut() -> Result<cpal::Stream, String>,
+) -> Result<cpal::Stream, String> {
+    let mut last_error = String::new();
+    for _ in 0..WATCHDOG_RESTART_ATTEMPTS {
+        thread::sleep(WATCHDOG_RESTART_DELAY);
+        match build() {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = e,
+        }
+    }
+    let name = device.name().unwrap_or_else(|_| "input device".into());
+    Err(format!("Lost {name}, restart failed: {last_error}"))
 }
 
 #[derive(Default)]
@@ -94,19 +786,24 @@ struct RecordingManager {
 }
 
 impl RecordingManager {
-    fn start(&self) -> Result<PathBuf, String> {
+    fn start(&self, options: RecordingOptions, app: tauri::AppHandle) -> Result<PathBuf, String> {
         let mut guard = self.current.lock().map_err(|_| "Lock poisoned")?;
         if guard.is_some() {
             return Err("Recording already in progress".into());
         }
+        if !matches!(options.bit_depth, 16 | 32) {
+            return Err(format!(
+                "Unsupported recording bit depth {} (use 16 or 32)",
+                options.bit_depth
+            ));
+        }
 
         let host = cpal::default_host();
-        let device = host
-            .default_input_device()
-            .ok_or_else(|| "No input device found".to_string())?;
-        let input_config = device
-            .default_input_config()
-            .map_err(|e| format!("Failed to get input config: {e}"))?;
+        let (device, mut input_config) = open_capture_device(&host, options.device.as_ref())?;
+        let loopback = options.device.as_ref().is_some_and(|sel| sel.loopback);
+        if let (Some(rate), false) = (options.preferred_sample_rate, loopback) {
+            input_config = with_preferred_rate(&device, input_config, rate);
+        }
         let sample_format = input_config.sample_format();
         let config: StreamConfig = input_config.into();
         let sample_rate = config.sample_rate.0;
@@ -119,134 +816,180 @@ impl RecordingManager {
         let output = std::env::temp_dir().join(format!("recall-{timestamp}.wav"));
         let output_for_api = output.clone();
         let (stop_tx, stop_rx) = mpsc::channel::<()>();
+        let counters = Arc::new(RecordingCounters {
+            log: options.log.clone(),
+            ..Default::default()
+        });
+        let thread_counters = counters.clone();
+        let discard = Arc::new(AtomicBool::new(false));
+        let thread_discard = discard.clone();
 
         let output_for_thread = output.clone();
+        let recorder_path = output.clone();
+        let append_targets = app
+            .try_state::<AppState>()
+            .map(|app_state| app_state.append_targets.clone())
+            .unwrap_or_default();
         let handle = thread::spawn(move || -> Result<PathBuf, String> {
-            let wav_spec = match sample_format {
-                SampleFormat::F32 => hound::WavSpec {
-                    channels,
-                    sample_rate,
-                    bits_per_sample: 32,
-                    sample_format: hound::SampleFormat::Float,
-                },
-                SampleFormat::I16 | SampleFormat::U16 => hound::WavSpec {
-                    channels,
-                    sample_rate,
-                    bits_per_sample: 16,
-                    sample_format: hound::SampleFormat::Int,
-                },
-                _ => return Err("Unsupported sample format".into()),
-            };
+            let wav_spec =
+                recording_wav_spec(sample_format, channels, sample_rate, options.bit_depth)?;
 
-            let (data_tx, data_rx) = mpsc::channel::<SampleChunk>();
+            let (data_tx, data_rx) = mpsc::sync_channel::<SampleChunk>(CHUNK_QUEUE_CAPACITY);
             let stop_flag = Arc::new(AtomicBool::new(false));
-            let cb_flag = stop_flag.clone();
-            let err_fn = |err| eprintln!("recording error: {err}");
-
-            let stream = match sample_format {
-                SampleFormat::F32 => {
-                    let tx = data_tx.clone();
-                    device
-                        .build_input_stream(
-                            &config,
-                            move |data: &[f32], _| {
-                                if !cb_flag.load(Ordering::Relaxed) {
-                                    let _ = tx.send(SampleChunk::F32(data.to_vec()));
-                                }
-                            },
-                            err_fn,
-                            None,
-                        )
-                        .map_err(|e| format!("Failed to build input stream: {e}"))?
-                }
-                SampleFormat::I16 => {
-                    let tx = data_tx.clone();
-                    device
-                        .build_input_stream(
-                            &config,
-                            move |data: &[i16], _| {
-                                if !cb_flag.load(Ordering::Relaxed) {
-                                    let _ = tx.send(SampleChunk::I16(data.to_vec()));
-                                }
-                            },
-                            err_fn,
-                            None,
-                        )
-                        .map_err(|e| format!("Failed to build input stream: {e}"))?
-                }
-                SampleFormat::U16 => {
-                    let tx = data_tx.clone();
-                    device
-                        .build_input_stream(
-                            &config,
-                            move |data: &[u16], _| {
-                                if !cb_flag.load(Ordering::Relaxed) {
-                                    let converted: Vec<i16> =
-                                        data.iter().map(|s| (*s as i32 - 32768) as i16).collect();
-                                    let _ = tx.send(SampleChunk::I16(converted));
-                                }
-                            },
-                            err_fn,
-                            None,
-                        )
-                        .map_err(|e| format!("Failed to build input stream: {e}"))?
-                }
-                _ => return Err("Unsupported sample format".into()),
+            let new_gate = || {
+                options
+                    .noise_gate
+                    .map(|settings| audio::NoiseGate::new(settings, sample_rate, channels))
             };
 
-            stream
-                .play()
-                .map_err(|e| format!("Failed to start input stream: {e}"))?;
+            let mut stream = build_input_stream(
+                &device,
+                &config,
+                sample_format,
+                data_tx.clone(),
+                stop_flag.clone(),
+                new_gate(),
+                thread_counters.clone(),
+            )
+            .map(Some)?;
 
             let writer_output = output_for_thread.clone();
             let writer_stop = stop_flag.clone();
+            let writer_app = app.clone();
+            let writer_counters = thread_counters.clone();
+            let mut meter = audio::LevelMeter::new(options.level_meter_hz);
+            // One second of audio per clipping check.
+            let mut clip_meter = audio::ClipMeter::new(sample_rate as u64 * channels as u64);
+            let warn_percent = options.clipping_warn_percent;
+            let mut waveform = options
+                .live_waveform
+                .then(|| audio::WaveformMeter::new(sample_rate, channels, options.level_meter_hz));
             let writer = thread::spawn(move || -> Result<(), String> {
                 let mut writer = hound::WavWriter::create(&writer_output, wav_spec)
                     .map_err(|e| e.to_string())?;
-                for chunk in data_rx.iter() {
-                    if writer_stop.load(Ordering::SeqCst) {
-                        break;
-                    }
-                    match chunk {
+                while let Some(chunk) = next_chunk(&data_rx, &writer_stop) {
+                    let (clipped, count) = match &chunk {
                         SampleChunk::F32(data) => {
-                            for sample in data {
-                                writer.write_sample(sample).map_err(|e| e.to_string())?;
-                            }
+                            meter.add(data.iter().copied());
+                            (clip_meter.add(data.iter().copied()), data.len())
                         }
                         SampleChunk::I16(data) => {
-                            for sample in data {
-                                writer.write_sample(sample).map_err(|e| e.to_string())?;
+                            let scaled = data.iter().map(|s| *s as f32 / 32768.0);
+                            meter.add(scaled.clone());
+                            (clip_meter.add(scaled), data.len())
+                        }
+                    };
+                    writer_counters
+                        .samples
+                        .fetch_add(count as u64, Ordering::Relaxed);
+                    writer_counters
+                        .clipped_samples
+                        .fetch_add(clipped, Ordering::Relaxed);
+                    let clipping = clip_meter.take_if_due().filter(|p| *p > warn_percent);
+                    if let Some(percent) = clipping {
+                        let _ = writer_app.emit("recording:clipping", ClippingEvent { percent });
+                    }
+                    if let Some(rms) = meter.take_if_due() {
+                        let db = if rms > 0.0 {
+                            (20.0 * rms.log10()).max(-100.0)
+                        } else {
+                            -100.0
+                        };
+                        let _ = writer_app.emit("recording:level", LevelEvent { rms, db });
+                    }
+                    if let Some(waveform) = waveform.as_mut() {
+                        match &chunk {
+                            SampleChunk::F32(data) => waveform.add(data.iter().copied()),
+                            SampleChunk::I16(data) => {
+                                waveform.add(data.iter().map(|s| *s as f32 / 32768.0))
                             }
                         }
+                        if let Some(peaks) = waveform.take_if_due() {
+                            let _ = writer_app.emit("recording:waveform", WaveformEvent { peaks });
+                        }
                     }
+                    write_chunk(&mut writer, chunk)?;
                 }
                 writer.finalize().map_err(|e| e.to_string())?;
                 Ok(())
             });
 
-            let _ = stop_rx.recv();
+            let mut failure = None;
+            while let Err(RecvTimeoutError::Timeout) = stop_rx.recv_timeout(WATCHDOG_INTERVAL) {
+                if !thread_counters.device_lost.swap(false, Ordering::SeqCst) {
+                    continue;
+                }
+                stream = None;
+                match restart_input_stream(&device, || {
+                    build_input_stream(
+                        &device,
+                        &config,
+                        sample_format,
+                        data_tx.clone(),
+                        stop_flag.clone(),
+                        new_gate(),
+                        thread_counters.clone(),
+                    )
+                }) {
+                    Ok(restarted) => {
+                        stream = Some(restarted);
+                        thread_counters.restarts.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Err(e) => {
+                        failure = Some(e);
+                        break;
+                    }
+                }
+            }
             stop_flag.store(true, Ordering::SeqCst);
+            // Dropping the stream releases the callback and its sender; once ours is gone too
+            // the writer finishes what's queued and stops.
             drop(stream);
-            // allow callback to unwind
-            thread::sleep(Duration::from_millis(50));
             drop(data_tx);
-            let _ = writer
-                .join()
-                .map_err(|_| "Writer join error".to_string())??;
+            let written = writer.join().map_err(|_| "Writer join error".to_string())?;
+            if thread_discard.load(Ordering::SeqCst) {
+                let _ = std::fs::remove_file(&output);
+                return Ok(output);
+            }
+            written?;
+            let output = finish_recording(output, &options, &append_targets)?;
+            let stats = thread_counters.snapshot(output.clone());
+            if let Some(error) = failure {
+                // Nothing is recording any more, so a new recording may start right away.
+                if let Some(manager) = app.try_state::<RecordingManager>() {
+                    manager.forget(&recorder_path);
+                }
+                let _ = app.emit(
+                    "recording:error",
+                    RecordingErrorEvent {
+                        error,
+                        stats: stats.clone(),
+                    },
+                );
+            }
+            let _ = app.emit("recording:stats", stats);
             Ok(output)
         });
 
         *guard = Some(Recorder {
             stop_tx: Some(stop_tx),
             handle: Some(handle),
+            path: output_for_api.clone(),
+            counters,
+            sample_rate,
+            discard,
         });
 
         Ok(output_for_api)
     }
 
     fn stop(&self) -> Result<PathBuf, String> {
-        let mut guard = self.current.lock().map_err(|_| "Lock poisoned")?;
-        let mut recorder = guard
+        // The lock is released before joining, as the recording thread may take it to
+        // `forget` itself.
+        let mut recorder = self
+            .current
+            .lock()
+            .map_err(|_| "Lock poisoned")?
             .take()
             .ok_or_else(|| "No active recording".to_string())?;
 
@@ -261,393 +1004,4105 @@ impl RecordingManager {
 
         Err("No recorder thread found".into())
     }
+
+    /// Stops the active recording and deletes its file. Returns whether one was running.
+    fn cancel(&self) -> Result<bool, String> {
+        let taken = self.current.lock().map_err(|_| "Lock poisoned")?.take();
+        let Some(mut recorder) = taken else {
+            return Ok(false);
+        };
+        recorder.discard.store(true, Ordering::SeqCst);
+        if let Some(tx) = recorder.stop_tx.take() {
+            let _ = tx.send(());
+        }
+        if let Some(handle) = recorder.handle.take() {
+            // The thread removes the file itself once the writer has shut down.
+            let _ = handle.join().map_err(|_| "Join error".to_string())?;
+        }
+        let _ = std::fs::remove_file(&recorder.path);
+        Ok(true)
+    }
+
+    /// Drops the recorder writing to `path`, if it is still the current one, without
+    /// stopping or joining it; for a recording thread that has ended by itself.
+    fn forget(&self, path: &std::path::Path) {
+        if let Ok(mut current) = self.current.lock() {
+            if current.as_ref().is_some_and(|r| r.path == path) {
+                *current = None;
+            }
+        }
+    }
+
+    fn status(&self) -> Result<RecordingStatus, String> {
+        let guard = self.current.lock().map_err(|_| "Lock poisoned")?;
+        Ok(RecordingStatus {
+            recording: guard.is_some(),
+            stats: guard
+                .as_ref()
+                .map(|recorder| recorder.counters.snapshot(recorder.path.clone())),
+            sample_rate: guard.as_ref().map(|recorder| recorder.sample_rate),
+        })
+    }
+
+    /// Stops and finalizes the active recording, if any, returning its path.
+    fn stop_if_recording(&self) -> Result<Option<PathBuf>, String> {
+        let active = self.current.lock().map_err(|_| "Lock poisoned")?.is_some();
+        if !active {
+            return Ok(None);
+        }
+        self.stop().map(Some)
+    }
+
+    /// Like [`Self::stop_if_recording`], but only stops the recording writing to `path`.
+    fn stop_if_writing(&self, path: &std::path::Path) -> Result<Option<PathBuf>, String> {
+        let ours = self
+            .current
+            .lock()
+            .map_err(|_| "Lock poisoned")?
+            .as_ref()
+            .is_some_and(|recorder| recorder.path == path);
+        if !ours {
+            return Ok(None);
+        }
+        self.stop().map(Some)
+    }
+}
+
+#[tauri::command]
+fn start_recording(
+    app: tauri::AppHandle,
+    state: State<RecordingManager>,
+    app_state: State<AppState>,
+) -> Result<PathBuf, String> {
+    let cfg = app_state.config.lock().map_err(|_| "config lock")?.clone();
+    let options = RecordingOptions::from_config(&cfg, app_state.logs.clone());
+    let result = state.start(options, app);
+    app_state.logs.check("start_recording", result)
 }
 
 #[tauri::command]
-fn start_recording(state: State<RecordingManager>) -> Result<PathBuf, String> {
-    state.start()
+fn stop_recording(
+    app: tauri::AppHandle,
+    state: State<RecordingManager>,
+    app_state: State<AppState>,
+) -> Result<PathBuf, String> {
+    let path = app_state.logs.check("stop_recording", state.stop())?;
+    let auto_transcribe = app_state
+        .config
+        .lock()
+        .map_err(|_| "config lock")?
+        .auto_transcribe_on_stop;
+    if auto_transcribe {
+        spawn_transcription(app, path.clone());
+    }
+    Ok(path)
 }
 
+/// Starts recording for an existing session, e.g. a recurring meeting. When the recording
+/// is transcribed, its transcript and segments are appended to that session instead of
+/// creating a new one.
 #[tauri::command]
-fn stop_recording(state: State<RecordingManager>) -> Result<PathBuf, String> {
-    state.stop()
+fn start_recording_for_session(
+    session_id: String,
+    app: tauri::AppHandle,
+    state: State<RecordingManager>,
+    app_state: State<AppState>,
+) -> Result<PathBuf, String> {
+    app_state.logs.run("start_recording_for_session", || {
+        {
+            let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+            let db = db_guard.as_ref().ok_or("Database not initialized")?;
+            if db.get_session(&session_id)?.is_none() {
+                return Err(format!("Session not found: {session_id}"));
+            }
+        }
+        let cfg = app_state.config.lock().map_err(|_| "config lock")?.clone();
+        let mut options = RecordingOptions::from_config(&cfg, app_state.logs.clone());
+        options.append_to = Some(session_id);
+        state.start(options, app)
+    })
 }
 
+/// Stops the current recording without keeping it; `false` when nothing was recording.
 #[tauri::command]
-fn transcribe_file(
-    path: String,
-    api_base: Option<String>,
+fn cancel_recording(
+    state: State<RecordingManager>,
     app_state: State<AppState>,
-) -> Result<String, String> {
-    let api_base = api_base
-        .or_else(|| {
-            let cfg = app_state.config.lock().ok()?.clone();
-            cfg.api_base
-        })
-        .unwrap_or_else(|| "http://localhost:8787".to_string());
+) -> Result<bool, String> {
+    app_state.logs.check("cancel_recording", state.cancel())
+}
 
-    // ensure embedder is available before processing results
-    {
-        let embedder_loaded = app_state.embedder.lock().map_err(|_| "embedder lock")?.is_some();
-        if !embedder_loaded {
-            app_state.load_embedder()?;
+/// Queues `path` for transcription on a background thread, reporting progress through
+/// `transcribe:start`, `transcribe:warning`, `transcribe:done` and `transcribe:error`
+/// events.
+///
+/// At most `max_background_transcriptions` jobs run at once; more wait their turn in the
+/// order they were queued. Running jobs overlap with each other and with commands:
+/// `transcribe_path` takes the DB lock only to store its results, and embedding waits
+/// only when every pooled embedder (`embedder_pool_size`) is in use.
+fn spawn_transcription(app: tauri::AppHandle, path: PathBuf) {
+    let app_state = app.state::<AppState>();
+    let limit = app_state
+        .config
+        .lock()
+        .map(|cfg| cfg.max_background_transcriptions())
+        .unwrap_or(config::DEFAULT_MAX_BACKGROUND_TRANSCRIPTIONS);
+    let start_worker = match app_state.transcriptions.lock() {
+        Ok(mut queue) => queue.push(path, limit),
+        Err(_) => {
+            app_state
+                .logs
+                .error("auto_transcribe", "transcription queue lock poisoned");
+            return;
         }
+    };
+    if start_worker {
+        thread::spawn(move || {
+            let app_state = app.state::<AppState>();
+            while let Some(path) = app_state
+                .transcriptions
+                .lock()
+                .ok()
+                .and_then(|mut q| q.next())
+            {
+                run_background_transcription(&app, &path.to_string_lossy());
+            }
+        });
     }
+}
 
-    let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
-    let db = db_guard
-        .as_ref()
-        .ok_or("Database not initialized (unlock to proceed)")?;
-    let _ = db.encrypted;
+fn run_background_transcription(app: &tauri::AppHandle, path: &str) {
+    let event = |transcript: Option<String>, error: Option<String>| TranscribeEvent {
+        path: path.to_string(),
+        transcript,
+        error,
+    };
+    let _ = app.emit("transcribe:start", event(None, None));
+    let app_state = app.state::<AppState>();
+    let on_progress = emit_progress(app, path);
+    let on_warning = emit_warning(app, path);
+    let perf = Perf::new(app, &app_state);
+    let result = transcribe_path(
+        &app_state,
+        path,
+        None,
+        None,
+        TranscribeOptions::default(),
+        &TranscribeReport {
+            on_progress: &on_progress,
+            on_warning: &on_warning,
+        },
+        &perf,
+    );
+    match app_state.logs.check("auto_transcribe", result) {
+        Ok(transcript) => {
+            let _ = app.emit("transcribe:done", event(Some(transcript), None));
+        }
+        Err(e) => {
+            let _ = app.emit("transcribe:error", event(None, Some(e)));
+        }
+    }
+}
 
-    let url = Url::parse(&api_base)
+#[derive(Debug, Clone, Serialize)]
+struct ClippingReport {
+    clipped_samples: u64,
+    total_samples: u64,
+    percent: f32,
+    /// Whether `percent` is above `clipping_warn_percent`.
+    clipping: bool,
+}
+
+/// Counts samples at or near full scale in a recorded WAV or FLAC file. Clipped audio
+/// transcribes worse; lowering the input gain avoids it.
+#[tauri::command]
+fn clipping_report(path: String, app_state: State<AppState>) -> Result<ClippingReport, String> {
+    app_state.logs.run("clipping_report", || {
+        let warn_percent = app_state
+            .config
+            .lock()
+            .map_err(|_| "config lock")?
+            .clipping_warn_percent();
+        let (clipped_samples, total_samples) = audio::count_clipped(std::path::Path::new(&path))
+            .map_err(|e| format!("{path}: {e}"))?;
+        let percent = audio::clipped_percent(clipped_samples, total_samples);
+        Ok(ClippingReport {
+            clipped_samples,
+            total_samples,
+            percent,
+            clipping: percent > warn_percent,
+        })
+    })
+}
+
+#[tauri::command]
+fn recording_status(
+    state: State<RecordingManager>,
+    app_state: State<AppState>,
+) -> Result<RecordingStatus, String> {
+    app_state.logs.run("recording_status", || state.status())
+}
+
+#[tauri::command]
+fn list_input_devices(app_state: State<AppState>) -> Result<Vec<InputDeviceInfo>, String> {
+    app_state.logs.run("list_input_devices", || {
+        let host = cpal::default_host();
+        let default_name = host.default_input_device().and_then(|d| d.name().ok());
+        let mut devices = Vec::new();
+        for device in host
+            .input_devices()
+            .map_err(|e| format!("Failed to list input devices: {e}"))?
+        {
+            let Ok(name) = device.name() else {
+                continue;
+            };
+            devices.push(InputDeviceInfo {
+                loopback: false,
+                system_audio: looks_like_system_audio(&name),
+                is_default: default_name.as_deref() == Some(name.as_str()),
+                name,
+            });
+        }
+        if LOOPBACK_SUPPORTED {
+            for device in host
+                .output_devices()
+                .map_err(|e| format!("Failed to list output devices: {e}"))?
+            {
+                let Ok(name) = device.name() else {
+                    continue;
+                };
+                devices.push(InputDeviceInfo {
+                    name,
+                    loopback: true,
+                    system_audio: true,
+                    is_default: false,
+                });
+            }
+        }
+        Ok(devices)
+    })
+}
+
+/// Stream configuration of the recording device (the selected one, else the default
+/// input), for diagnosing choppy recordings.
+#[tauri::command]
+fn audio_device_info(app_state: State<AppState>) -> Result<AudioDeviceInfo, String> {
+    app_state.logs.run("audio_device_info", || {
+        let selection = app_state
+            .config
+            .lock()
+            .map_err(|_| "config lock")?
+            .input_device
+            .clone();
+        let loopback = selection.as_ref().is_some_and(|sel| sel.loopback);
+        let (device, config) = open_capture_device(&cpal::default_host(), selection.as_ref())?;
+        let ranges: Vec<cpal::SupportedStreamConfigRange> = if loopback {
+            device
+                .supported_output_configs()
+                .map_err(|e| format!("Failed to query device configs: {e}"))?
+                .collect()
+        } else {
+            device
+                .supported_input_configs()
+                .map_err(|e| format!("Failed to query device configs: {e}"))?
+                .collect()
+        };
+        Ok(AudioDeviceInfo {
+            name: device.name().unwrap_or_default(),
+            loopback,
+            sample_rate: config.sample_rate().0,
+            channels: config.channels(),
+            sample_format: config.sample_format().to_string(),
+            buffer_size: buffer_size_range(config.buffer_size()),
+            supported_configs: ranges
+                .iter()
+                .map(|range| SupportedConfigInfo {
+                    channels: range.channels(),
+                    min_sample_rate: range.min_sample_rate().0,
+                    max_sample_rate: range.max_sample_rate().0,
+                    sample_format: range.sample_format().to_string(),
+                    buffer_size: buffer_size_range(range.buffer_size()),
+                })
+                .collect(),
+        })
+    })
+}
+
+/// Selects the recording device; `None` reverts to the system default input.
+#[tauri::command]
+fn set_input_device(
+    name: Option<String>,
+    loopback: bool,
+    app_state: State<AppState>,
+) -> Result<(), String> {
+    app_state.logs.run("set_input_device", || {
+        let selection = name.map(|name| InputDeviceSelection { name, loopback });
+        if let Some(sel) = &selection {
+            // Fails early with a clear message for unknown devices or unsupported loopback.
+            open_capture_device(&cpal::default_host(), Some(sel))?;
+        }
+        app_state
+            .config
+            .lock()
+            .map_err(|_| "config lock")?
+            .input_device = selection;
+        app_state.save_config()
+    })
+}
+
+/// Sets the `User-Agent` sent to the API and model host; `None` restores the default.
+#[tauri::command]
+fn set_user_agent(
+    user_agent: Option<String>,
+    app_state: State<AppState>,
+) -> Result<String, String> {
+    app_state.logs.run("set_user_agent", || {
+        let mut cfg = app_state.config.lock().map_err(|_| "config lock")?;
+        let previous = std::mem::replace(&mut cfg.user_agent, user_agent);
+        let effective = cfg.user_agent();
+        if let Err(e) = parse_user_agent(&effective) {
+            cfg.user_agent = previous;
+            return Err(e);
+        }
+        drop(cfg);
+        app_state.save_config()?;
+        Ok(effective)
+    })
+}
+
+/// Transcribes an audio file. Recordings in the temp dir are deleted afterwards unless
+/// `keep_source` is set; files elsewhere (picked by the user) are never deleted.
+#[tauri::command]
+fn transcribe_file(
+    path: String,
+    api_base: Option<String>,
+    client_request_id: Option<String>,
+    keep_source: Option<bool>,
+    skip_embeddings: Option<bool>,
+    app: tauri::AppHandle,
+    app_state: State<AppState>,
+) -> Result<String, String> {
+    let options = TranscribeOptions {
+        keep_source: keep_source.unwrap_or(false),
+        skip_embeddings: skip_embeddings.unwrap_or(false),
+    };
+    let result = transcribe_path(
+        &app_state,
+        &path,
+        api_base,
+        client_request_id.as_deref(),
+        options,
+        &TranscribeReport {
+            on_progress: &emit_progress(&app, &path),
+            on_warning: &emit_warning(&app, &path),
+        },
+        &Perf::new(&app, &app_state),
+    );
+    app_state.logs.check("transcribe_file", result)
+}
+
+/// Downloads audio from `audio_url` to a temp file, transcribes it like `transcribe_file`
+/// and deletes the download afterwards, whether or not transcription succeeded.
+#[tauri::command]
+fn transcribe_url(
+    audio_url: String,
+    api_base: Option<String>,
+    client_request_id: Option<String>,
+    app: tauri::AppHandle,
+    app_state: State<AppState>,
+) -> Result<String, String> {
+    let result = download_audio(&app_state, &audio_url).and_then(|path| {
+        let path_str = path.to_string_lossy().to_string();
+        let result = transcribe_path(
+            &app_state,
+            &path_str,
+            api_base,
+            client_request_id.as_deref(),
+            TranscribeOptions::default(),
+            &TranscribeReport {
+                on_progress: &emit_progress(&app, &audio_url),
+                on_warning: &emit_warning(&app, &audio_url),
+            },
+            &Perf::new(&app, &app_state),
+        );
+        let _ = std::fs::remove_file(&path);
+        result
+    });
+    app_state.logs.check("transcribe_url", result)
+}
+
+fn download_audio(app_state: &AppState, audio_url: &str) -> Result<PathBuf, String> {
+    let url = Url::parse(audio_url).map_err(|e| format!("Invalid audio URL: {e}"))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err("Audio URL must be http or https".into());
+    }
+    let cfg = app_state.config.lock().map_err(|_| "config lock")?.clone();
+    let client = http_client(&cfg)?;
+    let res = client
+        .get(url)
+        .send()
+        .map_err(|e| describe_http_error(&e, &cfg))?;
+    if !res.status().is_success() {
+        return Err(format!("Download failed with status {}", res.status()));
+    }
+    let content_type = res
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_lowercase();
+    if !content_type.starts_with("audio/") {
+        return Err(format!(
+            "URL does not point to audio (content type {:?})",
+            content_type
+        ));
+    }
+    if res.content_length().unwrap_or(0) > MAX_DOWNLOAD_BYTES {
+        return Err(format!(
+            "Audio is larger than {} MB",
+            MAX_DOWNLOAD_BYTES / 1024 / 1024
+        ));
+    }
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_nanos();
+    let path = std::env::temp_dir().join(format!("recall-download-{nanos}.wav"));
+    let written = std::fs::File::create(&path)
+        .map_err(|e| format!("Failed to create temp file: {e}"))
+        .and_then(|mut file| {
+            // The declared length can't be trusted, so the body is capped while copying too.
+            let mut body = std::io::Read::take(res, MAX_DOWNLOAD_BYTES + 1);
+            std::io::copy(&mut body, &mut file).map_err(|e| format!("Download failed: {e}"))
+        })
+        .and_then(|written| {
+            if written > MAX_DOWNLOAD_BYTES {
+                return Err(format!(
+                    "Audio is larger than {} MB",
+                    MAX_DOWNLOAD_BYTES / 1024 / 1024
+                ));
+            }
+            hound::WavReader::open(&path)
+                .map(|_| ())
+                .map_err(|e| format!("Downloaded audio is not a readable WAV: {e}"))
+        });
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(&path);
+        return Err(e);
+    }
+    Ok(path)
+}
+
+fn is_temp_file(path: &std::path::Path) -> bool {
+    match (
+        std::fs::canonicalize(path),
+        std::fs::canonicalize(std::env::temp_dir()),
+    ) {
+        (Ok(path), Ok(temp_dir)) => path.starts_with(temp_dir),
+        _ => false,
+    }
+}
+
+/// Forwards server-reported progress for `path` as `transcribe:progress` events.
+fn emit_progress<'a>(app: &'a tauri::AppHandle, path: &'a str) -> impl Fn(f32) + 'a {
+    move |percent| {
+        let _ = app.emit(
+            "transcribe:progress",
+            TranscribeProgress {
+                path: path.to_string(),
+                percent,
+            },
+        );
+    }
+}
+
+/// Forwards warnings about a transcription of `path` that still succeeded as
+/// `transcribe:warning` events.
+fn emit_warning<'a>(app: &'a tauri::AppHandle, path: &'a str) -> impl Fn(String) + 'a {
+    move |warning| {
+        let _ = app.emit(
+            "transcribe:warning",
+            TranscribeWarning {
+                path: path.to_string(),
+                warning,
+            },
+        );
+    }
+}
+
+/// Where `transcribe_path` reports on a job while it runs.
+struct TranscribeReport<'a> {
+    /// Server-reported progress, in percent.
+    on_progress: &'a dyn Fn(f32),
+    /// Problems that didn't stop the transcription.
+    on_warning: &'a dyn Fn(String),
+}
+
+/// Per-request switches for `transcribe_path`.
+#[derive(Debug, Clone, Copy, Default)]
+struct TranscribeOptions {
+    /// Keep a temp-dir recording after transcribing it.
+    keep_source: bool,
+    /// Store the API's speaker labels as they are, without loading the model.
+    skip_embeddings: bool,
+}
+
+fn transcribe_path(
+    app_state: &AppState,
+    path: &str,
+    api_base: Option<String>,
+    client_request_id: Option<&str>,
+    options: TranscribeOptions,
+    report: &TranscribeReport,
+    perf: &Perf,
+) -> Result<String, String> {
+    let api_base = resolve_api_base(app_state, api_base);
+    // Taken rather than read, so a failed transcription doesn't leave the entry behind.
+    let append_to = app_state
+        .append_targets
+        .lock()
+        .map_err(|_| "append targets lock")?
+        .remove(std::path::Path::new(path));
+
+    // Whether speakers get matched is settled up front, but the embedder is only checked
+    // out after the upload so a loaded model doesn't sit idle while the server works.
+    let match_speakers = if options.skip_embeddings || app_state.matching_disabled() {
+        false
+    } else if let Some(reason) = app_state.embeddings_unavailable() {
+        let warning =
+            format!("Transcribed without speaker matching; ONNX Runtime is unavailable: {reason}");
+        app_state.logs.error("transcribe", warning.clone());
+        (report.on_warning)(warning);
+        false
+    } else {
+        true
+    };
+
+    // The upload, decoding and embedding happen without the DB lock, so other jobs and
+    // commands aren't held up behind a slow server; only the writes take it.
+    let cfg = app_state.config.lock().map_err(|_| "config lock")?.clone();
+    let started = Instant::now();
+    let body = app_state
+        .transcriber
+        .transcribe(&cfg, &api_base, path, report.on_progress)?;
+    perf.record("transcribe_file:upload", started.elapsed());
+
+    let started = Instant::now();
+    let api_resp: ApiTranscribeResponse =
+        serde_json::from_str(&body).map_err(|e| format!("Decode error: {e}"))?;
+    let _ = (&api_resp.summary, &api_resp.speakers, &api_resp.audio_url);
+    if api_resp.is_empty() {
+        return Err(NOTHING_TRANSCRIBED.into());
+    }
+
+    let duration_ms = audio_duration_ms(path)?;
+    let mut segments = normalize_segments(api_resp.segments.clone(), &api_resp.transcript, duration_ms);
+    let mut embedder = if match_speakers {
+        Some(app_state.checkout_embedder()?)
+    } else {
+        None
+    };
+    let speaker_audio = if embedder.is_some() {
+        Some(read_speaker_audio(
+            path,
+            &segments,
+            cfg.min_segment_ms(),
+            cfg.downmix_cancellation_db(),
+        )?)
+    } else {
+        None
+    };
+    perf.record("transcribe_file:decode", started.elapsed());
+
+    let started = Instant::now();
+    let speaker_embeddings = match (speaker_audio, embedder.as_mut()) {
+        (Some(speaker_audio), Some(embedder)) => {
+            Some(embed_speakers(speaker_audio, &segments, embedder)?)
+        }
+        _ => None,
+    };
+    drop(embedder);
+    perf.record("transcribe_file:embed", started.elapsed());
+
+    // Speakers-only mode keeps timings and voiceprints but none of the words.
+    let stored_transcript = if cfg.store_transcript() {
+        api_resp.transcript.as_str()
+    } else {
+        segments.iter_mut().for_each(|seg| seg.text.clear());
+        ""
+    };
+
+    // The session, its embeddings and segments are stored all-or-nothing.
+    let started = Instant::now();
+    let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+    let db = db_guard
+        .as_ref()
+        .ok_or("Database not initialized (unlock to proceed)")?;
+    let (session_id, created) = db.in_savepoint(|db| {
+        let (session_id, created) = match &append_to {
+            Some(session_id) => {
+                db.append_session_transcript(
+                    session_id,
+                    stored_transcript,
+                    cfg.segment_separator(),
+                )?;
+                (session_id.clone(), true)
+            }
+            None => db
+                .insert_session(stored_transcript, client_request_id)
+                .map_err(|e| format!("DB error: {e}"))?,
+        };
+
+        // A retried job whose session already exists must not add duplicate segments/embeddings.
+        if created {
+            match speaker_embeddings {
+                Some(speaker_embeddings) => {
+                    process_segments(speaker_embeddings, &segments, &session_id, db, &cfg)?
+                }
+                None => store_segments(&segments, &session_id, db, &HashMap::new())?,
+            }
+            // Prefixes need the speaker labels, which matching has only just assigned.
+            let has_text = segments.iter().any(|seg| !seg.text.trim().is_empty());
+            if let (Some(prefix), true) = (cfg.transcript_prefix(), has_text) {
+                db.rebuild_transcript(&session_id, Some(prefix), cfg.segment_separator())?;
+            }
+        }
+        Ok((session_id, created))
+    })?;
+    if created {
+        let result = db.session_fingerprint(&session_id);
+        let _ = app_state.logs.check("session_fingerprint", result);
+        if let Ok(mut undoable) = app_state.undoable.lock() {
+            *undoable = append_to.is_none().then(|| state::UndoableTranscription {
+                session_id: session_id.clone(),
+                finished_at: Utc::now(),
+            });
+        }
+    }
+    drop(db_guard);
+    perf.record("transcribe_file:db", started.elapsed());
+
+    // Drawn from this recording only, so appended ones leave the thumbnail as it is.
+    let thumbnail = (created && append_to.is_none() && cfg.session_thumbnails)
+        .then(|| render_thumbnail(path, cfg.downmix_cancellation_db()));
+
+    // An appended recording covers only part of its session, so it isn't kept as the
+    // session's audio.
+    let kept = if !options.keep_source && is_temp_file(std::path::Path::new(path)) {
+        let _ = std::fs::remove_file(path);
+        None
+    } else if created && append_to.is_none() {
+        let kept = std::fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+        Some(retained_recording(app_state, kept, cfg.recording_format()))
+    } else {
+        None
+    };
+
+    if thumbnail.is_some() || kept.is_some() {
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard
+            .as_ref()
+            .ok_or("Database not initialized (unlock to proceed)")?;
+        if let Some(result) = thumbnail {
+            let result = result.and_then(|png| db.set_session_thumbnail(&session_id, &png));
+            let _ = app_state.logs.check("session_thumbnail", result);
+        }
+        if let Some(kept) = kept {
+            db.set_session_audio_path(&session_id, &kept.to_string_lossy())?;
+        }
+    }
+
+    Ok(api_resp.transcript)
+}
+
+/// With `recording_format` set to FLAC, a kept WAV recording from the temp dir is
+/// re-encoded and the WAV removed; files the user picked are kept as they are. Falls back
+/// to the WAV if encoding fails.
+fn retained_recording(app_state: &AppState, path: PathBuf, format: RecordingFormat) -> PathBuf {
+    let is_wav = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"));
+    if format != RecordingFormat::Flac || !is_wav || !is_temp_file(&path) {
+        return path;
+    }
+    let encoded = path.with_extension("flac");
+    match flac::encode_wav(&path, &encoded) {
+        Ok(()) => {
+            let _ = std::fs::remove_file(&path);
+            encoded
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(&encoded);
+            app_state
+                .logs
+                .error("transcribe_file", format!("FLAC encoding failed: {e}"));
+            path
+        }
+    }
+}
+
+fn resolve_api_base(app_state: &AppState, api_base: Option<String>) -> String {
+    api_base
+        .or_else(|| {
+            let cfg = app_state.config.lock().ok()?.clone();
+            cfg.api_base
+        })
+        .unwrap_or_else(|| "http://localhost:8787".to_string())
+}
+
+/// Sends recordings to the transcription server's `v1/transcribe` endpoint.
+struct HttpTranscriber;
+
+impl transcriber::Transcriber for HttpTranscriber {
+    fn transcribe(
+        &self,
+        cfg: &AppConfig,
+        api_base: &str,
+        path: &str,
+        on_progress: &dyn Fn(f32),
+    ) -> Result<String, String> {
+        request_transcription(cfg, api_base, path, on_progress)
+    }
+}
+
+/// The server client, or in debug builds a canned response named by
+/// `RECALL_CANNED_RESPONSE` to run the pipeline offline. A canned response that can't be
+/// read falls back to the server, with the reason to log.
+#[cfg(debug_assertions)]
+fn select_transcriber() -> (Arc<dyn transcriber::Transcriber>, Option<String>) {
+    let Some(path) = std::env::var_os(transcriber::CANNED_RESPONSE_ENV) else {
+        return (Arc::new(HttpTranscriber), None);
+    };
+    match transcriber::CannedTranscriber::from_file(std::path::Path::new(&path)) {
+        Ok(canned) => (Arc::new(canned), None),
+        Err(e) => (Arc::new(HttpTranscriber), Some(e)),
+    }
+}
+
+#[cfg(not(debug_assertions))]
+fn select_transcriber() -> (Arc<dyn transcriber::Transcriber>, Option<String>) {
+    (Arc::new(HttpTranscriber), None)
+}
+
+/// Posts the file to `v1/transcribe` and returns the JSON response body. Servers that
+/// answer with `application/x-ndjson` stream [`TranscribeStreamLine`]s, whose progress is
+/// passed to `on_progress`; a plain JSON reply is returned as-is with no progress.
+fn request_transcription(
+    cfg: &AppConfig,
+    api_base: &str,
+    path: &str,
+    on_progress: &dyn Fn(f32),
+) -> Result<String, String> {
+    let url = Url::parse(api_base)
         .map_err(|e| format!("Invalid API base: {e}"))?
         .join("v1/transcribe")
         .map_err(|e| format!("Invalid endpoint: {e}"))?;
 
-    let file_bytes = std::fs::read(&path).map_err(|e| format!("Failed to read file: {e}"))?;
-    let part = multipart::Part::bytes(file_bytes).file_name("audio.wav");
-    let form = multipart::Form::new().part("file", part);
+    if let Some(max_mb) = cfg.max_upload_mb {
+        let size = std::fs::metadata(path)
+            .map_err(|e| format!("Failed to read file: {e}"))?
+            .len();
+        if size > max_mb * 1024 * 1024 {
+            return Err(format!(
+                "Audio file is {:.1} MB, over the {max_mb} MB upload limit (max_upload_mb). Downsample it (e.g. 16 kHz mono) or split it into shorter chunks.",
+                size as f64 / (1024.0 * 1024.0)
+            ));
+        }
+    }
+
+    let file_bytes = std::fs::read(path).map_err(|e| format!("Failed to read file: {e}"))?;
+    let part = multipart::Part::bytes(file_bytes).file_name("audio.wav");
+    let form = multipart::Form::new().part("file", part);
+
+    let client = http_client(cfg)?;
+
+    let res = client
+        .post(url)
+        .header(
+            reqwest::header::ACCEPT,
+            "application/x-ndjson, application/json;q=0.9",
+        )
+        .multipart(form)
+        .send()
+        .map_err(|e| describe_http_error(&e, cfg))?;
+
+    if !res.status().is_success() {
+        return Err(format!("API responded with status {}", res.status()));
+    }
+
+    let streamed = res
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/x-ndjson"));
+    if streamed {
+        return read_transcription_stream(std::io::BufReader::new(res), on_progress);
+    }
+    res.text().map_err(|e| describe_http_error(&e, cfg))
+}
+
+fn read_transcription_stream(
+    reader: impl std::io::BufRead,
+    on_progress: &dyn Fn(f32),
+) -> Result<String, String> {
+    for line in reader.lines() {
+        let line = line.map_err(|e| format!("Failed to read transcription stream: {e}"))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let message: TranscribeStreamLine = serde_json::from_str(&line)
+            .map_err(|e| format!("Invalid transcription stream message: {e}"))?;
+        match message {
+            TranscribeStreamLine::Progress { progress } => on_progress(progress.clamp(0.0, 100.0)),
+            TranscribeStreamLine::Result { result } => return Ok(result.to_string()),
+            TranscribeStreamLine::Error { error } => return Err(format!("API error: {error}")),
+        }
+    }
+    Err("Transcription stream ended without a result".into())
+}
+
+/// Merges transcripts of overlapping audio chunks, dropping text heard twice.
+#[tauri::command]
+fn merge_transcript_chunks(
+    chunks: Vec<merge::TranscriptChunk>,
+    overlap_ms: u64,
+) -> Vec<ApiSegment> {
+    merge::merge_transcripts(chunks, overlap_ms)
+}
+
+/// Sends a file to the backend without storing anything, returning the untouched response
+/// next to the segments the app would derive from it. The file is kept.
+#[tauri::command]
+fn transcribe_debug(
+    path: String,
+    api_base: Option<String>,
+    app_state: State<AppState>,
+) -> Result<TranscriptionDebug, String> {
+    app_state.logs.run("transcribe_debug", || {
+        let api_base = resolve_api_base(&app_state, api_base);
+        let cfg = app_state.config.lock().map_err(|_| "config lock")?.clone();
+        let body = app_state
+            .transcriber
+            .transcribe(&cfg, &api_base, &path, &|_| {})?;
+        let raw: serde_json::Value =
+            serde_json::from_str(&body).map_err(|e| format!("Response is not JSON: {e}"))?;
+        let (normalized_segments, decode_error) =
+            match serde_json::from_value::<ApiTranscribeResponse>(raw.clone()) {
+                Ok(api_resp) => {
+                    let duration_ms = audio_duration_ms(&path)?;
+                    let segments =
+                        normalize_segments(api_resp.segments, &api_resp.transcript, duration_ms);
+                    (Some(segments), None)
+                }
+                Err(e) => (None, Some(e.to_string())),
+            };
+        Ok(TranscriptionDebug {
+            raw,
+            normalized_segments,
+            decode_error,
+        })
+    })
+}
+
+/// Diarizes a WAV without any backend: speech regions are found by level, embedded in
+/// short windows and clustered, then stored as a session with empty transcript text.
+#[tauri::command]
+fn diarize_local(path: String, app_state: State<AppState>) -> Result<LocalDiarization, String> {
+    let result = diarize_path(&app_state, &path);
+    app_state.logs.check("diarize_local", result)
+}
+
+/// Candidate points (ms) to split a recording into separate meetings: the middle of
+/// every silence of at least `min_gap_seconds`.
+#[tauri::command]
+fn detect_meeting_boundaries(
+    path: String,
+    min_gap_seconds: u64,
+    app_state: State<AppState>,
+) -> Result<Vec<u64>, String> {
+    app_state.logs.run("detect_meeting_boundaries", || {
+        let cancellation_db = app_state
+            .config
+            .lock()
+            .map_err(|_| "config lock")?
+            .downmix_cancellation_db();
+        let audio_clip = read_audio_clip(&path, cancellation_db)?;
+        let regions = diarize::speech_regions(&audio_clip.samples, audio_clip.sample_rate);
+        Ok(diarize::long_gaps(&regions, min_gap_seconds * 1000))
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct TrimmedAudio {
+    path: String,
+    duration_ms: u64,
+}
+
+/// Writes a copy of the WAV without its leading and trailing silence, found with the same
+/// speech detector as `diarize_local`, keeping `TRIM_PAD_MS` either side of the speech.
+#[tauri::command]
+fn trim_silence(path: String, app_state: State<AppState>) -> Result<TrimmedAudio, String> {
+    app_state.logs.run("trim_silence", || {
+        let cancellation_db = app_state
+            .config
+            .lock()
+            .map_err(|_| "config lock")?
+            .downmix_cancellation_db();
+        let audio_clip = read_audio_clip(&path, cancellation_db)?;
+        let regions = diarize::speech_regions(&audio_clip.samples, audio_clip.sample_rate);
+        let (Some(first), Some(last)) = (regions.first(), regions.last()) else {
+            return Err("No speech found in audio".into());
+        };
+        let start_ms = first.start_ms.saturating_sub(TRIM_PAD_MS);
+        let end_ms = (last.end_ms + TRIM_PAD_MS).min(audio_clip.duration_ms());
+        let output = audio::trim_wav(std::path::Path::new(&path), start_ms, end_ms)?;
+        Ok(TrimmedAudio {
+            path: output.to_string_lossy().to_string(),
+            duration_ms: end_ms - start_ms,
+        })
+    })
+}
+
+fn diarize_path(app_state: &AppState, path: &str) -> Result<LocalDiarization, String> {
+    let mut embedder = app_state.checkout_embedder()?;
+    let cfg = app_state.config.lock().map_err(|_| "config lock")?.clone();
+    let metric = cfg.similarity_metric();
+    let audio_clip = read_audio_clip(path, cfg.downmix_cancellation_db())?;
+
+    let windows = diarize::windows(&diarize::speech_regions(
+        &audio_clip.samples,
+        audio_clip.sample_rate,
+    ));
+    if windows.is_empty() {
+        return Err("No speech found in audio".into());
+    }
+    let sr = audio_clip.sample_rate as u64;
+    let mut embeddings = Vec::with_capacity(windows.len());
+    for window in &windows {
+        let start = (window.start_ms * sr / 1000) as usize;
+        let end = ((window.end_ms * sr / 1000) as usize).min(audio_clip.samples.len());
+        embeddings.push(embedder.embed(&audio_clip.samples[start..end], audio_clip.sample_rate)?);
+    }
+    drop(embedder);
+    let labels = diarize::cluster(&embeddings, metric, match_threshold(metric));
+
+    // Adjacent windows of the same cluster become one segment.
+    let mut segments: Vec<ApiSegment> = Vec::new();
+    for (window, label) in windows.iter().zip(&labels) {
+        let speaker = format!("speaker_{label}");
+        match segments.last_mut() {
+            Some(last) if last.speaker == speaker && last.end_ms == window.start_ms => {
+                last.end_ms = window.end_ms;
+            }
+            _ => segments.push(ApiSegment {
+                speaker,
+                start_ms: window.start_ms,
+                end_ms: window.end_ms,
+                text: String::new(),
+                exclude_from_embedding: false,
+            }),
+        }
+    }
+
+    // The windows were embedded for clustering already, so each speaker's voiceprint is
+    // its cluster's centroid rather than a second pass over the audio.
+    let speaker_embeddings =
+        (!app_state.matching_disabled()).then(|| cluster_centroids(&embeddings, &labels));
+
+    let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+    let db = db_guard
+        .as_ref()
+        .ok_or("Database not initialized (unlock to proceed)")?;
+    let session_id = db.in_savepoint(|db| {
+        let (session_id, _) = db
+            .insert_session("", None)
+            .map_err(|e| format!("DB error: {e}"))?;
+        match speaker_embeddings {
+            Some(speaker_embeddings) => {
+                process_segments(speaker_embeddings, &segments, &session_id, db, &cfg)?
+            }
+            None => store_segments(&segments, &session_id, db, &HashMap::new())?,
+        }
+        Ok(session_id)
+    })?;
+
+    Ok(LocalDiarization {
+        session_id,
+        segments: segments.len(),
+        speakers: labels.iter().collect::<HashSet<_>>().len(),
+    })
+}
+
+/// Parses `http_proxy`; credentials in the URL are sent as proxy basic auth.
+fn parse_proxy(proxy: &str) -> Result<reqwest::Proxy, String> {
+    let url = Url::parse(proxy).map_err(|e| format!("Invalid proxy URL: {e}"))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err("Proxy URL must be http or https".into());
+    }
+    if url.host_str().is_none() {
+        return Err("Proxy URL has no host".into());
+    }
+    reqwest::Proxy::all(url).map_err(|e| format!("Invalid proxy URL: {e}"))
+}
+
+fn parse_user_agent(user_agent: &str) -> Result<reqwest::header::HeaderValue, String> {
+    reqwest::header::HeaderValue::from_str(user_agent)
+        .map_err(|_| "User agent may only contain visible ASCII characters".to_string())
+}
+
+fn http_client(cfg: &AppConfig) -> Result<Client, String> {
+    http_client_with_timeout(cfg, Some(Duration::from_secs(cfg.request_timeout_secs())))
+}
+
+/// A client with the configured user agent, proxy and connect timeout; `timeout` bounds
+/// each whole request, or nothing when `None`.
+fn http_client_with_timeout(cfg: &AppConfig, timeout: Option<Duration>) -> Result<Client, String> {
+    let mut builder = Client::builder()
+        .user_agent(parse_user_agent(&cfg.user_agent())?)
+        .connect_timeout(Duration::from_secs(cfg.connect_timeout_secs()))
+        .timeout(timeout);
+    // Without an explicit proxy reqwest picks up the environment's proxy settings.
+    if let Some(proxy) = cfg.http_proxy() {
+        builder = builder.proxy(parse_proxy(proxy)?);
+    }
+    builder
+        .build()
+        .map_err(|e| format!("HTTP client error: {e}"))
+}
+
+fn describe_http_error(err: &reqwest::Error, cfg: &AppConfig) -> String {
+    if err.is_timeout() && err.is_connect() {
+        format!(
+            "HTTP error: connect timeout ({}s) reached: {err}",
+            cfg.connect_timeout_secs()
+        )
+    } else if err.is_timeout() {
+        format!(
+            "HTTP error: request timeout ({}s) reached: {err}",
+            cfg.request_timeout_secs()
+        )
+    } else {
+        format!("HTTP error: {err}")
+    }
+}
+
+/// Opens a WAV or FLAC file as a stream of mono samples, so no interleaved copy of the
+/// whole recording is held. Channels are averaged, unless averaging the start of the
+/// file loses `cancellation_db` or more of the channels' energy (phase-inverted mics in
+/// an array); then only the loudest channel is used. A `cancellation_db` of 0 always
+/// averages.
+fn open_mono(path: &str, cancellation_db: f32) -> Result<(u32, Samples), String> {
+    let (sample_rate, channels, mut interleaved) = open_interleaved(path)?;
+    let channels = channels.max(1);
+    let only_channel = if channels > 1 && cancellation_db > 0.0 {
+        // Only the probe is buffered; it's then replayed ahead of the rest of the stream.
+        let probe_len = sample_rate as usize * DOWNMIX_PROBE_SECS * channels;
+        let probe = interleaved
+            .by_ref()
+            .take(probe_len)
+            .collect::<Result<Vec<f32>, String>>()?;
+        let only_channel = cancelling_downmix(&probe, channels, cancellation_db);
+        interleaved = Box::new(probe.into_iter().map(Ok).chain(interleaved));
+        only_channel
+    } else {
+        None
+    };
+    let mono = std::iter::from_fn(move || {
+        let mut sum = 0f32;
+        for n in 0..channels {
+            match interleaved.next() {
+                Some(Ok(s)) if only_channel.is_none_or(|c| c == n) => sum += s,
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Some(Err(e)),
+                None if n == 0 => return None,
+                // A trailing partial frame is averaged like a full one.
+                None => break,
+            }
+        }
+        Some(Ok(match only_channel {
+            Some(_) => sum,
+            None => sum / channels as f32,
+        }))
+    });
+    Ok((sample_rate, Box::new(mono)))
+}
+
+/// The loudest channel when averaging the interleaved `probe` leaves at least
+/// `cancellation_db` less energy than the channels carry on average; `None` to average.
+fn cancelling_downmix(probe: &[f32], channels: usize, cancellation_db: f32) -> Option<usize> {
+    let mut channel_energy = vec![0f64; channels];
+    let mut mix_energy = 0f64;
+    for frame in probe.chunks_exact(channels) {
+        for (energy, &s) in channel_energy.iter_mut().zip(frame) {
+            *energy += s as f64 * s as f64;
+        }
+        let mix = frame.iter().map(|&s| s as f64).sum::<f64>() / channels as f64;
+        mix_energy += mix * mix;
+    }
+    let mean_channel = channel_energy.iter().sum::<f64>() / channels as f64;
+    if mean_channel <= 0.0 {
+        return None;
+    }
+    let lost_db = 10.0 * (mean_channel / mix_energy.max(f64::MIN_POSITIVE)).log10();
+    if lost_db < cancellation_db as f64 {
+        return None;
+    }
+    channel_energy
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(channel, _)| channel)
+}
+
+/// A WAV or FLAC file's sample rate, channel count and interleaved samples, decoded as
+/// they are read.
+fn open_interleaved(path: &str) -> Result<(u32, usize, Samples), String> {
+    let open_err = |e: String| format!("Failed to open audio for embeddings: {e}");
+    let (sample_rate, channels, interleaved): (u32, usize, Samples) =
+        if flac::is_flac(std::path::Path::new(path)) {
+            let reader = flac::FlacReader::open(std::path::Path::new(path)).map_err(open_err)?;
+            let info = reader.info();
+            let (sample_rate, channels) = (info.sample_rate, info.channels as usize);
+            let scale = info.full_scale();
+            let samples = reader.map(move |s| s.map(|s| s as f32 / scale));
+            (sample_rate, channels, Box::new(samples))
+        } else {
+            let reader = hound::WavReader::open(path).map_err(|e| open_err(e.to_string()))?;
+            let spec = reader.spec();
+            let scale = audio::full_scale(&spec).map_err(|_| {
+                format!(
+                    "Unsupported WAV format for embedding: {}-bit {:?}",
+                    spec.bits_per_sample, spec.sample_format
+                )
+            })?;
+            let decode_err = |e: hound::Error| format!("Sample decode error: {e}");
+            let samples: Samples = if spec.sample_format == hound::SampleFormat::Float {
+                Box::new(
+                    reader
+                        .into_samples::<f32>()
+                        .map(move |s| s.map_err(decode_err)),
+                )
+            } else {
+                // 8/16/24/32-bit PCM, each scaled by its own full scale.
+                Box::new(
+                    reader
+                        .into_samples::<i32>()
+                        .map(move |s| s.map(|s| s as f32 / scale).map_err(decode_err)),
+                )
+            };
+            (spec.sample_rate, spec.channels as usize, samples)
+        };
+    Ok((sample_rate, channels, interleaved))
+}
+
+fn read_audio_clip(path: &str, cancellation_db: f32) -> Result<AudioClip, String> {
+    let (sample_rate, mono) = open_mono(path, cancellation_db)?;
+    let samples = mono.collect::<Result<Vec<f32>, String>>()?;
+    if samples.is_empty() {
+        return Err("Audio buffer is empty".into());
+    }
+    Ok(AudioClip {
+        samples,
+        sample_rate,
+    })
+}
+
+/// Length of a WAV or FLAC file from its header, without reading the audio.
+fn audio_duration_ms(path: &str) -> Result<u64, String> {
+    let (frames, sample_rate) = audio_frames(path)?;
+    if sample_rate == 0 {
+        return Ok(0);
+    }
+    Ok(frames * 1000 / sample_rate as u64)
+}
+
+/// Frame count and sample rate of a WAV or FLAC file, from its header.
+fn audio_frames(path: &str) -> Result<(u64, u32), String> {
+    Ok(if flac::is_flac(std::path::Path::new(path)) {
+        let info = flac::read_info(std::path::Path::new(path))
+            .map_err(|e| format!("Failed to open audio for embeddings: {e}"))?;
+        (info.total_frames, info.sample_rate)
+    } else {
+        let reader = hound::WavReader::open(path)
+            .map_err(|e| format!("Failed to open audio for embeddings: {e}"))?;
+        (reader.duration() as u64, reader.spec().sample_rate)
+    })
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+struct AudioValidation {
+    /// True when nothing in `problems` would stop the file from being transcribed.
+    accepted: bool,
+    /// `"wav"` or `"flac"` when the header could be read.
+    format: Option<&'static str>,
+    duration_ms: Option<u64>,
+    sample_rate: Option<u32>,
+    channels: Option<u16>,
+    bits_per_sample: Option<u32>,
+    size_bytes: Option<u64>,
+    max_upload_mb: Option<u64>,
+    exceeds_upload_limit: bool,
+    problems: Vec<String>,
+}
+
+/// Reads a file's size and header the way transcription would, without uploading it,
+/// so a dropped file can be checked up front.
+#[tauri::command]
+fn validate_audio(path: String, app_state: State<AppState>) -> Result<AudioValidation, String> {
+    app_state.logs.run("validate_audio", || {
+        let max_upload_mb = app_state
+            .config
+            .lock()
+            .map_err(|_| "config lock")?
+            .max_upload_mb;
+        let mut report = AudioValidation {
+            max_upload_mb,
+            ..Default::default()
+        };
+        match std::fs::metadata(&path) {
+            Ok(meta) if meta.is_file() => report.size_bytes = Some(meta.len()),
+            Ok(_) => report.problems.push(format!("Not a file: {path}")),
+            Err(e) => report.problems.push(format!("Cannot read {path}: {e}")),
+        }
+        if let Some(size) = report.size_bytes {
+            if let Some(max_mb) = max_upload_mb {
+                report.exceeds_upload_limit = size > max_mb * 1024 * 1024;
+                if report.exceeds_upload_limit {
+                    report.problems.push(format!(
+                        "File is {:.1} MB, over the {max_mb} MB upload limit (max_upload_mb)",
+                        size as f64 / (1024.0 * 1024.0)
+                    ));
+                }
+            }
+            let header = if flac::is_flac(std::path::Path::new(&path)) {
+                flac::read_info(std::path::Path::new(&path)).map(|info| {
+                    report.format = Some("flac");
+                    (
+                        info.sample_rate,
+                        info.channels,
+                        info.bits_per_sample,
+                        info.total_frames,
+                    )
+                })
+            } else {
+                hound::WavReader::open(&path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|reader| {
+                        let spec = reader.spec();
+                        audio::full_scale(&spec).map_err(|_| {
+                            format!(
+                                "Unsupported WAV sample format: {}-bit {:?}",
+                                spec.bits_per_sample, spec.sample_format
+                            )
+                        })?;
+                        report.format = Some("wav");
+                        Ok((
+                            spec.sample_rate,
+                            spec.channels,
+                            spec.bits_per_sample as u32,
+                            reader.duration() as u64,
+                        ))
+                    })
+            };
+            match header {
+                Ok((sample_rate, channels, bits_per_sample, frames)) => {
+                    report.sample_rate = Some(sample_rate);
+                    report.channels = Some(channels);
+                    report.bits_per_sample = Some(bits_per_sample);
+                    // A FLAC header may leave the length out (zero frames) for streamed files.
+                    let unknown_length = frames == 0 && report.format == Some("flac");
+                    if !unknown_length && sample_rate > 0 {
+                        report.duration_ms = Some(frames * 1000 / sample_rate as u64);
+                    }
+                    if frames == 0 && !unknown_length {
+                        report.problems.push("Audio is empty".into());
+                    }
+                }
+                Err(e) => report
+                    .problems
+                    .push(format!("Not a supported WAV or FLAC file: {e}")),
+            }
+        }
+        report.accepted = report.problems.is_empty();
+        Ok(report)
+    })
+}
+
+fn render_thumbnail(path: &str, cancellation_db: f32) -> Result<Vec<u8>, String> {
+    let (frames, _) = audio_frames(path)?;
+    let (sample_rate, mono) = open_mono(path, cancellation_db)?;
+    thumbnail::spectrogram_png(mono, frames as usize, sample_rate)
+}
+
+/// Reads only the audio `process_segments` embeds, stopping once the last needed
+/// segment has been read, so memory stays bounded by speaker count rather than
+/// recording length.
+fn read_speaker_audio(
+    path: &str,
+    segments: &[ApiSegment],
+    min_segment_ms: u64,
+    cancellation_db: f32,
+) -> Result<SpeakerAudio, String> {
+    let (sample_rate, mono) = open_mono(path, cancellation_db)?;
+    let mut pieces = plan_speaker_pieces(segments, sample_rate, min_segment_ms);
+    let mut by_start: Vec<usize> = (0..pieces.len()).collect();
+    by_start.sort_by_key(|&p| pieces[p].start);
+    let mut pending = by_start.into_iter().peekable();
+    let mut active: Vec<usize> = Vec::new();
+    let mut read_any = false;
+
+    for (index, sample) in mono.enumerate() {
+        let sample = sample?;
+        read_any = true;
+        while let Some(p) = pending.next_if(|&p| pieces[p].start <= index) {
+            active.push(p);
+        }
+        // Segments of different speakers may overlap, so a sample can go to several.
+        for &p in &active {
+            pieces[p].samples.push(sample);
+        }
+        active.retain(|&p| pieces[p].end > index + 1);
+        if active.is_empty() && pending.peek().is_none() {
+            break;
+        }
+    }
+    if !read_any {
+        return Err("Audio buffer is empty".into());
+    }
+
+    Ok(SpeakerAudio {
+        by_speaker: join_speaker_pieces(pieces),
+        sample_rate,
+    })
+}
+
+fn normalize_segments(
+    segments: Option<Vec<ApiSegment>>,
+    transcript: &str,
+    duration_ms: u64,
+) -> Vec<ApiSegment> {
+    let mut segs = segments.unwrap_or_default();
+    if segs.is_empty() {
+        let end_ms = duration_ms.max(1_000);
+        segs.push(ApiSegment {
+            speaker: "speaker_0".to_string(),
+            start_ms: 0,
+            end_ms,
+            text: transcript.to_string(),
+            exclude_from_embedding: false,
+        });
+    }
+
+    let max_end = duration_ms;
+    for seg in segs.iter_mut() {
+        if seg.end_ms == 0 || seg.end_ms < seg.start_ms {
+            seg.end_ms = seg.start_ms.saturating_add(1_000);
+        }
+        if max_end > 0 && seg.end_ms > max_end {
+            seg.end_ms = max_end;
+        }
+    }
+    segs.sort_by_key(|s| s.start_ms);
+    segs
+}
+
+/// Splits each speaker's segments, in order, into the pieces that fill up to
+/// `TARGET_SPEAKER_MS` of audio for that speaker.
+fn plan_speaker_pieces(
+    segments: &[ApiSegment],
+    sample_rate: u32,
+    min_segment_ms: u64,
+) -> Vec<SpeakerPiece> {
+    let target_samples = std::cmp::max(
+        1,
+        ((sample_rate as u64 * TARGET_SPEAKER_MS) / 1000) as usize,
+    );
+    let sr = sample_rate as f64;
+    let mut taken: HashMap<&str, usize> = HashMap::new();
+    let mut pieces = Vec::new();
+
+    for seg in segments {
+        if seg.exclude_from_embedding || seg.end_ms.saturating_sub(seg.start_ms) < min_segment_ms {
+            continue;
+        }
+        let start = ((seg.start_ms as f64 / 1000.0) * sr).floor() as usize;
+        let end = ((seg.end_ms as f64 / 1000.0) * sr).ceil() as usize;
+        if end <= start {
+            continue;
+        }
+        let taken = taken.entry(seg.speaker.as_str()).or_default();
+        let take_len = std::cmp::min(target_samples.saturating_sub(*taken), end - start);
+        if take_len == 0 {
+            continue;
+        }
+        *taken += take_len;
+        pieces.push(SpeakerPiece {
+            speaker: seg.speaker.clone(),
+            start,
+            end: start + take_len,
+            samples: Vec::new(),
+        });
+    }
+
+    pieces
+}
+
+fn join_speaker_pieces(pieces: Vec<SpeakerPiece>) -> HashMap<String, Vec<f32>> {
+    let mut buckets: HashMap<String, Vec<f32>> = HashMap::new();
+    for piece in pieces {
+        buckets
+            .entry(piece.speaker)
+            .or_default()
+            .extend(piece.samples);
+    }
+    buckets
+}
+
+fn match_threshold(metric: SimilarityMetric) -> f32 {
+    match metric {
+        SimilarityMetric::Cosine => MATCH_THRESHOLD,
+        SimilarityMetric::Euclidean => EUCLIDEAN_MATCH_THRESHOLD,
+    }
+}
+
+fn best_match<'a>(
+    embedding: &[f32],
+    known: &'a [StoredEmbedding],
+    metric: SimilarityMetric,
+    threshold: f32,
+) -> Option<(&'a StoredEmbedding, f32)> {
+    best_match_indexed(embedding, known, None, metric, threshold)
+}
+
+/// [`best_match`] that only scores the voiceprints at `candidates` (from
+/// [`Db::ann_candidates`]), or all of them when `None`.
+fn best_match_indexed<'a>(
+    embedding: &[f32],
+    known: &'a [StoredEmbedding],
+    candidates: Option<&[usize]>,
+    metric: SimilarityMetric,
+    threshold: f32,
+) -> Option<(&'a StoredEmbedding, f32)> {
+    let candidates: Box<dyn Iterator<Item = &StoredEmbedding>> = match candidates {
+        Some(positions) => Box::new(positions.iter().map(|&i| &known[i])),
+        None => Box::new(known.iter()),
+    };
+    let mut best: Option<(&StoredEmbedding, f32)> = None;
+    for record in candidates {
+        if record.vector.len() != embedding.len() {
+            continue;
+        }
+        let score = metric.similarity(embedding, &record.vector);
+        match best {
+            Some((_, current)) if score <= current => continue,
+            _ => best = Some((record, score)),
+        }
+    }
+    if let Some((rec, score)) = best {
+        if score >= threshold {
+            return Some((rec, score));
+        }
+    }
+    None
+}
+
+fn centroid(vectors: &[Vec<f32>]) -> Option<Vec<f32>> {
+    let dim = vectors.first()?.len();
+    let mut sum = vec![0f32; dim];
+    let mut count = 0usize;
+    for vector in vectors.iter().filter(|v| v.len() == dim) {
+        for (acc, v) in sum.iter_mut().zip(vector) {
+            *acc += v;
+        }
+        count += 1;
+    }
+    sum.iter_mut().for_each(|v| *v /= count as f32);
+    Some(sum)
+}
+
+/// Each cluster's centroid, keyed `speaker_{label}` like the segments `diarize_path`
+/// builds, in order of first appearance.
+fn cluster_centroids(embeddings: &[Vec<f32>], labels: &[usize]) -> Vec<(String, Vec<f32>)> {
+    let mut order = Vec::new();
+    let mut members: HashMap<usize, Vec<Vec<f32>>> = HashMap::new();
+    for (embedding, &label) in embeddings.iter().zip(labels) {
+        let cluster = members.entry(label).or_default();
+        if cluster.is_empty() {
+            order.push(label);
+        }
+        cluster.push(embedding.clone());
+    }
+    order
+        .into_iter()
+        .filter_map(|label| Some((format!("speaker_{label}"), centroid(&members[&label])?)))
+        .collect()
+}
+
+/// Embeds each diarized speaker's audio, in order of first appearance so per-session
+/// numbers follow the conversation. Needs no database, so it runs before the DB lock.
+fn embed_speakers(
+    speaker_audio: SpeakerAudio,
+    segments: &[ApiSegment],
+    embedder: &mut crate::embedding::Embedder,
+) -> Result<Vec<(String, Vec<f32>)>, String> {
+    let mut by_speaker: Vec<(String, Vec<f32>)> = speaker_audio
+        .by_speaker
+        .into_iter()
+        .filter(|(_, pcm)| !pcm.is_empty())
+        .collect();
+    by_speaker.sort_by_key(|(key, _)| segments.iter().position(|seg| &seg.speaker == key));
+    by_speaker
+        .into_iter()
+        .map(|(key, pcm)| Ok((key, embedder.embed(&pcm, speaker_audio.sample_rate)?)))
+        .collect()
+}
+
+/// Matches the segments' speakers, by the embeddings `embed_speakers` made, and stores
+/// them in `session_id`.
+fn process_segments(
+    speaker_embeddings: Vec<(String, Vec<f32>)>,
+    segments: &[ApiSegment],
+    session_id: &str,
+    db: &Db,
+    cfg: &AppConfig,
+) -> Result<(), String> {
+    let metric = cfg.similarity_metric();
+    let mut diarization_to_profile: HashMap<String, (String, String)> = HashMap::new();
+    let mut known_embeddings = db.list_embeddings()?;
+
+    // Speakers the session has so far, for per-session numbering; appended recordings
+    // continue after the existing ones.
+    let mut session_speakers: HashSet<String> = db
+        .session_speakers(session_id)?
+        .into_iter()
+        .filter_map(|speaker| speaker.speaker_id)
+        .collect();
+
+    for (speaker_key, embedding_vec) in speaker_embeddings {
+        let candidates = if cfg.ann_matching {
+            db.ann_candidates(&known_embeddings, &embedding_vec, metric)?
+        } else {
+            None
+        };
+        let (speaker_id, speaker_label) = if let Some((matched, _score)) = best_match_indexed(
+            &embedding_vec,
+            &known_embeddings,
+            candidates.as_deref(),
+            metric,
+            match_threshold(metric),
+        ) {
+            let label = match &matched.speaker_label {
+                Some(label) => label.clone(),
+                None => new_speaker_label(db, cfg, session_speakers.len())?,
+            };
+            if matched.speaker_label.is_none() {
+                db.rename_speaker(&matched.speaker_id, &label)?;
+            }
+            (matched.speaker_id.clone(), label)
+        } else if let Some((candidate, score)) =
+            cfg.ambiguous_match_threshold.and_then(|threshold| {
+                best_match_indexed(
+                    &embedding_vec,
+                    &known_embeddings,
+                    candidates.as_deref(),
+                    metric,
+                    threshold,
+                )
+            })
+        {
+            // Too close to someone to safely call new; its segments keep the diarization
+            // label until the review is resolved.
+            db.insert_speaker_review(
+                session_id,
+                &speaker_key,
+                &candidate.speaker_id,
+                score,
+                &embedding_vec,
+            )?;
+            continue;
+        } else {
+            let label = new_speaker_label(db, cfg, session_speakers.len())?;
+            let id = db.insert_speaker(Some(&label))?;
+            (id, label)
+        };
+        session_speakers.insert(speaker_id.clone());
+
+        let embedding_id = db.insert_embedding(&speaker_id, session_id, &embedding_vec)?;
+        known_embeddings.push(StoredEmbedding {
+            id: embedding_id,
+            speaker_id: speaker_id.clone(),
+            speaker_label: Some(speaker_label.clone()),
+            vector: embedding_vec,
+            source_session_id: Some(session_id.to_string()),
+            created_at: Utc::now(),
+        });
+        diarization_to_profile.insert(speaker_key, (speaker_id, speaker_label));
+    }
+
+    store_segments(segments, session_id, db, &diarization_to_profile)
+}
+
+/// Label for a speaker named while processing a session that already has
+/// `session_speakers` speakers, per `speaker_label_prefix` and `speaker_numbering`.
+fn new_speaker_label(db: &Db, cfg: &AppConfig, session_speakers: usize) -> Result<String, String> {
+    let prefix = cfg.speaker_label_prefix();
+    match cfg.speaker_numbering() {
+        config::SpeakerNumbering::Global => db.next_speaker_label(prefix),
+        config::SpeakerNumbering::Session => Ok(format!("{prefix} {}", session_speakers + 1)),
+    }
+}
+
+/// Stores segments under the speaker profiles they were matched to; unmatched ones keep
+/// the label the diarization gave them. Segments are placed after any the session
+/// already has, so appended recordings continue its timeline.
+fn store_segments(
+    segments: &[ApiSegment],
+    session_id: &str,
+    db: &Db,
+    diarization_to_profile: &HashMap<String, (String, String)>,
+) -> Result<(), String> {
+    let offset_ms = db.session_end_ms(session_id)?;
+    for seg in segments {
+        let (speaker_id, speaker_label) = diarization_to_profile
+            .get(&seg.speaker)
+            .cloned()
+            .unwrap_or_else(|| (String::new(), seg.speaker.clone()));
+        let speaker_id_opt = if speaker_id.is_empty() {
+            None
+        } else {
+            Some(speaker_id.as_str())
+        };
+        let segment_id = db
+            .insert_segment(
+                session_id,
+                seg.start_ms as i64 + offset_ms,
+                seg.end_ms as i64 + offset_ms,
+                speaker_id_opt,
+                Some(&speaker_label),
+                &seg.text,
+            )
+            .map_err(|e| format!("DB error: {e}"))?;
+        if seg.exclude_from_embedding {
+            db.set_segment_excluded(&segment_id, true)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+fn model_info(app_state: State<AppState>) -> Result<embedding::ModelInfo, String> {
+    app_state.logs.run("model_info", || {
+        Ok(app_state.checkout_embedder()?.model_info())
+    })
+}
+
+/// Compares the length of stored voiceprints with the loaded model's output and turns
+/// speaker matching off (or back on) accordingly.
+fn check_embedding_dimensions(
+    app_state: &AppState,
+) -> Result<Option<embedding::EmbeddingMismatch>, String> {
+    let dims = app_state.with_readonly_db(|db| db.embedding_dimensions())?;
+    // Nothing stored yet, so no need to load the model.
+    let model_dim = if dims.is_empty() {
+        0
+    } else {
+        app_state.checkout_embedder()?.output_dim()?
+    };
+    let mismatch = dims
+        .iter()
+        .filter(|(dim, _)| **dim != model_dim)
+        .max_by_key(|(dim, count)| (**count, **dim))
+        .map(|(dim, _)| embedding::EmbeddingMismatch {
+            stored_dim: *dim,
+            model_dim,
+            mismatched: dims
+                .iter()
+                .filter(|(dim, _)| **dim != model_dim)
+                .map(|(_, count)| count)
+                .sum(),
+        });
+    *app_state
+        .embedding_mismatch
+        .lock()
+        .map_err(|_| "embedding mismatch lock")? = mismatch.clone();
+    Ok(mismatch)
+}
+
+/// Checks stored voiceprints against the model in the background once the DB is open,
+/// emitting `model:embedding-mismatch` when they don't fit.
+fn spawn_embedding_check(app: tauri::AppHandle) {
+    thread::spawn(move || {
+        let app_state = app.state::<AppState>();
+        let result = check_embedding_dimensions(&app_state);
+        if let Ok(Some(mismatch)) = app_state.logs.check("embedding_check", result) {
+            app_state.logs.error(
+                "embedding_check",
+                format!(
+                    "Stored voiceprints have {} values but the model produces {}; speaker matching is off until reembed_all runs",
+                    mismatch.stored_dim, mismatch.model_dim
+                ),
+            );
+            let _ = app.emit("model:embedding-mismatch", mismatch);
+        }
+    });
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+struct ReembedReport {
+    /// Sessions whose kept audio was embedded again.
+    sessions: usize,
+    voiceprints: usize,
+    /// Sessions whose audio could not be re-embedded; see the log.
+    failed: usize,
+    /// Voiceprints of another length deleted because their audio is gone.
+    removed: usize,
+}
+
+/// Rebuilds voiceprints with the loaded model, e.g. after switching to one with another
+/// output size. Every session with kept audio is re-embedded; voiceprints that still don't
+/// fit the model are deleted. Speaker matching is back on afterwards.
+#[tauri::command]
+fn reembed_all(app_state: State<AppState>) -> Result<ReembedReport, String> {
+    let result = reembed_sessions(&app_state);
+    app_state.logs.check("reembed_all", result)
+}
+
+fn reembed_sessions(app_state: &AppState) -> Result<ReembedReport, String> {
+    let model_dim = app_state.checkout_embedder()?.output_dim()?;
+    let session_ids = app_state.with_readonly_db(|db| {
+        let mut with_audio = Vec::new();
+        for session_id in db.session_ids()? {
+            if db
+                .session_audio_path(&session_id)?
+                .is_some_and(|path| std::path::Path::new(&path).exists())
+            {
+                with_audio.push(session_id);
+            }
+        }
+        Ok(with_audio)
+    })?;
+
+    let mut report = ReembedReport::default();
+    for session_id in &session_ids {
+        match reprocess_embeddings(app_state, session_id) {
+            Ok(voiceprints) => {
+                report.sessions += 1;
+                report.voiceprints += voiceprints;
+            }
+            Err(e) => {
+                app_state
+                    .logs
+                    .error("reembed_all", format!("Session {session_id}: {e}"));
+                report.failed += 1;
+            }
+        }
+    }
+    report.removed = {
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        db.delete_embeddings_not_of_dim(model_dim)?
+    };
+    check_embedding_dimensions(app_state)?;
+    Ok(report)
+}
+
+/// Downloads the model from `url` (or `model_url`) into a `.part` file next to it. A
+/// dropped connection is resumed with an HTTP range request up to `model_download_retries`
+/// times. The file is checked against `model_sha256` (or the bundled digest) and only
+/// then renamed over the model, so a partial or wrong file never replaces it.
+#[tauri::command]
+fn download_model(
+    url: Option<String>,
+    app_state: State<AppState>,
+) -> Result<embedding::ModelChecksum, String> {
+    let result = download_model_file(&app_state, url);
+    app_state.logs.check("download_model", result)
+}
+
+fn download_model_file(
+    app_state: &AppState,
+    url: Option<String>,
+) -> Result<embedding::ModelChecksum, String> {
+    let cfg = app_state.config.lock().map_err(|_| "config lock")?.clone();
+    let url = url
+        .or_else(|| cfg.model_url.clone())
+        .ok_or("No model URL given and model_url is not set")?;
+    let url = Url::parse(&url).map_err(|e| format!("Invalid model URL: {e}"))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err("Model URL must be http or https".into());
+    }
+    let dest = app_state.model_path();
+    if let Some(dir) = dest.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let part = dest.with_extension("onnx.part");
+    // The model can take a long time to arrive, so only stalls are timed out.
+    let client = http_client_with_timeout(&cfg, None)?;
+
+    let mut attempt = 0;
+    loop {
+        match fetch_model_part(&client, &url, &part, &cfg) {
+            Ok(()) => break,
+            Err(e) if attempt < cfg.model_download_retries() => {
+                attempt += 1;
+                app_state.logs.error(
+                    "download_model",
+                    format!("{e}; resuming (attempt {attempt})"),
+                );
+                thread::sleep(MODEL_DOWNLOAD_RETRY_DELAY);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    let checksum = embedding::ModelChecksum::compute(&part, &cfg.model_sha256())?;
+    if !checksum.matches {
+        let _ = std::fs::remove_file(&part);
+        return Err(format!(
+            "Downloaded model checksum mismatch: expected {}, got {}",
+            checksum.expected, checksum.actual
+        ));
+    }
+    std::fs::rename(&part, &dest).map_err(|e| format!("Failed to install model: {e}"))?;
+    app_state.embedders.clear();
+    Ok(embedding::ModelChecksum {
+        path: dest,
+        ..checksum
+    })
+}
+
+/// What the thread fetching a model reports back to [`fetch_model_part`].
+enum DownloadEvent {
+    /// The response's status, once its headers arrive.
+    Status(reqwest::StatusCode),
+    Chunk(Vec<u8>),
+    Failed(String),
+}
+
+/// Fetches the rest of the model into `part`, asking only for the bytes it doesn't have.
+///
+/// The request runs on its own thread, because a blocking read can't be interrupted; the
+/// attempt fails once that thread reports nothing for `model_download_timeout_secs`, and
+/// the thread is left to end with its connection.
+fn fetch_model_part(
+    client: &Client,
+    url: &Url,
+    part: &std::path::Path,
+    cfg: &AppConfig,
+) -> Result<(), String> {
+    use std::io::{Read, Write};
+
+    let have = std::fs::metadata(part).map(|m| m.len()).unwrap_or(0);
+    let mut request = client.get(url.clone());
+    if have > 0 {
+        request = request.header(reqwest::header::RANGE, format!("bytes={have}-"));
+    }
+    let (tx, rx) = std::sync::mpsc::sync_channel(4);
+    let thread_cfg = cfg.clone();
+    thread::spawn(move || {
+        let mut res = match request.send() {
+            Ok(res) => res,
+            Err(e) => {
+                let _ = tx.send(DownloadEvent::Failed(describe_http_error(&e, &thread_cfg)));
+                return;
+            }
+        };
+        if tx.send(DownloadEvent::Status(res.status())).is_err() {
+            return;
+        }
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let event = match res.read(&mut buf) {
+                Ok(0) => return,
+                Ok(n) => DownloadEvent::Chunk(buf[..n].to_vec()),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => DownloadEvent::Failed(format!("Model download interrupted: {e}")),
+            };
+            let failed = matches!(event, DownloadEvent::Failed(_));
+            if tx.send(event).is_err() || failed {
+                return;
+            }
+        }
+    });
+
+    let stall = Duration::from_secs(cfg.model_download_timeout_secs());
+    let next = || match rx.recv_timeout(stall) {
+        Ok(DownloadEvent::Failed(e)) => Err(e),
+        Ok(event) => Ok(Some(event)),
+        Err(std::sync::mpsc::RecvTimeoutError::Timeout) => Err(format!(
+            "Model download stalled: nothing received for {}s",
+            stall.as_secs()
+        )),
+        Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => Ok(None),
+    };
+    let status = match next()? {
+        Some(DownloadEvent::Status(status)) => status,
+        _ => return Err("Model download ended without a response".into()),
+    };
+    let append = match status {
+        reqwest::StatusCode::PARTIAL_CONTENT => true,
+        // The server ignored the range; start over.
+        reqwest::StatusCode::OK => false,
+        // Nothing past what we have: the part file is already complete.
+        reqwest::StatusCode::RANGE_NOT_SATISFIABLE if have > 0 => return Ok(()),
+        status => return Err(format!("Model download failed with status {status}")),
+    };
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .open(part)
+        .map_err(|e| format!("Failed to write {}: {e}", part.display()))?;
+    while let Some(event) = next()? {
+        if let DownloadEvent::Chunk(chunk) = event {
+            file.write_all(&chunk)
+                .map_err(|e| format!("Failed to write {}: {e}", part.display()))?;
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn verify_model(app_state: State<AppState>) -> Result<embedding::ModelChecksum, String> {
+    app_state.logs.run("verify_model", || {
+        app_state.copy_model_if_missing()?;
+        app_state.verify_model()
+    })
+}
+
+#[tauri::command]
+fn benchmark_embedder(
+    duration_seconds: u64,
+    app_state: State<AppState>,
+) -> Result<BenchmarkReport, String> {
+    app_state.logs.run("benchmark_embedder", || {
+        use rand::Rng;
+
+        let duration = Duration::from_secs(duration_seconds.clamp(1, BENCHMARK_MAX_SECONDS));
+        let mut embedder = app_state.checkout_embedder()?;
+
+        let clip_len = (BENCHMARK_SAMPLE_RATE as u64 * BENCHMARK_CLIP_MS / 1000) as usize;
+        let mut rng = rand::thread_rng();
+        let clip: Vec<f32> = (0..clip_len).map(|_| rng.gen_range(-0.1..0.1)).collect();
+
+        let started = std::time::Instant::now();
+        let mut clips = 0u64;
+        while started.elapsed() < duration {
+            embedder.embed(&clip, BENCHMARK_SAMPLE_RATE)?;
+            clips += 1;
+        }
+        let elapsed = started.elapsed();
+        let elapsed_secs = elapsed.as_secs_f64();
+        Ok(BenchmarkReport {
+            clips,
+            clip_ms: BENCHMARK_CLIP_MS,
+            elapsed_ms: elapsed.as_millis() as u64,
+            clips_per_sec: if elapsed_secs > 0.0 {
+                clips as f64 / elapsed_secs
+            } else {
+                0.0
+            },
+            avg_latency_ms: if clips > 0 {
+                elapsed_secs * 1000.0 / clips as f64
+            } else {
+                0.0
+            },
+        })
+    })
+}
+
+/// Checks ONNX Runtime again, e.g. after installing a missing library, and turns speaker
+/// matching back on if it now starts.
+#[tauri::command]
+fn retry_embeddings(app_state: State<AppState>) -> Result<AppStatus, String> {
+    let result = app_state.check_onnx_runtime();
+    app_state.logs.check("retry_embeddings", result)?;
+    current_status(&app_state)
+}
+
+#[tauri::command]
+fn available_execution_providers() -> Vec<embedding::ExecutionProviderStatus> {
+    embedding::available_execution_providers()
+}
+
+#[tauri::command]
+fn unlock_db(
+    password: String,
+    app: tauri::AppHandle,
+    app_state: State<AppState>,
+) -> Result<(), String> {
+    app_state.logs.run("unlock_db", || {
+        let cfg = app_state.config.lock().map_err(|_| "config lock")?.clone();
+        if !cfg.encryption_enabled {
+            return Err("Encryption is not enabled".into());
+        }
+        let salt = Db::load_existing_salt(app_state.db_path()).unwrap_or(None);
+        let crypto = Crypto::new(Some(&password), salt);
+        app_state.open_db(crypto)?;
+        let _ = app.emit("db:unlocked", ());
+        spawn_embedding_check(app);
+        Ok(())
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct DbReinitialized {
+    /// Where the damaged database was moved, for manual recovery; `None` if it was deleted.
+    backup_path: Option<String>,
+}
+
+/// Replaces a corrupt `recall.db` with an empty one. The damaged file (and its WAL/SHM
+/// files) is moved aside as `recall-corrupt-<UTC timestamp>.db` when `backup_existing`
+/// is set, otherwise deleted. Refuses when SQLite finds nothing wrong with the file, and
+/// returns the error unchanged when the check itself fails (e.g. the file is busy). An
+/// unencrypted DB is reopened right away; with encryption on, the next `unlock_db` sets
+/// up the new one with the password given there. Emits `db:reinitialized`.
+#[tauri::command]
+fn reinitialize_db(
+    backup_existing: bool,
+    app: tauri::AppHandle,
+    app_state: State<AppState>,
+) -> Result<DbReinitialized, String> {
+    app_state.logs.run("reinitialize_db", || {
+        // Held until the files are moved, so no other command uses or reopens them meanwhile.
+        let mut db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db_path = app_state.db_path();
+        if db_path.exists() && Db::check_integrity(&db_path)?.is_none() {
+            return Err("The database is not corrupt; nothing was changed".into());
+        }
+        *db_guard = None;
+
+        let stamp = Utc::now().format("%Y%m%d-%H%M%S");
+        let mut backup_path = None;
+        for suffix in ["", "-wal", "-shm"] {
+            let file = PathBuf::from(format!("{}{suffix}", db_path.display()));
+            if !file.exists() {
+                continue;
+            }
+            if backup_existing {
+                let dest = app_state
+                    .data_dir
+                    .join(format!("recall-corrupt-{stamp}.db{suffix}"));
+                std::fs::rename(&file, &dest)
+                    .map_err(|e| format!("Failed to move {}: {e}", file.display()))?;
+                if suffix.is_empty() {
+                    backup_path = Some(dest.to_string_lossy().into_owned());
+                }
+            } else {
+                std::fs::remove_file(&file)
+                    .map_err(|e| format!("Failed to delete {}: {e}", file.display()))?;
+            }
+        }
+        drop(db_guard);
+        if let Ok(mut waveforms) = app_state.waveforms.lock() {
+            waveforms.clear();
+        }
+
+        let encrypted = app_state
+            .config
+            .lock()
+            .map_err(|_| "config lock")?
+            .encryption_enabled;
+        if !encrypted {
+            app_state.open_db(Crypto::new(None, None))?;
+        }
+        let event = DbReinitialized { backup_path };
+        app_state.logs.error(
+            "reinitialize_db",
+            match &event.backup_path {
+                Some(path) => format!("Replaced corrupt database; the old file is at {path}"),
+                None => "Replaced corrupt database; the old file was deleted".to_string(),
+            },
+        );
+        let _ = app.emit("db:reinitialized", event.clone());
+        Ok(event)
+    })
+}
+
+#[tauri::command]
+fn enable_encryption(password: String, app_state: State<AppState>) -> Result<(), String> {
+    app_state.logs.run("enable_encryption", || {
+        {
+            let mut cfg = app_state.config.lock().map_err(|_| "config lock")?;
+            cfg.encryption_enabled = true;
+            cfg.save(&app_state.config_path)?;
+        }
+        // Recreate DB encrypted (note: existing plaintext data not migrated).
+        {
+            let mut db_guard = app_state.db.lock().map_err(|_| "db lock")?;
+            *db_guard = None;
+        }
+        let db_path = app_state.data_dir.join("recall.db");
+        let _ = std::fs::remove_file(&db_path);
+        let crypto = Crypto::new(Some(&password), None);
+        app_state.open_db(crypto)
+    })
+}
+
+/// Turns encryption off without losing data: every row is decrypted and stored as
+/// plaintext, then the DB is reopened without a key. Needs the DB unlocked and the same
+/// password. Returns the number of rows rewritten.
+#[tauri::command]
+fn disable_encryption(password: String, app_state: State<AppState>) -> Result<usize, String> {
+    if !app_state
+        .config
+        .lock()
+        .map_err(|_| "config lock")?
+        .encryption_enabled
+    {
+        return Err("Encryption is not enabled".into());
+    }
+    let mut db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+    let db = db_guard
+        .as_ref()
+        .ok_or("Database is locked; unlock it first")?;
+    if !db.password_matches(&password) {
+        return Err("Wrong password".into());
+    }
+    let result = db.decrypt_all();
+    let rewritten = app_state.logs.check("disable_encryption", result)?;
+    *db_guard = None;
+    drop(db_guard);
+    {
+        let mut cfg = app_state.config.lock().map_err(|_| "config lock")?;
+        cfg.encryption_enabled = false;
+        cfg.save(&app_state.config_path)?;
+    }
+    app_state.open_db(Crypto::new(None, None))?;
+    Ok(rewritten)
+}
+
+/// Whether a session's transcript and segments are actually stored encrypted, e.g. to
+/// check rows written before encryption was enabled.
+#[tauri::command]
+fn session_encryption_info(
+    session_id: String,
+    app_state: State<AppState>,
+) -> Result<db::SessionEncryptionInfo, String> {
+    app_state.logs.run("session_encryption_info", || {
+        app_state.with_readonly_db(|db| db.session_encryption_info(&session_id))
+    })
+}
+
+/// Round-trips a known string through the DB's cipher to confirm encryption works.
+#[tauri::command]
+fn test_encryption(app_state: State<AppState>) -> Result<db::EncryptionSelfTest, String> {
+    app_state.logs.run("test_encryption", || {
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        Ok(db.encryption_self_test())
+    })
+}
+
+/// Re-encrypts all voiceprints with fresh nonces (same key) and returns how many were
+/// rewritten. A narrower step than a full rekey, limited to the embeddings table.
+#[tauri::command]
+fn rotate_embedding_key(app_state: State<AppState>) -> Result<usize, String> {
+    let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+    let db = db_guard.as_ref().ok_or("Database not initialized")?;
+    let result = db.reseal_embeddings();
+    app_state.logs.check("rotate_embedding_key", result)
+}
+
+#[tauri::command]
+fn app_status(app_state: State<AppState>) -> Result<AppStatus, String> {
+    app_state
+        .logs
+        .run("app_status", || current_status(&app_state))
+}
+
+fn current_status(app_state: &AppState) -> Result<AppStatus, String> {
+    let cfg = app_state
+        .config
+        .lock()
+        .map_err(|_| "config lock")?
+        .clone();
+    let db_open = app_state.db.lock().map_err(|_| "DB lock poisoned")?.is_some();
+    let embeddings_error = app_state.embeddings_unavailable();
+    Ok(AppStatus {
+        encryption_enabled: cfg.encryption_enabled,
+        db_open,
+        needs_password: cfg.encryption_enabled && !db_open,
+        api_base: cfg.api_base,
+        embeddings_available: embeddings_error.is_none(),
+        embeddings_error,
+    })
+}
+
+/// Resolved paths, versions and status in one place, for pasting into bug reports.
+#[tauri::command]
+fn diagnostics(app_state: State<AppState>) -> Result<Diagnostics, String> {
+    app_state.logs.run("diagnostics", || {
+        let status = current_status(&app_state)?;
+        let db_path = app_state.db_path();
+        let model_path = app_state.model_path();
+        Ok(Diagnostics {
+            app_version: env!("CARGO_PKG_VERSION"),
+            os: std::env::consts::OS,
+            arch: std::env::consts::ARCH,
+            data_dir: app_state.data_dir.clone(),
+            config_path: app_state.config_path.clone(),
+            db_exists: db_path.exists(),
+            db_path,
+            model_exists: model_path.exists(),
+            model_path,
+            encryption_enabled: status.encryption_enabled,
+            db_open: status.db_open,
+            api_base: status.api_base,
+        })
+    })
+}
+
+/// The newest `limit` logged errors (all when omitted), oldest first. Every command that
+/// fails logs its error under its own name; background work such as recording, automatic
+/// transcription, backups and schedules logs under the job's name.
+#[tauri::command]
+fn get_recent_logs(limit: Option<usize>, app_state: State<AppState>) -> Vec<logs::LogEntry> {
+    app_state.logs.recent(limit)
+}
+
+/// The settings from `config.json`, for a settings screen.
+#[tauri::command]
+fn get_config(app_state: State<AppState>) -> Result<AppConfig, String> {
+    app_state.logs.run("get_config", || {
+        Ok(app_state.config.lock().map_err(|_| "config lock")?.clone())
+    })
+}
+
+/// Changes the settings named in `changes` (see [`AppConfig::with_changes`]), keeping the
+/// rest, and saves them. Most options are only read from `config.json`, so this is how
+/// the UI changes them without restarting. `encryption_enabled` can't be changed here; it
+/// goes through `enable_encryption` / `disable_encryption`.
+#[tauri::command]
+fn update_config(
+    changes: serde_json::Value,
+    app_state: State<AppState>,
+) -> Result<AppConfig, String> {
+    app_state.logs.run("update_config", || {
+        let reload_embedders = {
+            // Held throughout, so concurrent updates can't undo each other's changes.
+            let mut cfg = app_state.config.lock().map_err(|_| "config lock")?;
+            let mut config = cfg.with_changes(changes)?;
+            if let Some(proxy) = config.http_proxy() {
+                parse_proxy(proxy)?;
+            }
+            parse_user_agent(&config.user_agent())?;
+            if let Some(fmt) = &config.export_date_format {
+                if !config::is_valid_date_format(fmt) {
+                    return Err(format!("Invalid export date format: {fmt}"));
+                }
+            }
+            if let Some(threshold) = config.ambiguous_match_threshold {
+                let match_at = match_threshold(config.similarity_metric());
+                if !(-1.0..match_at).contains(&threshold) {
+                    return Err(format!(
+                        "Ambiguous match threshold must be at least -1 and below the match threshold ({match_at})"
+                    ));
+                }
+            }
+            config.encryption_enabled = cfg.encryption_enabled;
+            let embedder_settings = |c: &AppConfig| {
+                (
+                    c.embedding_input_layout,
+                    c.embedding_output.clone(),
+                    c.max_embedding_input_secs(),
+                )
+            };
+            let reload = embedder_settings(&cfg) != embedder_settings(&config);
+            *cfg = config;
+            reload
+        };
+        app_state.save_config()?;
+        if reload_embedders {
+            app_state.embedders.clear();
+        }
+        let cfg = app_state.config.lock().map_err(|_| "config lock")?.clone();
+        if let Some(db) = app_state
+            .db
+            .lock()
+            .map_err(|_| "DB lock poisoned")?
+            .as_mut()
+        {
+            db.compress_embeddings = cfg.compress_embeddings;
+            db.max_embeddings_per_speaker = cfg.max_embeddings_per_speaker();
+            db.set_embedding_cache_size(cfg.embedding_cache_size());
+            db.set_ann_matching(cfg.ann_matching.then(|| cfg.similarity_metric()))?;
+            if db.plaintext_search_index != cfg.plaintext_search_index {
+                db.plaintext_search_index = cfg.plaintext_search_index;
+                if cfg.plaintext_search_index {
+                    db.rebuild_search_index()?;
+                } else {
+                    db.clear_search_index()?;
+                }
+            }
+        }
+        Ok(cfg)
+    })
+}
+
+#[tauri::command]
+fn storage_stats(app_state: State<AppState>) -> Result<StorageStats, String> {
+    app_state.logs.run("storage_stats", || {
+        let counts = {
+            let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+            let db = db_guard.as_ref().ok_or("Database not initialized")?;
+            db.table_counts()?
+        };
+        let db_path = app_state.db_path();
+        let db_bytes = ["", "-wal", "-shm"]
+            .iter()
+            .map(|suffix| {
+                let mut path = db_path.clone().into_os_string();
+                path.push(suffix);
+                file_size(&PathBuf::from(path))
+            })
+            .sum();
+        let temp_bytes = std::fs::read_dir(std::env::temp_dir())
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter(|entry| entry.file_name().to_string_lossy().starts_with("recall-"))
+                    .map(|entry| file_size(&entry.path()))
+                    .sum()
+            })
+            .unwrap_or(0);
+        Ok(StorageStats {
+            db_bytes,
+            models_bytes: dir_size(&app_state.data_dir.join("models")),
+            temp_bytes,
+            sessions: counts.sessions,
+            segments: counts.segments,
+            speakers: counts.speakers,
+            embeddings: counts.embeddings,
+        })
+    })
+}
+
+fn file_size(path: &std::path::Path) -> u64 {
+    std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+fn dir_size(path: &std::path::Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(ft) if ft.is_dir() => dir_size(&entry.path()),
+            _ => file_size(&entry.path()),
+        })
+        .sum()
+}
+
+#[tauri::command]
+fn list_sessions(app_state: State<AppState>) -> Result<Vec<Session>, String> {
+    app_state.logs.run("list_sessions", || {
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        db.list_sessions()
+    })
+}
+
+/// Sessions containing every word of `query`, newest first.
+///
+/// Fast when `plaintext_search_index` is enabled, at the cost of keeping a word index on
+/// disk next to the encrypted transcripts; see `Db::search_sessions`.
+#[tauri::command]
+fn search_sessions(query: String, app_state: State<AppState>) -> Result<Vec<Session>, String> {
+    app_state.logs.run("search_sessions", || {
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        db.search_sessions(&query)
+    })
+}
+
+/// Segments across all sessions whose text contains `query`, with their session's date.
+#[tauri::command]
+fn search_segments(
+    query: String,
+    limit: Option<usize>,
+    app_state: State<AppState>,
+) -> Result<Vec<db::SegmentMatch>, String> {
+    app_state.logs.run("search_segments", || {
+        app_state.with_readonly_db(|db| {
+            db.search_segments(&query, limit.unwrap_or(DEFAULT_SEGMENT_SEARCH_LIMIT))
+        })
+    })
+}
+
+/// Min/max peak pairs (`2 * buckets` values) of a session's retained audio.
+#[tauri::command]
+fn waveform_peaks(
+    session_id: String,
+    buckets: usize,
+    app_state: State<AppState>,
+) -> Result<Vec<f32>, String> {
+    app_state.logs.run("waveform_peaks", || {
+        if buckets == 0 || buckets > MAX_WAVEFORM_BUCKETS {
+            return Err(format!(
+                "buckets must be between 1 and {MAX_WAVEFORM_BUCKETS}"
+            ));
+        }
+        let key = (session_id, buckets);
+        if let Some(peaks) = app_state
+            .waveforms
+            .lock()
+            .map_err(|_| "waveform cache lock")?
+            .get(&key)
+        {
+            return Ok(peaks.clone());
+        }
+        let path = {
+            let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+            let db = db_guard.as_ref().ok_or("Database not initialized")?;
+            db.session_audio_path(&key.0)?
+                .ok_or("No audio retained for this session")?
+        };
+        let peaks = audio::waveform_peaks(std::path::Path::new(&path), buckets)?;
+        let mut cache = app_state
+            .waveforms
+            .lock()
+            .map_err(|_| "waveform cache lock")?;
+        if cache.len() >= WAVEFORM_CACHE_ENTRIES {
+            cache.clear();
+        }
+        cache.insert(key, peaks.clone());
+        Ok(peaks)
+    })
+}
+
+/// A session's spectrogram thumbnail as a base64 PNG, for a `data:image/png;base64,` URL.
+/// With `session_thumbnails` on, sessions transcribed before it was enabled get one drawn
+/// from their kept audio on first request. `None` when there is nothing to draw from.
+#[tauri::command]
+fn session_thumbnail(
+    session_id: String,
+    app_state: State<AppState>,
+) -> Result<Option<String>, String> {
+    app_state.logs.run("session_thumbnail", || {
+        use base64::Engine as _;
+
+        let (enabled, cancellation_db) = {
+            let cfg = app_state.config.lock().map_err(|_| "config lock")?;
+            (cfg.session_thumbnails, cfg.downmix_cancellation_db())
+        };
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        if db.get_session(&session_id)?.is_none() {
+            return Err(format!("Session not found: {session_id}"));
+        }
+        let png = match db.session_thumbnail(&session_id)? {
+            Some(png) => png,
+            None => {
+                let audio_path = db
+                    .session_audio_path(&session_id)?
+                    .filter(|path| enabled && std::path::Path::new(path).exists());
+                let Some(audio_path) = audio_path else {
+                    return Ok(None);
+                };
+                let png = render_thumbnail(&audio_path, cancellation_db)?;
+                db.set_session_thumbnail(&session_id, &png)?;
+                png
+            }
+        };
+        Ok(Some(base64::engine::general_purpose::STANDARD.encode(png)))
+    })
+}
+
+/// Sessions created between `from` (inclusive) and `to` (exclusive), oldest first.
+#[tauri::command]
+fn timeline(
+    from: chrono::DateTime<Utc>,
+    to: chrono::DateTime<Utc>,
+    app_state: State<AppState>,
+) -> Result<Vec<TimelineEntry>, String> {
+    app_state.logs.run("timeline", || {
+        if to <= from {
+            return Err("`to` must be after `from`".into());
+        }
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        db.timeline(from, to)
+    })
+}
+
+#[tauri::command]
+fn list_segments(
+    session_id: String,
+    order: Option<db::SortOrder>,
+    app_state: State<AppState>,
+) -> Result<Vec<SegmentRecord>, String> {
+    app_state.logs.run("list_segments", || {
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        db.list_segments_ordered(&session_id, order.unwrap_or_default())
+    })
+}
+
+#[tauri::command]
+fn segment_at(
+    session_id: String,
+    ms: i64,
+    app_state: State<AppState>,
+) -> Result<Option<SegmentRecord>, String> {
+    app_state.logs.run("segment_at", || {
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        db.segment_at(&session_id, ms)
+    })
+}
+
+#[tauri::command]
+fn export_session_json(
+    session_id: String,
+    app_state: State<AppState>,
+) -> Result<SessionExport, String> {
+    app_state.logs.run("export_session_json", || {
+        app_state.with_readonly_db(|db| {
+            let session = db
+                .get_session(&session_id)?
+                .ok_or_else(|| format!("Session not found: {session_id}"))?;
+            let segments = db.list_segments(&session_id)?;
+            let speaker_ids: HashSet<&str> = segments
+                .iter()
+                .filter_map(|seg| seg.speaker_id.as_deref())
+                .collect();
+            let speakers = db
+                .list_speakers(false)?
+                .into_iter()
+                .filter(|speaker| speaker_ids.contains(speaker.id.as_str()))
+                .collect();
+            Ok(SessionExport {
+                session,
+                segments,
+                speakers,
+            })
+        })
+    })
+}
+
+/// A session's heading and its segments formatted like a transcript rebuilt with speaker
+/// prefixes, as Markdown or plain text.
+fn render_session(
+    db: &Db,
+    session: &Session,
+    cfg: &AppConfig,
+    markdown: bool,
+) -> Result<String, String> {
+    let segments = db.list_segments(&session.id)?;
+    let body = db::join_segments(
+        &segments,
+        Some(cfg.speaker_prefix_format()),
+        cfg.segment_separator(),
+    );
+    let started = cfg.format_export_time(session.created_at);
+    Ok(if markdown {
+        format!("# Session {started}\n\n{body}\n")
+    } else {
+        format!("Session {started}\n\n{body}\n")
+    })
+}
+
+/// Renders a session as Markdown: a heading with its start time, then its segments
+/// formatted like a transcript rebuilt with speaker prefixes.
+#[tauri::command]
+fn export_session_markdown(
+    session_id: String,
+    app_state: State<AppState>,
+) -> Result<String, String> {
+    app_state.logs.run("export_session_markdown", || {
+        let cfg = app_state.config.lock().map_err(|_| "config lock")?.clone();
+        app_state.with_readonly_db(|db| {
+            let session = db
+                .get_session(&session_id)?
+                .ok_or_else(|| format!("Session not found: {session_id}"))?;
+            render_session(db, &session, &cfg, true)
+        })
+    })
+}
+
+/// A session's segments as an Audacity label track: one `start<TAB>end<TAB>speaker` line
+/// per segment, times in seconds from the start of the recording.
+#[tauri::command]
+fn export_labels(session_id: String, app_state: State<AppState>) -> Result<String, String> {
+    app_state.logs.run("export_labels", || {
+        app_state.with_readonly_db(|db| {
+            if db.get_session(&session_id)?.is_none() {
+                return Err(format!("Session not found: {session_id}"));
+            }
+            Ok(render_labels(&db.list_segments(&session_id)?))
+        })
+    })
+}
+
+fn render_labels(segments: &[SegmentRecord]) -> String {
+    segments
+        .iter()
+        .map(|seg| {
+            // A tab or line break in a name would split the label into extra fields.
+            let label: String = seg
+                .speaker_label
+                .as_deref()
+                .unwrap_or("Unknown")
+                .chars()
+                .map(|c| if c.is_control() { ' ' } else { c })
+                .collect();
+            format!(
+                "{:.3}\t{:.3}\t{label}\n",
+                seg.start_ms as f64 / 1000.0,
+                seg.end_ms as f64 / 1000.0
+            )
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ExportAllReport {
+    path: String,
+    sessions: usize,
+}
+
+/// Writes every session, oldest first, into one `"markdown"` or `"text"` document at
+/// `path`. Sessions are decrypted and written one at a time rather than held in memory.
+#[tauri::command]
+fn export_all(
+    format: String,
+    path: String,
+    app_state: State<AppState>,
+) -> Result<ExportAllReport, String> {
+    use std::io::Write;
+
+    let markdown = match format.as_str() {
+        "markdown" | "md" => true,
+        "text" | "txt" => false,
+        other => return Err(format!("Unknown export format: {other}")),
+    };
+    let cfg = app_state.config.lock().map_err(|_| "config lock")?.clone();
+    let result = app_state.with_readonly_db(|db| {
+        let file = std::fs::File::create(&path).map_err(|e| format!("Export failed: {e}"))?;
+        let mut out = std::io::BufWriter::new(file);
+        let mut sessions = 0;
+        for session_id in db.session_ids()? {
+            // Deleted while exporting.
+            let Some(session) = db.get_session(&session_id)? else {
+                continue;
+            };
+            if sessions > 0 {
+                out.write_all(b"\n")
+                    .map_err(|e| format!("Export failed: {e}"))?;
+            }
+            out.write_all(render_session(db, &session, &cfg, markdown)?.as_bytes())
+                .map_err(|e| format!("Export failed: {e}"))?;
+            sessions += 1;
+        }
+        out.flush().map_err(|e| format!("Export failed: {e}"))?;
+        Ok(ExportAllReport {
+            path: path.clone(),
+            sessions,
+        })
+    });
+    app_state.logs.check("export_all", result)
+}
+
+#[tauri::command]
+fn update_transcript(
+    session_id: String,
+    transcript: String,
+    app_state: State<AppState>,
+) -> Result<(), String> {
+    app_state.logs.run("update_transcript", || {
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        db.replace_session_transcript(&session_id, &transcript)
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct TranscriptVersions {
+    /// Segments with `speaker_prefix_format` before each, as in exports.
+    annotated: String,
+    /// The words only, as searched.
+    clean: String,
+}
+
+/// Both forms of a session's transcript, whichever one is stored.
+#[tauri::command]
+fn transcript_versions(
+    session_id: String,
+    app_state: State<AppState>,
+) -> Result<TranscriptVersions, String> {
+    app_state.logs.run("transcript_versions", || {
+        let cfg = app_state.config.lock().map_err(|_| "config lock")?.clone();
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        let session = db
+            .get_session(&session_id)?
+            .ok_or_else(|| format!("Session not found: {session_id}"))?;
+        let segments = db.list_segments(&session_id)?;
+        Ok(TranscriptVersions {
+            annotated: db::join_segments(
+                &segments,
+                Some(cfg.speaker_prefix_format()),
+                cfg.segment_separator(),
+            ),
+            clean: db.clean_transcript(&session)?,
+        })
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct SegmentExclusion {
+    segment_id: String,
+    excluded: bool,
+    /// The speaker's voiceprint from this session was re-learned from the kept audio.
+    relearned: bool,
+}
+
+/// Marks a segment as "do not learn" (or clears the mark). When the session's audio was
+/// kept, the segment's speaker is re-embedded from their remaining segments right away;
+/// otherwise only future embeddings are affected.
+#[tauri::command]
+fn set_segment_excluded(
+    segment_id: String,
+    excluded: bool,
+    app_state: State<AppState>,
+) -> Result<SegmentExclusion, String> {
+    let result = relearn_without_excluded(&app_state, &segment_id, excluded).map(|relearned| {
+        SegmentExclusion {
+            segment_id: segment_id.clone(),
+            excluded,
+            relearned,
+        }
+    });
+    app_state.logs.check("set_segment_excluded", result)
+}
+
+fn relearn_without_excluded(
+    app_state: &AppState,
+    segment_id: &str,
+    excluded: bool,
+) -> Result<bool, String> {
+    let cfg = app_state.config.lock().map_err(|_| "config lock")?.clone();
+    let (session_id, audio_path, segments) = {
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        let session_id = db
+            .set_segment_excluded(segment_id, excluded)?
+            .ok_or_else(|| format!("Segment not found: {segment_id}"))?;
+        let audio_path = db.session_audio_path(&session_id)?;
+        let segments = db.list_segments(&session_id)?;
+        (session_id, audio_path, segments)
+    };
+    let speaker_id = segments
+        .iter()
+        .find(|s| s.id == segment_id)
+        .and_then(|s| s.speaker_id.clone());
+    let (Some(speaker_id), Some(audio_path)) = (speaker_id, audio_path) else {
+        return Ok(false);
+    };
+    if !std::path::Path::new(&audio_path).exists() {
+        return Ok(false);
+    }
+
+    let speaker_segments: Vec<ApiSegment> = segments
+        .into_iter()
+        .filter(|s| s.speaker_id.as_deref() == Some(speaker_id.as_str()))
+        .map(|s| ApiSegment {
+            speaker: speaker_id.clone(),
+            start_ms: s.start_ms.max(0) as u64,
+            end_ms: s.end_ms.max(0) as u64,
+            text: String::new(),
+            exclude_from_embedding: s.exclude_from_embedding,
+        })
+        .collect();
+    let mut speaker_audio = read_speaker_audio(
+        &audio_path,
+        &speaker_segments,
+        cfg.min_segment_ms(),
+        cfg.downmix_cancellation_db(),
+    )?;
+    let pcm = speaker_audio
+        .by_speaker
+        .remove(&speaker_id)
+        .unwrap_or_default();
+    let mut embedder = app_state.checkout_embedder()?;
+    let vector = if pcm.is_empty() {
+        None
+    } else {
+        Some(embedder.embed(&pcm, speaker_audio.sample_rate)?)
+    };
+
+    let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+    let db = db_guard.as_ref().ok_or("Database not initialized")?;
+    db.in_savepoint(|db| {
+        db.replace_session_embedding(&session_id, &speaker_id, vector.as_deref())
+    })?;
+    Ok(true)
+}
+
+/// Re-embeds every speaker of a session from its retained audio with the current
+/// `min_segment_ms`, replacing the voiceprints the session contributed. Transcript and
+/// segments are left as they are. Returns how many voiceprints were stored; speakers
+/// with no usable audio left lose theirs.
+#[tauri::command]
+fn reprocess_session_embeddings(
+    session_id: String,
+    app_state: State<AppState>,
+) -> Result<usize, String> {
+    let result = reprocess_embeddings(&app_state, &session_id);
+    app_state.logs.check("reprocess_session_embeddings", result)
+}
+
+fn reprocess_embeddings(app_state: &AppState, session_id: &str) -> Result<usize, String> {
+    let cfg = app_state.config.lock().map_err(|_| "config lock")?.clone();
+    let (audio_path, segments) = app_state.with_readonly_db(|db| {
+        if db.get_session(session_id)?.is_none() {
+            return Err(format!("Session not found: {session_id}"));
+        }
+        Ok((
+            db.session_audio_path(session_id)?,
+            db.list_segments(session_id)?,
+        ))
+    })?;
+    let audio_path = audio_path
+        .filter(|path| std::path::Path::new(path).exists())
+        .ok_or("Session audio was not kept")?;
+
+    let speaker_segments: Vec<ApiSegment> = segments
+        .into_iter()
+        .filter_map(|s| {
+            Some(ApiSegment {
+                speaker: s.speaker_id?,
+                start_ms: s.start_ms.max(0) as u64,
+                end_ms: s.end_ms.max(0) as u64,
+                text: String::new(),
+                exclude_from_embedding: s.exclude_from_embedding,
+            })
+        })
+        .collect();
+    let speaker_ids: HashSet<&str> = speaker_segments
+        .iter()
+        .map(|s| s.speaker.as_str())
+        .collect();
+    let mut speaker_audio = read_speaker_audio(
+        &audio_path,
+        &speaker_segments,
+        cfg.min_segment_ms(),
+        cfg.downmix_cancellation_db(),
+    )?;
+
+    let mut embedder = app_state.checkout_embedder()?;
+    let mut vectors = Vec::with_capacity(speaker_ids.len());
+    for speaker_id in speaker_ids {
+        let pcm = speaker_audio
+            .by_speaker
+            .remove(speaker_id)
+            .unwrap_or_default();
+        let vector = if pcm.is_empty() {
+            None
+        } else {
+            Some(embedder.embed(&pcm, speaker_audio.sample_rate)?)
+        };
+        vectors.push((speaker_id, vector));
+    }
+
+    let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+    let db = db_guard.as_ref().ok_or("Database not initialized")?;
+    db.in_savepoint(|db| {
+        for (speaker_id, vector) in &vectors {
+            db.replace_session_embedding(session_id, speaker_id, vector.as_deref())?;
+        }
+        Ok(())
+    })?;
+    Ok(vectors
+        .iter()
+        .filter(|(_, vector)| vector.is_some())
+        .count())
+}
+
+/// Edits one segment's text. With `rebuild_transcript` the session transcript is
+/// re-derived from the segments and returned.
+#[tauri::command]
+fn update_segment_text(
+    segment_id: String,
+    text: String,
+    rebuild_transcript: Option<bool>,
+    app_state: State<AppState>,
+) -> Result<Option<String>, String> {
+    app_state.logs.run("update_segment_text", || {
+        let cfg = app_state.config.lock().map_err(|_| "config lock")?.clone();
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        let session_id = db
+            .update_segment_text(&segment_id, &text)?
+            .ok_or_else(|| format!("Segment not found: {segment_id}"))?;
+        if rebuild_transcript.unwrap_or(false) {
+            db.rebuild_transcript(
+                &session_id,
+                cfg.transcript_prefix(),
+                cfg.segment_separator(),
+            )
+            .map(Some)
+        } else {
+            Ok(None)
+        }
+    })
+}
+
+#[tauri::command]
+fn rebuild_transcript(
+    session_id: String,
+    speaker_prefixes: Option<bool>,
+    app_state: State<AppState>,
+) -> Result<String, String> {
+    app_state.logs.run("rebuild_transcript", || {
+        let cfg = app_state.config.lock().map_err(|_| "config lock")?.clone();
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        if db.get_session(&session_id)?.is_none() {
+            return Err(format!("Session not found: {session_id}"));
+        }
+        let speaker_prefix = speaker_prefixes
+            .unwrap_or(cfg.transcript_speaker_prefixes)
+            .then(|| cfg.speaker_prefix_format());
+        db.rebuild_transcript(&session_id, speaker_prefix, cfg.segment_separator())
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct SegmentOverlap {
+    first_segment_id: String,
+    second_segment_id: String,
+    first_speaker: Option<String>,
+    second_speaker: Option<String>,
+    start_ms: i64,
+    end_ms: i64,
+    overlap_ms: i64,
+}
 
-    let client = Client::builder()
-        .timeout(std::time::Duration::from_secs(240))
-        .build()
-        .map_err(|e| format!("HTTP client error: {e}"))?;
+/// Pairs of a session's segments from different speakers whose time ranges intersect,
+/// in order of where the overlap starts.
+#[tauri::command]
+fn detect_overlaps(
+    session_id: String,
+    app_state: State<AppState>,
+) -> Result<Vec<SegmentOverlap>, String> {
+    app_state.logs.run("detect_overlaps", || {
+        let segments = app_state.with_readonly_db(|db| db.list_segments(&session_id))?;
+        let speaker = |s: &SegmentRecord| s.speaker_id.clone().or_else(|| s.speaker_label.clone());
+        let mut overlaps = Vec::new();
+        // Segments are sorted by start, so only later ones starting before `first` ends can overlap.
+        for (i, first) in segments.iter().enumerate() {
+            for second in segments[i + 1..]
+                .iter()
+                .take_while(|s| s.start_ms < first.end_ms)
+            {
+                if speaker(first) == speaker(second) {
+                    continue;
+                }
+                let end_ms = first.end_ms.min(second.end_ms);
+                if end_ms <= second.start_ms {
+                    continue;
+                }
+                overlaps.push(SegmentOverlap {
+                    first_segment_id: first.id.clone(),
+                    second_segment_id: second.id.clone(),
+                    first_speaker: first.speaker_label.clone(),
+                    second_speaker: second.speaker_label.clone(),
+                    start_ms: second.start_ms,
+                    end_ms,
+                    overlap_ms: end_ms - second.start_ms,
+                });
+            }
+        }
+        overlaps.sort_by_key(|o| o.start_ms);
+        Ok(overlaps)
+    })
+}
 
-    let res = client
-        .post(url)
-        .multipart(form)
-        .send()
-        .map_err(|e| format!("HTTP error: {e}"))?;
+/// Splits a session at `ms` into two, rebuilding both transcripts from their segments.
+/// Returns the id of the new, later session.
+#[tauri::command]
+fn split_session_at(
+    session_id: String,
+    ms: u64,
+    app_state: State<AppState>,
+) -> Result<String, String> {
+    app_state.logs.run("split_session_at", || {
+        let cfg = app_state.config.lock().map_err(|_| "config lock")?.clone();
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        let new_id = db.split_session(&session_id, ms as i64)?;
+        for id in [&session_id, &new_id] {
+            db.rebuild_transcript(id, cfg.transcript_prefix(), cfg.segment_separator())?;
+        }
+        Ok(new_id)
+    })
+}
 
-    if !res.status().is_success() {
-        return Err(format!("API responded with status {}", res.status()));
-    }
+#[tauri::command]
+fn delete_session(session_id: String, app_state: State<AppState>) -> Result<(), String> {
+    app_state.logs.run("delete_session", || {
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        db.delete_session(&session_id)
+    })
+}
 
-    let api_resp: ApiTranscribeResponse = res
-        .json()
-        .map_err(|e| format!("Decode error: {e}"))?;
-    let _ = (&api_resp.summary, &api_resp.speakers, &api_resp.audio_url);
+#[tauri::command]
+fn delete_sessions(ids: Vec<String>, app_state: State<AppState>) -> Result<usize, String> {
+    app_state.logs.run("delete_sessions", || {
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        db.delete_sessions(&ids)
+    })
+}
 
-    let audio_clip = read_audio_clip(&path)?;
-    let segments = normalize_segments(api_resp.segments.clone(), &api_resp.transcript, &audio_clip);
+/// Reverts the most recent transcription, as long as it finished within `UNDO_WINDOW_SECS`.
+/// Each transcription can be undone once; a transcription appended to an existing
+/// session can't be.
+#[tauri::command]
+fn undo_last_transcription(app_state: State<AppState>) -> Result<UndoSummary, String> {
+    app_state.logs.run("undo_last_transcription", || {
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        let undoable = app_state
+            .undoable
+            .lock()
+            .map_err(|_| "undo lock")?
+            .take()
+            .ok_or("Nothing to undo")?;
+        let age = Utc::now().signed_duration_since(undoable.finished_at);
+        if age > chrono::Duration::seconds(UNDO_WINDOW_SECS) {
+            return Err("The last transcription is too old to undo".into());
+        }
+        db.undo_session(&undoable.session_id)
+    })
+}
 
-    let session_id = db
-        .insert_session(&api_resp.transcript)
-        .map_err(|e| format!("DB error: {e}"))?;
+/// Sessions the retention policy would delete; `None` when `retention_days` is unset.
+fn retention_candidates(db: &Db, cfg: &AppConfig) -> Result<Option<RetentionReport>, String> {
+    let Some(retention_days) = cfg.retention_days else {
+        return Ok(None);
+    };
+    let cutoff = Utc::now() - chrono::Duration::days(retention_days as i64);
+    let session_ids = db.sessions_created_before(cutoff)?;
+    Ok(Some(RetentionReport {
+        retention_days,
+        cutoff,
+        session_ids,
+    }))
+}
 
-    {
-        let mut embedder_guard = app_state.embedder.lock().map_err(|_| "embedder lock")?;
-        let embedder = embedder_guard
-            .as_mut()
-            .ok_or("Embedder not initialized")?;
-        process_segments(&audio_clip, &segments, &session_id, db, embedder)?;
+#[tauri::command]
+fn preview_retention(app_state: State<AppState>) -> Result<Option<RetentionReport>, String> {
+    app_state.logs.run("preview_retention", || {
+        let cfg = app_state.config.lock().map_err(|_| "config lock")?.clone();
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        retention_candidates(db, &cfg)
+    })
+}
+
+/// Deletes expired sessions and emits `retention-applied` with what was removed.
+/// Does nothing while the database is locked.
+fn apply_retention(app: &tauri::AppHandle) -> Result<(), String> {
+    let app_state = app.state::<AppState>();
+    let cfg = app_state.config.lock().map_err(|_| "config lock")?.clone();
+    let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+    let Some(db) = db_guard.as_ref() else {
+        return Ok(());
+    };
+    let Some(report) = retention_candidates(db, &cfg)? else {
+        return Ok(());
+    };
+    if report.session_ids.is_empty() {
+        return Ok(());
     }
+    db.delete_sessions(&report.session_ids)?;
+    app.emit("retention-applied", report)
+        .map_err(|e| e.to_string())
+}
 
-    let _ = std::fs::remove_file(&path);
+fn spawn_retention_task(app: tauri::AppHandle) {
+    thread::spawn(move || loop {
+        if let Err(e) = apply_retention(&app) {
+            app.state::<AppState>().logs.error("retention", e);
+        }
+        thread::sleep(RETENTION_CHECK_INTERVAL);
+    });
+}
 
-    Ok(api_resp.transcript)
+#[derive(Debug, Clone, Serialize)]
+struct BackupDone {
+    path: String,
 }
 
-fn read_audio_clip(path: &str) -> Result<AudioClip, String> {
-    let mut reader = hound::WavReader::open(path)
-        .map_err(|e| format!("Failed to open audio for embeddings: {e}"))?;
-    let spec = reader.spec();
-    let channels = std::cmp::max(spec.channels as usize, 1);
-    let mut interleaved: Vec<f32> = Vec::new();
-    match (spec.sample_format, spec.bits_per_sample) {
-        (hound::SampleFormat::Int, 16) => {
-            for sample in reader.samples::<i16>() {
-                let s = sample.map_err(|e| format!("Sample decode error: {e}"))?;
-                interleaved.push(s as f32 / i16::MAX as f32);
-            }
+#[derive(Debug, Clone, Serialize)]
+struct BackupError {
+    error: String,
+}
+
+fn backup_dir(app_state: &AppState, cfg: &AppConfig) -> PathBuf {
+    cfg.backup_dir
+        .clone()
+        .unwrap_or_else(|| app_state.data_dir.join("backups"))
+}
+
+/// Backups in `dir` written by [`write_backup`], oldest first. Names embed the UTC
+/// timestamp, so name order is age order.
+fn list_backups(dir: &std::path::Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut backups: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with(BACKUP_PREFIX) && n.ends_with(".db"))
+        })
+        .collect();
+    backups.sort();
+    backups
+}
+
+/// Copies the database into `dir` under a timestamped name.
+fn write_backup(db: &Db, dir: &std::path::Path) -> Result<PathBuf, String> {
+    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let name = format!("{BACKUP_PREFIX}{}.db", Utc::now().format("%Y%m%d-%H%M%S"));
+    let path = dir.join(name);
+    if path.exists() {
+        return Err(format!("Backup {} already exists", path.display()));
+    }
+    db.backup_to(&path)?;
+    Ok(path)
+}
+
+/// Writes a backup of the database to `dir` (the configured backup directory when
+/// omitted) and returns its path. With encryption on, the backup needs the same password.
+#[tauri::command]
+fn export_backup(dir: Option<String>, app_state: State<AppState>) -> Result<String, String> {
+    app_state.logs.run("export_backup", || {
+        let cfg = app_state.config.lock().map_err(|_| "config lock")?.clone();
+        let dir = dir
+            .map(PathBuf::from)
+            .unwrap_or_else(|| backup_dir(&app_state, &cfg));
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        let path = write_backup(db, &dir)?;
+        Ok(path.to_string_lossy().to_string())
+    })
+}
+
+/// Writes a scheduled backup when the newest one is older than `backup_interval_hours`,
+/// then deletes all but the newest `backup_keep`. Returns the new backup's path, if any.
+/// Does nothing while the database is locked.
+fn run_scheduled_backup(app: &tauri::AppHandle) -> Result<Option<PathBuf>, String> {
+    let app_state = app.state::<AppState>();
+    let cfg = app_state.config.lock().map_err(|_| "config lock")?.clone();
+    let Some(hours) = cfg.backup_interval_hours.filter(|h| *h > 0) else {
+        return Ok(None);
+    };
+    let dir = backup_dir(&app_state, &cfg);
+    let interval = Duration::from_secs(u64::from(hours) * 60 * 60);
+    let last = list_backups(&dir)
+        .last()
+        .and_then(|p| std::fs::metadata(p).ok())
+        .and_then(|m| m.modified().ok());
+    if let Some(last) = last {
+        if last.elapsed().map(|age| age < interval).unwrap_or(true) {
+            return Ok(None);
         }
-        (hound::SampleFormat::Int, 24) | (hound::SampleFormat::Int, 32) => {
-            for sample in reader.samples::<i32>() {
-                let s = sample.map_err(|e| format!("Sample decode error: {e}"))?;
-                interleaved.push(s as f32 / i32::MAX as f32);
+    }
+    let path = {
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let Some(db) = db_guard.as_ref() else {
+            return Ok(None);
+        };
+        write_backup(db, &dir)?
+    };
+    let backups = list_backups(&dir);
+    let excess = backups.len().saturating_sub(cfg.backup_keep());
+    for old in &backups[..excess] {
+        std::fs::remove_file(old).map_err(|e| e.to_string())?;
+    }
+    Ok(Some(path))
+}
+
+/// Checks right away, so a stale backup is replaced on startup, then every
+/// `BACKUP_CHECK_INTERVAL`. Emits `backup:done` or `backup:error`.
+fn spawn_backup_task(app: tauri::AppHandle) {
+    thread::spawn(move || loop {
+        match run_scheduled_backup(&app) {
+            Ok(Some(path)) => {
+                let path = path.to_string_lossy().to_string();
+                let _ = app.emit("backup:done", BackupDone { path });
             }
-        }
-        (hound::SampleFormat::Float, _) => {
-            for sample in reader.samples::<f32>() {
-                let s = sample.map_err(|e| format!("Sample decode error: {e}"))?;
-                interleaved.push(s);
+            Ok(None) => {}
+            Err(error) => {
+                app.state::<AppState>().logs.error("backup", error.clone());
+                let _ = app.emit("backup:error", BackupError { error });
             }
         }
-        _ => return Err("Unsupported WAV format for embedding".into()),
-    }
-    if interleaved.is_empty() {
-        return Err("Audio buffer is empty".into());
-    }
-    let mut mono = Vec::with_capacity(interleaved.len() / channels + 1);
-    for frame in interleaved.chunks(channels) {
-        let sum: f32 = frame.iter().sum();
-        mono.push(sum / channels as f32);
-    }
-    Ok(AudioClip {
-        samples: mono,
-        sample_rate: spec.sample_rate,
-    })
+        thread::sleep(BACKUP_CHECK_INTERVAL);
+    });
 }
 
-fn normalize_segments(
-    segments: Option<Vec<ApiSegment>>,
-    transcript: &str,
-    audio: &AudioClip,
-) -> Vec<ApiSegment> {
-    let mut segs = segments.unwrap_or_default();
-    if segs.is_empty() {
-        let end_ms = audio.duration_ms().max(1_000);
-        segs.push(ApiSegment {
-            speaker: "speaker_0".to_string(),
-            start_ms: 0,
-            end_ms,
-            text: transcript.to_string(),
-        });
-    }
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum ScheduleState {
+    Pending,
+    Recording,
+}
 
-    let max_end = audio.duration_ms();
-    for seg in segs.iter_mut() {
-        if seg.end_ms == 0 || seg.end_ms < seg.start_ms {
-            seg.end_ms = seg.start_ms.saturating_add(1_000);
+#[derive(Debug, Clone, Serialize)]
+struct ScheduledRecording {
+    id: String,
+    start_at: chrono::DateTime<Utc>,
+    stop_at: chrono::DateTime<Utc>,
+    state: ScheduleState,
+    /// The file being written, once recording has started.
+    path: Option<PathBuf>,
+}
+
+/// Recordings waiting to start or stop, soonest first. They only live in memory, so
+/// schedules are lost when the app quits and nothing records while it isn't running.
+#[derive(Default)]
+struct RecordingScheduler {
+    entries: Mutex<Vec<ScheduledRecording>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ScheduleEvent {
+    id: String,
+    path: Option<String>,
+    error: Option<String>,
+}
+
+/// Schedules a recording from `start_at` to `stop_at` (RFC 3339). It is transcribed once
+/// it stops. A start time that has already passed starts on the next check.
+#[tauri::command]
+fn schedule_recording(
+    start_at: chrono::DateTime<Utc>,
+    stop_at: chrono::DateTime<Utc>,
+    scheduler: State<RecordingScheduler>,
+    app_state: State<AppState>,
+) -> Result<ScheduledRecording, String> {
+    app_state.logs.run("schedule_recording", || {
+        if stop_at <= start_at {
+            return Err("`stop_at` must be after `start_at`".into());
         }
-        if max_end > 0 && seg.end_ms > max_end {
-            seg.end_ms = max_end;
+        if stop_at <= Utc::now() {
+            return Err("`stop_at` has already passed".into());
         }
-    }
-    segs.sort_by_key(|s| s.start_ms);
-    segs
+        let mut entries = scheduler.entries.lock().map_err(|_| "Lock poisoned")?;
+        if let Some(other) = entries
+            .iter()
+            .find(|e| e.start_at < stop_at && start_at < e.stop_at)
+        {
+            return Err(format!("Overlaps scheduled recording {}", other.id));
+        }
+        let entry = ScheduledRecording {
+            id: uuid::Uuid::new_v4().to_string(),
+            start_at,
+            stop_at,
+            state: ScheduleState::Pending,
+            path: None,
+        };
+        entries.push(entry.clone());
+        entries.sort_by_key(|e| e.start_at);
+        Ok(entry)
+    })
 }
 
-fn collect_audio_by_speaker(
-    audio: &AudioClip,
-    segments: &[ApiSegment],
-) -> HashMap<String, Vec<f32>> {
-    let mut buckets: HashMap<String, Vec<f32>> = HashMap::new();
-    let total_samples = audio.samples.len();
-    let target_samples =
-        std::cmp::max(1, ((audio.sample_rate as u64 * TARGET_SPEAKER_MS) / 1000) as usize);
-    let sr = audio.sample_rate as f64;
+#[tauri::command]
+fn list_scheduled_recordings(
+    scheduler: State<RecordingScheduler>,
+    app_state: State<AppState>,
+) -> Result<Vec<ScheduledRecording>, String> {
+    app_state.logs.run("list_scheduled_recordings", || {
+        let entries = scheduler.entries.lock().map_err(|_| "Lock poisoned")?;
+        Ok(entries.clone())
+    })
+}
 
-    for seg in segments {
-        let start = ((seg.start_ms as f64 / 1000.0) * sr).floor() as usize;
-        let end = ((seg.end_ms as f64 / 1000.0) * sr).ceil() as usize;
-        if end <= start {
-            continue;
+/// Removes a scheduled recording. One that has already started is stopped now and still
+/// transcribed.
+#[tauri::command]
+fn cancel_scheduled_recording(
+    id: String,
+    app: tauri::AppHandle,
+    scheduler: State<RecordingScheduler>,
+    app_state: State<AppState>,
+) -> Result<(), String> {
+    app_state.logs.run("cancel_scheduled_recording", || {
+        let entry = {
+            let mut entries = scheduler.entries.lock().map_err(|_| "Lock poisoned")?;
+            let index = entries
+                .iter()
+                .position(|e| e.id == id)
+                .ok_or_else(|| format!("Scheduled recording not found: {id}"))?;
+            entries.remove(index)
+        };
+        if entry.state == ScheduleState::Recording {
+            stop_scheduled(&app, &entry);
         }
-        let start_idx = std::cmp::min(start, total_samples);
-        let end_idx = std::cmp::min(end, total_samples);
-        if end_idx <= start_idx {
-            continue;
+        Ok(())
+    })
+}
+
+/// Starts `entry` and returns whether to keep it. Emits `schedule:started` or
+/// `schedule:error`.
+fn start_scheduled(app: &tauri::AppHandle, entry: &mut ScheduledRecording) -> bool {
+    let app_state = app.state::<AppState>();
+    let result = if entry.stop_at <= Utc::now() {
+        // The machine slept through the whole slot.
+        Err("The scheduled time passed before recording could start".to_string())
+    } else {
+        app_state
+            .config
+            .lock()
+            .map(|cfg| RecordingOptions::from_config(&cfg, app_state.logs.clone()))
+            .map_err(|_| "config lock".to_string())
+            .and_then(|options| app.state::<RecordingManager>().start(options, app.clone()))
+    };
+    match app_state.logs.check("scheduled_recording", result) {
+        Ok(path) => {
+            let event = ScheduleEvent {
+                id: entry.id.clone(),
+                path: Some(path.to_string_lossy().to_string()),
+                error: None,
+            };
+            entry.state = ScheduleState::Recording;
+            entry.path = Some(path);
+            let _ = app.emit("schedule:started", event);
+            true
         }
-        let entry = buckets.entry(seg.speaker.clone()).or_default();
-        let remaining = target_samples.saturating_sub(entry.len());
-        if remaining == 0 {
-            continue;
+        Err(error) => {
+            let event = ScheduleEvent {
+                id: entry.id.clone(),
+                path: None,
+                error: Some(error),
+            };
+            let _ = app.emit("schedule:error", event);
+            false
         }
-        let take_len = std::cmp::min(remaining, end_idx - start_idx);
-        entry.extend_from_slice(&audio.samples[start_idx..start_idx + take_len]);
     }
-
-    buckets
 }
 
-fn best_match<'a>(
-    embedding: &[f32],
-    known: &'a [StoredEmbedding],
-) -> Option<(&'a StoredEmbedding, f32)> {
-    let mut best: Option<(&StoredEmbedding, f32)> = None;
-    for record in known {
-        if record.vector.len() != embedding.len() {
-            continue;
+/// Stops `entry`'s recording and transcribes it. A recording already stopped by hand is
+/// left to whatever stopped it. Emits `schedule:stopped` or `schedule:error`.
+fn stop_scheduled(app: &tauri::AppHandle, entry: &ScheduledRecording) {
+    let Some(path) = &entry.path else {
+        return;
+    };
+    let app_state = app.state::<AppState>();
+    let result = app.state::<RecordingManager>().stop_if_writing(path);
+    match app_state.logs.check("scheduled_recording", result) {
+        Ok(stopped) => {
+            let event = ScheduleEvent {
+                id: entry.id.clone(),
+                path: Some(path.to_string_lossy().to_string()),
+                error: None,
+            };
+            let _ = app.emit("schedule:stopped", event);
+            if let Some(path) = stopped {
+                spawn_transcription(app.clone(), path);
+            }
         }
-        let score = embedding::cosine_similarity(embedding, &record.vector);
-        match best {
-            Some((_, current)) if score <= current => continue,
-            _ => best = Some((record, score)),
+        Err(error) => {
+            let event = ScheduleEvent {
+                id: entry.id.clone(),
+                path: Some(path.to_string_lossy().to_string()),
+                error: Some(error),
+            };
+            let _ = app.emit("schedule:error", event);
         }
     }
-    if let Some((rec, score)) = best {
-        if score >= MATCH_THRESHOLD {
-            return Some((rec, score));
+}
+
+/// Starts and stops scheduled recordings as their times come up. Entries are in start
+/// order and don't overlap, so one stops before the next starts in the same check.
+fn spawn_schedule_task(app: tauri::AppHandle) {
+    thread::spawn(move || loop {
+        let scheduler = app.state::<RecordingScheduler>();
+        if let Ok(mut entries) = scheduler.entries.lock() {
+            let now = Utc::now();
+            entries.retain_mut(|entry| match entry.state {
+                ScheduleState::Pending if entry.start_at <= now => start_scheduled(&app, entry),
+                ScheduleState::Recording if entry.stop_at <= now => {
+                    stop_scheduled(&app, entry);
+                    false
+                }
+                _ => true,
+            });
         }
-    }
-    None
+        thread::sleep(SCHEDULE_CHECK_INTERVAL);
+    });
 }
 
-fn process_segments(
-    audio: &AudioClip,
-    segments: &[ApiSegment],
-    session_id: &str,
+/// Re-runs speaker matching for a session's stored embeddings against every other
+/// session's, using `threshold` (in the configured `similarity_metric`) instead of the
+/// default. Nothing is re-embedded.
+///
+/// Segments are tracked per speaker, so diarization buckets that were merged into the
+/// same speaker move together.
+#[tauri::command]
+fn rematch_session(
+    session_id: String,
+    threshold: f32,
+    app_state: State<AppState>,
+) -> Result<RematchReport, String> {
+    app_state.logs.run("rematch_session", || {
+        if !(-1.0..=1.0).contains(&threshold) {
+            return Err("Threshold must be between -1 and 1".into());
+        }
+        let (metric, prefix) = {
+            let cfg = app_state.config.lock().map_err(|_| "config lock")?;
+            (
+                cfg.similarity_metric(),
+                cfg.speaker_label_prefix().to_string(),
+            )
+        };
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        let (own, others) = partition_embeddings(db, &session_id)?;
+        if own.is_empty() {
+            return Err("Session has no stored embeddings".into());
+        }
+        let prefix = Some(prefix.as_str());
+        let changes = db.in_savepoint(|db| {
+            rematch_speakers(db, &session_id, &own, &others, metric, threshold, prefix)
+        })?;
+        Ok(RematchReport {
+            session_id,
+            threshold,
+            changes,
+        })
+    })
+}
+
+/// Stored embeddings split into those made from `session_id` and everyone else's.
+fn partition_embeddings(
     db: &Db,
-    embedder: &mut crate::embedding::Embedder,
-) -> Result<(), String> {
-    let mut diarization_to_profile: HashMap<String, (String, String)> = HashMap::new();
-    let mut known_embeddings = db.list_embeddings()?;
-    let speakers = db.list_speakers()?;
-    let mut next_label_index = speakers.len() + 1;
+    session_id: &str,
+) -> Result<(Vec<StoredEmbedding>, Vec<StoredEmbedding>), String> {
+    Ok(db
+        .list_embeddings()?
+        .into_iter()
+        .partition(|e| e.source_session_id.as_deref() == Some(session_id)))
+}
 
-    for (speaker_key, pcm) in collect_audio_by_speaker(audio, segments) {
-        if pcm.is_empty() {
-            continue;
+/// Matches a session's embeddings (`own`) against every other session's (`others`) at
+/// `threshold` and moves each of the session's speakers to the speaker it now matches.
+/// Segments belong to speakers, not diarization buckets, so a speaker moves as a whole,
+/// decided by its best-matching embedding. A speaker that matches nobody but is shared
+/// with other sessions is split off under `new_speaker_prefix`, or kept when that's `None`.
+fn rematch_speakers(
+    db: &Db,
+    session_id: &str,
+    own: &[StoredEmbedding],
+    others: &[StoredEmbedding],
+    metric: SimilarityMetric,
+    threshold: f32,
+    new_speaker_prefix: Option<&str>,
+) -> Result<Vec<RematchChange>, String> {
+    let mut by_speaker: Vec<(&str, Vec<&StoredEmbedding>)> = Vec::new();
+    for record in own {
+        match by_speaker
+            .iter_mut()
+            .find(|(id, _)| *id == record.speaker_id)
+        {
+            Some((_, records)) => records.push(record),
+            None => by_speaker.push((&record.speaker_id, vec![record])),
         }
-        let embedding_vec = embedder.embed(&pcm)?;
-        let (speaker_id, speaker_label) = if let Some((matched, _score)) = best_match(&embedding_vec, &known_embeddings) {
-            let label = matched
-                .speaker_label
-                .clone()
-                .unwrap_or_else(|| {
-                    let generated = format!("Speaker {}", next_label_index);
-                    next_label_index += 1;
-                    generated
-                });
-            if matched.speaker_label.is_none() {
-                db.rename_speaker(&matched.speaker_id, &label)?;
+    }
+
+    let mut changes = Vec::new();
+    for (from_speaker_id, records) in by_speaker {
+        let best = records
+            .iter()
+            .filter_map(|record| best_match(&record.vector, others, metric, threshold))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        let (speaker_id, label, score) = match (best, new_speaker_prefix) {
+            (Some((matched, score)), _) => (
+                matched.speaker_id.clone(),
+                matched.speaker_label.clone(),
+                Some(score),
+            ),
+            // Already a speaker of its own; nothing to split off.
+            (None, Some(prefix)) if others.iter().any(|o| o.speaker_id == from_speaker_id) => {
+                let label = db.next_speaker_label(prefix)?;
+                (db.insert_speaker(Some(&label))?, Some(label), None)
             }
-            (matched.speaker_id.clone(), label)
-        } else {
-            let label = format!("Speaker {}", next_label_index);
-            next_label_index += 1;
-            let id = db.insert_speaker(Some(&label))?;
-            (id, label)
+            (None, _) => continue,
         };
-
-        let embedding_id = db.insert_embedding(&speaker_id, session_id, &embedding_vec)?;
-        known_embeddings.push(StoredEmbedding {
-            id: embedding_id,
-            speaker_id: speaker_id.clone(),
-            speaker_label: Some(speaker_label.clone()),
-            vector: embedding_vec,
-            source_session_id: session_id.to_string(),
-            created_at: Utc::now(),
+        if speaker_id == from_speaker_id {
+            continue;
+        }
+        // The first move takes the speaker's segments along; the rest move embeddings.
+        let mut segments_updated = 0;
+        for record in &records {
+            segments_updated += db.reassign_session_speaker(
+                session_id,
+                &record.id,
+                from_speaker_id,
+                &speaker_id,
+                label.as_deref(),
+            )?;
+        }
+        changes.push(RematchChange {
+            embedding_ids: records.iter().map(|record| record.id.clone()).collect(),
+            from_label: records[0].speaker_label.clone(),
+            to_label: label,
+            score,
+            segments_updated,
         });
-        diarization_to_profile.insert(speaker_key, (speaker_id, speaker_label));
     }
+    Ok(changes)
+}
 
-    for seg in segments {
-        let (speaker_id, speaker_label) = diarization_to_profile
-            .get(&seg.speaker)
-            .cloned()
-            .unwrap_or_else(|| (String::new(), seg.speaker.clone()));
-        let speaker_id_opt = if speaker_id.is_empty() {
-            None
-        } else {
-            Some(speaker_id.as_str())
-        };
-        db.insert_segment(
-            session_id,
-            seg.start_ms as i64,
-            seg.end_ms as i64,
-            speaker_id_opt,
-            Some(&speaker_label),
-            &seg.text,
-        )
-        .map_err(|e| format!("DB error: {e}"))?;
-    }
+/// Re-derives each of a session's speakers from its stored embeddings, matched at the
+/// configured threshold against every other session's voiceprints, and moves all of the
+/// session's segments under that speaker along (see [`rematch_speakers`]). Nothing is
+/// re-embedded. Speakers that match nobody are kept, and labels left stale by renames
+/// are refreshed. Returns how many segments changed.
+#[tauri::command]
+fn remap_session_profiles(session_id: String, app_state: State<AppState>) -> Result<usize, String> {
+    app_state.logs.run("remap_session_profiles", || {
+        let metric = app_state
+            .config
+            .lock()
+            .map_err(|_| "config lock")?
+            .similarity_metric();
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        if db.get_session(&session_id)?.is_none() {
+            return Err(format!("Session not found: {session_id}"));
+        }
+        let (own, others) = partition_embeddings(db, &session_id)?;
+        let threshold = match_threshold(metric);
+        let changes = db.in_savepoint(|db| {
+            rematch_speakers(db, &session_id, &own, &others, metric, threshold, None)
+        })?;
+        let moved: usize = changes.iter().map(|change| change.segments_updated).sum();
+        Ok(moved + db.resync_session_segment_labels(&session_id)?)
+    })
+}
+
+/// Histograms of pairwise similarity between stored embeddings, split into same-speaker
+/// and different-speaker pairs, for choosing a match threshold that separates the two.
+#[tauri::command]
+fn similarity_histogram(
+    bins: Option<usize>,
+    app_state: State<AppState>,
+) -> Result<SimilarityHistogram, String> {
+    app_state.logs.run("similarity_histogram", || {
+        let bins = bins.unwrap_or(DEFAULT_HISTOGRAM_BINS).clamp(2, 200);
+        let metric = app_state
+            .config
+            .lock()
+            .map_err(|_| "config lock")?
+            .similarity_metric();
+        let embeddings = app_state.with_readonly_db(|db| db.list_embeddings())?;
+        let total = embeddings.len();
+        let step = total.div_ceil(MAX_HISTOGRAM_EMBEDDINGS).max(1);
+        let sample: Vec<&StoredEmbedding> = embeddings.iter().step_by(step).collect();
+
+        let bin_of = |score: f32| (((score + 1.0) / 2.0 * bins as f32) as usize).min(bins - 1);
+        let mut same_speaker = vec![0u64; bins];
+        let mut different_speaker = vec![0u64; bins];
+        for (i, a) in sample.iter().enumerate() {
+            for b in &sample[i + 1..] {
+                let bin = bin_of(metric.similarity(&a.vector, &b.vector).clamp(-1.0, 1.0));
+                if a.speaker_id == b.speaker_id {
+                    same_speaker[bin] += 1;
+                } else {
+                    different_speaker[bin] += 1;
+                }
+            }
+        }
+        Ok(SimilarityHistogram {
+            metric,
+            threshold: match_threshold(metric),
+            bins,
+            same_speaker,
+            different_speaker,
+            embeddings_total: total,
+            embeddings_used: sample.len(),
+        })
+    })
+}
+
+/// Speakers with their voiceprint counts; `with_cohesion` also scores how consistent
+/// each speaker's voiceprints are, which decrypts every embedding.
+#[tauri::command]
+fn list_speakers(
+    with_cohesion: Option<bool>,
+    app_state: State<AppState>,
+) -> Result<Vec<Speaker>, String> {
+    app_state.logs.run("list_speakers", || {
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        db.list_speakers(with_cohesion.unwrap_or(false))
+    })
+}
+
+#[tauri::command]
+fn get_speaker(speaker_id: String, app_state: State<AppState>) -> Result<Speaker, String> {
+    app_state.logs.run("get_speaker", || {
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        db.get_speaker(&speaker_id)
+    })
+}
+
+/// Who spoke in a session, in order of first appearance.
+#[tauri::command]
+fn session_speakers(
+    session_id: String,
+    app_state: State<AppState>,
+) -> Result<Vec<db::SessionSpeaker>, String> {
+    app_state.logs.run("session_speakers", || {
+        app_state.with_readonly_db(|db| db.session_speakers(&session_id))
+    })
+}
+
+/// The speaker who talked longest in a session, for titles like "Meeting with Alice";
+/// `None` when none of its speakers is known.
+#[tauri::command]
+fn primary_speaker(
+    session_id: String,
+    app_state: State<AppState>,
+) -> Result<Option<db::SessionSpeaker>, String> {
+    app_state.logs.run("primary_speaker", || {
+        app_state.with_readonly_db(|db| db.primary_speaker(&session_id))
+    })
+}
 
-    Ok(())
+#[tauri::command]
+fn global_speaker_stats(app_state: State<AppState>) -> Result<Vec<GlobalSpeakerStats>, String> {
+    app_state.logs.run("global_speaker_stats", || {
+        app_state.with_readonly_db(|db| db.global_speaker_stats())
+    })
 }
 
 #[tauri::command]
-fn unlock_db(password: String, app_state: State<AppState>) -> Result<(), String> {
-    let cfg = app_state.config.lock().map_err(|_| "config lock")?.clone();
-    if !cfg.encryption_enabled {
-        return Err("Encryption is not enabled".into());
-    }
-    let salt = Db::load_existing_salt(app_state.db_path()).unwrap_or(None);
-    let crypto = Crypto::new(Some(&password), salt);
-    app_state.open_db(crypto)
+fn rename_speaker(
+    speaker_id: String,
+    new_label: String,
+    app_state: State<AppState>,
+) -> Result<(), String> {
+    app_state.logs.run("rename_speaker", || {
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        db.rename_speaker(&speaker_id, &new_label)
+    })
 }
 
 #[tauri::command]
-fn enable_encryption(password: String, app_state: State<AppState>) -> Result<(), String> {
-    {
-        let mut cfg = app_state.config.lock().map_err(|_| "config lock")?;
-        cfg.encryption_enabled = true;
-        cfg.save(&app_state.config_path)?;
-    }
-    // Recreate DB encrypted (note: existing plaintext data not migrated).
-    {
-        let mut db_guard = app_state.db.lock().map_err(|_| "db lock")?;
-        *db_guard = None;
-    }
-    let db_path = app_state.data_dir.join("recall.db");
-    let _ = std::fs::remove_file(&db_path);
-    let crypto = Crypto::new(Some(&password), None);
-    app_state.open_db(crypto)
+fn suggest_speaker_name(
+    speaker_id: String,
+    app_state: State<AppState>,
+) -> Result<Vec<naming::NameSuggestion>, String> {
+    app_state.logs.run("suggest_speaker_name", || {
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        let texts = db.speaker_segment_texts(&speaker_id, NAME_SUGGESTION_SEGMENTS)?;
+        Ok(naming::suggest_names(&texts))
+    })
 }
 
 #[tauri::command]
-fn app_status(app_state: State<AppState>) -> Result<AppStatus, String> {
-    let cfg = app_state
-        .config
-        .lock()
-        .map_err(|_| "config lock")?
-        .clone();
-    let db_open = app_state.db.lock().map_err(|_| "DB lock poisoned")?.is_some();
-    Ok(AppStatus {
-        encryption_enabled: cfg.encryption_enabled,
-        db_open,
-        needs_password: cfg.encryption_enabled && !db_open,
-        api_base: cfg.api_base,
+fn resync_segment_labels(app_state: State<AppState>) -> Result<usize, String> {
+    app_state.logs.run("resync_segment_labels", || {
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        db.resync_segment_labels()
     })
 }
 
+/// Removes segments left without a session and unsets references to deleted speakers.
 #[tauri::command]
-fn list_sessions(app_state: State<AppState>) -> Result<Vec<Session>, String> {
+fn repair_orphans(app_state: State<AppState>) -> Result<db::OrphanRepair, String> {
     let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
     let db = db_guard.as_ref().ok_or("Database not initialized")?;
-    db.list_sessions()
+    let result = db.repair_orphans();
+    app_state.logs.check("repair_orphans", result)
 }
 
 #[tauri::command]
-fn list_segments(session_id: String, app_state: State<AppState>) -> Result<Vec<SegmentRecord>, String> {
-    let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
-    let db = db_guard.as_ref().ok_or("Database not initialized")?;
-    db.list_segments(&session_id)
+fn export_speakers(
+    dest: String,
+    password: String,
+    app_state: State<AppState>,
+) -> Result<usize, String> {
+    app_state.logs.run("export_speakers", || {
+        let (speakers, embeddings) = app_state
+            .with_readonly_db(|db| Ok((db.list_speakers(false)?, db.list_embeddings()?)))?;
+        let entries: Vec<SpeakerDirectoryEntry> = speakers
+            .into_iter()
+            .map(|speaker| SpeakerDirectoryEntry {
+                embeddings: embeddings
+                    .iter()
+                    .filter(|e| e.speaker_id == speaker.id)
+                    .map(|e| e.vector.clone())
+                    .collect(),
+                label: speaker.label,
+            })
+            .filter(|entry| !entry.embeddings.is_empty())
+            .collect();
+
+        let payload = serde_json::to_vec(&entries).map_err(|e| e.to_string())?;
+        let crypto = Crypto::new(Some(&password), None);
+        let salt = crypto.salt().ok_or("Failed to derive export key")?;
+        let (nonce, ciphertext) = crypto.encrypt(&payload);
+        let file = SpeakerDirectoryFile {
+            version: SPEAKER_DIRECTORY_VERSION,
+            salt,
+            nonce,
+            ciphertext,
+        };
+        let content = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
+        std::fs::write(&dest, content).map_err(|e| format!("Failed to write {dest}: {e}"))?;
+        Ok(entries.len())
+    })
+}
+
+/// Encodes `rows` as a 2-D little-endian float32 array in NumPy's `.npy` format (1.0).
+fn npy_bytes(rows: &[&[f32]], dim: usize) -> Vec<u8> {
+    let mut header = format!(
+        "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}), }}",
+        rows.len(),
+        dim
+    );
+    // Magic, version and header length take 10 bytes; padding keeps the data 64-byte aligned.
+    let padded = (10 + header.len() + 1).div_ceil(64) * 64 - 10;
+    header.push_str(&" ".repeat(padded - header.len() - 1));
+    header.push('\n');
+    let mut out = Vec::with_capacity(10 + header.len() + rows.len() * dim * 4);
+    out.extend_from_slice(b"\x93NUMPY\x01\x00");
+    out.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    out.extend_from_slice(header.as_bytes());
+    for row in rows {
+        for value in *row {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    out
 }
 
+/// Writes each speaker's voiceprints to `<speaker_id>.npy` in `dest_dir`, one row per
+/// embedding, plus a `manifest.json` mapping ids to labels, for analysis in Python.
+/// Unlike `export_speakers` nothing is encrypted, so this needs the DB unlocked.
+/// Returns the number of speakers written.
 #[tauri::command]
-fn update_transcript(
+fn export_embeddings_npy(dest_dir: String, app_state: State<AppState>) -> Result<usize, String> {
+    app_state.logs.run("export_embeddings_npy", || {
+        if app_state
+            .db
+            .lock()
+            .map_err(|_| "DB lock poisoned")?
+            .is_none()
+        {
+            return Err("Database is locked; unlock it first".into());
+        }
+        let (speakers, embeddings) = app_state
+            .with_readonly_db(|db| Ok((db.list_speakers(false)?, db.list_embeddings()?)))?;
+        let dir = PathBuf::from(&dest_dir);
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {dest_dir}: {e}"))?;
+
+        let mut manifest = Vec::new();
+        for speaker in speakers {
+            let rows: Vec<&[f32]> = embeddings
+                .iter()
+                .filter(|e| e.speaker_id == speaker.id)
+                .map(|e| e.vector.as_slice())
+                .collect();
+            let Some(dim) = rows.first().map(|row| row.len()) else {
+                continue;
+            };
+            if rows.iter().any(|row| row.len() != dim) {
+                return Err(format!(
+                    "Speaker {} has embeddings of different sizes",
+                    speaker.id
+                ));
+            }
+            let file = format!("{}.npy", speaker.id);
+            std::fs::write(dir.join(&file), npy_bytes(&rows, dim))
+                .map_err(|e| format!("Failed to write {file}: {e}"))?;
+            manifest.push(NpyManifestEntry {
+                speaker_id: speaker.id,
+                label: speaker.label,
+                file,
+                count: rows.len(),
+                dim,
+            });
+        }
+        let content = serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?;
+        std::fs::write(dir.join("manifest.json"), content)
+            .map_err(|e| format!("Failed to write manifest.json: {e}"))?;
+        Ok(manifest.len())
+    })
+}
+
+/// Merges an exported speaker directory: speakers whose voiceprint matches an existing
+/// profile get the imported embeddings appended, others are created as new speakers.
+#[tauri::command]
+fn import_speakers(
+    src: String,
+    password: String,
+    app_state: State<AppState>,
+) -> Result<SpeakerImportSummary, String> {
+    app_state.logs.run("import_speakers", || {
+        let content =
+            std::fs::read_to_string(&src).map_err(|e| format!("Failed to read {src}: {e}"))?;
+        let file: SpeakerDirectoryFile = serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid speaker directory: {e}"))?;
+        if file.version != SPEAKER_DIRECTORY_VERSION {
+            return Err(format!(
+                "Unsupported speaker directory version {}",
+                file.version
+            ));
+        }
+        let crypto = Crypto::new(Some(&password), Some(file.salt));
+        let payload = crypto
+            .decrypt(&file.nonce, &file.ciphertext)
+            .map_err(|_| "Wrong password or corrupted speaker directory".to_string())?;
+        let entries: Vec<SpeakerDirectoryEntry> = serde_json::from_slice(&payload)
+            .map_err(|e| format!("Invalid speaker directory: {e}"))?;
+
+        let metric = app_state
+            .config
+            .lock()
+            .map_err(|_| "config lock")?
+            .similarity_metric();
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        db.in_savepoint(|db| import_speaker_entries(db, entries, metric))
+    })
+}
+
+/// Stores imported speakers, matching each against the library as it grows, so an
+/// entry repeated in the same file joins the speaker created for the first one.
+fn import_speaker_entries(
+    db: &Db,
+    entries: Vec<SpeakerDirectoryEntry>,
+    metric: SimilarityMetric,
+) -> Result<SpeakerImportSummary, String> {
+    let mut known = db.list_embeddings()?;
+    let mut summary = SpeakerImportSummary {
+        merged: 0,
+        created: 0,
+        embeddings: 0,
+    };
+    for entry in entries {
+        let Some(centroid) = centroid(&entry.embeddings) else {
+            continue;
+        };
+        let (speaker_id, speaker_label) =
+            match best_match(&centroid, &known, metric, match_threshold(metric)) {
+                Some((matched, _score)) => {
+                    summary.merged += 1;
+                    (matched.speaker_id.clone(), matched.speaker_label.clone())
+                }
+                None => {
+                    summary.created += 1;
+                    let id = db.insert_speaker(entry.label.as_deref())?;
+                    (id, entry.label.clone())
+                }
+            };
+        for vector in entry.embeddings {
+            let id = db.insert_imported_embedding(&speaker_id, &vector)?;
+            summary.embeddings += 1;
+            known.push(StoredEmbedding {
+                id,
+                speaker_id: speaker_id.clone(),
+                speaker_label: speaker_label.clone(),
+                vector,
+                source_session_id: None,
+                created_at: Utc::now(),
+            });
+        }
+    }
+    Ok(summary)
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct SessionFingerprint {
+    fingerprint: String,
+    /// Other sessions with a close fingerprint, most likely the same meeting imported
+    /// from another device.
+    duplicates: Vec<String>,
+}
+
+/// Recomputes and stores a session's content fingerprint (see [`Db::session_fingerprint`])
+/// and lists other sessions whose fingerprint is close to it. New sessions are fingerprinted when
+/// transcribed; edited ones get an updated fingerprint the next time this runs.
+#[tauri::command]
+fn session_fingerprint(
     session_id: String,
-    transcript: String,
     app_state: State<AppState>,
-) -> Result<(), String> {
-    let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
-    let db = db_guard.as_ref().ok_or("Database not initialized")?;
-    db.update_session_transcript(&session_id, &transcript)
+) -> Result<SessionFingerprint, String> {
+    app_state.logs.run("session_fingerprint", || {
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        let fingerprint = db
+            .session_fingerprint(&session_id)?
+            .ok_or_else(|| format!("Session not found: {session_id}"))?;
+        let duplicates = db.sessions_with_fingerprint(&fingerprint, &session_id)?;
+        Ok(SessionFingerprint {
+            fingerprint,
+            duplicates,
+        })
+    })
 }
 
+/// Voices held back from becoming new speakers by `ambiguous_match_threshold`, oldest
+/// first, each with the known speaker it came closest to.
 #[tauri::command]
-fn delete_session(session_id: String, app_state: State<AppState>) -> Result<(), String> {
-    let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
-    let db = db_guard.as_ref().ok_or("Database not initialized")?;
-    db.delete_session(&session_id)
+fn pending_speaker_reviews(app_state: State<AppState>) -> Result<Vec<db::SpeakerReview>, String> {
+    app_state.logs.run("pending_speaker_reviews", || {
+        app_state.with_readonly_db(|db| db.pending_speaker_reviews())
+    })
 }
 
+/// Settles a review: `merge` adds the voice to the suggested speaker, otherwise it becomes
+/// a new speaker. Either way its voiceprint is stored and the session's segments with its
+/// label are assigned. Returns the speaker id.
 #[tauri::command]
-fn list_speakers(app_state: State<AppState>) -> Result<Vec<Speaker>, String> {
-    let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
-    let db = db_guard.as_ref().ok_or("Database not initialized")?;
-    db.list_speakers()
+fn resolve_speaker_review(
+    review_id: String,
+    merge: bool,
+    app_state: State<AppState>,
+) -> Result<String, String> {
+    app_state.logs.run("resolve_speaker_review", || {
+        let prefix = app_state
+            .config
+            .lock()
+            .map_err(|_| "config lock")?
+            .speaker_label_prefix()
+            .to_string();
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        let (review, vector) = db
+            .speaker_review(&review_id)?
+            .ok_or_else(|| format!("Speaker review not found: {review_id}"))?;
+        // The speaker, voiceprint, segments and review change together or not at all.
+        db.in_savepoint(|db| {
+            let (speaker_id, label) = if merge {
+                let speaker_id = review
+                    .candidate_speaker_id
+                    .ok_or("The suggested speaker was deleted; create a new speaker instead")?;
+                let label = match review.candidate_label {
+                    Some(label) => label,
+                    None => {
+                        let label = db.next_speaker_label(&prefix)?;
+                        db.rename_speaker(&speaker_id, &label)?;
+                        label
+                    }
+                };
+                (speaker_id, label)
+            } else {
+                let label = db.next_speaker_label(&prefix)?;
+                let speaker_id = db.insert_speaker(Some(&label))?;
+                (speaker_id, label)
+            };
+            db.insert_embedding(&speaker_id, &review.session_id, &vector)?;
+            db.assign_unmatched_segments(
+                &review.session_id,
+                &review.speaker_label,
+                &speaker_id,
+                &label,
+            )?;
+            db.delete_speaker_review(&review_id)?;
+            Ok(speaker_id)
+        })
+    })
 }
 
+/// A speaker's voiceprints (id, source session, time) without the vectors.
 #[tauri::command]
-fn rename_speaker(
+fn list_speaker_embedding_ids(
     speaker_id: String,
-    new_label: String,
     app_state: State<AppState>,
-) -> Result<(), String> {
-    let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
-    let db = db_guard.as_ref().ok_or("Database not initialized")?;
-    db.rename_speaker(&speaker_id, &new_label)
+) -> Result<Vec<db::EmbeddingRef>, String> {
+    app_state.logs.run("list_speaker_embedding_ids", || {
+        app_state.with_readonly_db(|db| db.list_speaker_embedding_ids(&speaker_id))
+    })
+}
+
+/// Removes a single voiceprint, e.g. one recorded on a noisy mic, keeping the speaker.
+/// Matching reads voiceprints fresh on every run, so the next transcription no longer
+/// uses it.
+#[tauri::command]
+fn delete_embedding(embedding_id: String, app_state: State<AppState>) -> Result<(), String> {
+    app_state.logs.run("delete_embedding", || {
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        if !db.delete_embedding(&embedding_id)? {
+            return Err(format!("Embedding not found: {embedding_id}"));
+        }
+        Ok(())
+    })
 }
 
 #[tauri::command]
 fn delete_speaker(speaker_id: String, app_state: State<AppState>) -> Result<(), String> {
-    let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
-    let db = db_guard.as_ref().ok_or("Database not initialized")?;
-    db.delete_speaker(&speaker_id)
+    app_state.logs.run("delete_speaker", || {
+        let db_guard = app_state.db.lock().map_err(|_| "DB lock poisoned")?;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        db.delete_speaker(&speaker_id)
+    })
+}
+
+/// Keeps an in-progress recording from being lost when the app quits (tray quit or
+/// last window closed): the WAV is finalized and, if configured, transcribed first.
+fn finalize_recording_on_exit(app: &tauri::AppHandle) {
+    // Managed during setup, before anything can start a recording.
+    let Some(app_state) = app.try_state::<AppState>() else {
+        return;
+    };
+    let path = match app.state::<RecordingManager>().stop_if_recording() {
+        Ok(Some(path)) => path,
+        Ok(None) => return,
+        Err(e) => {
+            app_state
+                .logs
+                .error("exit", format!("failed to finalize recording: {e}"));
+            return;
+        }
+    };
+    let transcribe = app_state
+        .config
+        .lock()
+        .map(|cfg| cfg.transcribe_on_quit)
+        .unwrap_or(false);
+    if transcribe {
+        let path = path.to_string_lossy();
+        let perf = Perf::disabled();
+        if let Err(e) = transcribe_path(
+            &app_state,
+            &path,
+            None,
+            None,
+            TranscribeOptions::default(),
+            &TranscribeReport {
+                on_progress: &|_| {},
+                on_warning: &|_| {},
+            },
+            &perf,
+        ) {
+            app_state
+                .logs
+                .error("exit", format!("transcription failed: {e}"));
+        }
+    }
 }
 
 fn build_tray(app: &mut tauri::App) -> tauri::Result<()> {
@@ -696,7 +5151,7 @@ fn build_tray(app: &mut tauri::App) -> tauri::Result<()> {
             "stop" => {
                 let _ = app.emit("recording:stop", ());
             }
-            "quit" => std::process::exit(0),
+            "quit" => app.exit(0),
             _ => {}
         })
         .build(app)?;
@@ -706,40 +5161,883 @@ fn build_tray(app: &mut tauri::App) -> tauri::Result<()> {
 
 fn main() {
     tauri::Builder::default()
-        .invoke_handler(tauri::generate_handler![
+        .invoke_handler(perf::timed(tauri::generate_handler![
             start_recording,
+            start_recording_for_session,
             stop_recording,
+            cancel_recording,
+            recording_status,
+            clipping_report,
+            list_input_devices,
+            set_input_device,
+            set_user_agent,
+            audio_device_info,
             transcribe_file,
+            validate_audio,
+            transcribe_url,
+            transcribe_debug,
+            diarize_local,
+            detect_meeting_boundaries,
+            trim_silence,
+            merge_transcript_chunks,
+            model_info,
+            reembed_all,
+            verify_model,
+            download_model,
+            benchmark_embedder,
+            available_execution_providers,
+            retry_embeddings,
             unlock_db,
+            reinitialize_db,
             enable_encryption,
+            disable_encryption,
+            rotate_embedding_key,
+            test_encryption,
+            session_encryption_info,
             app_status,
+            get_recent_logs,
+            diagnostics,
+            get_config,
+            update_config,
+            storage_stats,
             list_sessions,
+            search_sessions,
+            search_segments,
+            waveform_peaks,
+            session_thumbnail,
+            session_fingerprint,
+            timeline,
             list_segments,
+            segment_at,
+            export_session_json,
+            export_session_markdown,
+            export_labels,
+            export_all,
             update_transcript,
+            transcript_versions,
+            update_segment_text,
+            set_segment_excluded,
+            reprocess_session_embeddings,
+            rebuild_transcript,
+            split_session_at,
+            detect_overlaps,
             delete_session,
+            delete_sessions,
+            undo_last_transcription,
+            preview_retention,
+            export_backup,
             list_speakers,
+            get_speaker,
+            session_speakers,
+            primary_speaker,
+            global_speaker_stats,
             rename_speaker,
+            suggest_speaker_name,
+            resync_segment_labels,
+            remap_session_profiles,
+            repair_orphans,
+            rematch_session,
+            similarity_histogram,
+            export_speakers,
+            export_embeddings_npy,
+            import_speakers,
+            list_speaker_embedding_ids,
+            pending_speaker_reviews,
+            resolve_speaker_review,
+            schedule_recording,
+            list_scheduled_recordings,
+            cancel_scheduled_recording,
+            delete_embedding,
             delete_speaker
-        ])
+        ]))
         .manage(RecordingManager::default())
+        .manage(RecordingScheduler::default())
+        // Tell a freshly loaded UI right away that the encrypted DB is waiting for a password.
+        .on_page_load(|webview, payload| {
+            if payload.event() != PageLoadEvent::Finished {
+                return;
+            }
+            let Some(app_state) = webview.try_state::<AppState>() else {
+                return;
+            };
+            if let Ok(status) = current_status(&app_state) {
+                if status.needs_password {
+                    let _ = webview.emit("db:needs-password", status);
+                }
+            }
+        })
         .setup(|app| {
             let data_dir = app
                 .path()
                 .app_data_dir()
                 .unwrap_or_else(|_| std::env::temp_dir().join("recall"));
             std::fs::create_dir_all(&data_dir).ok();
-            let app_state = AppState::new(data_dir);
-            {
-                let cfg = app_state.config.lock().unwrap().clone();
-                if !cfg.encryption_enabled {
-                    let _ = app_state.open_db(Crypto::new(None, None));
-                }
+            let (transcriber, transcriber_error) = select_transcriber();
+            let app_state = AppState::new(data_dir, transcriber);
+            if let Some(e) = transcriber_error {
+                app_state.logs.error("transcriber", e);
+            }
+            if let Err(e) = app_state.check_onnx_runtime() {
+                app_state.logs.error("onnx_runtime", e);
             }
+            let db_opened = {
+                let cfg = app_state.config.lock().unwrap().clone();
+                !cfg.encryption_enabled && app_state.open_db(Crypto::new(None, None)).is_ok()
+            };
             app.manage(app_state);
+            if db_opened {
+                spawn_embedding_check(app.handle().clone());
+            }
+            spawn_retention_task(app.handle().clone());
+            spawn_backup_task(app.handle().clone());
+            spawn_schedule_task(app.handle().clone());
 
             build_tray(app)?;
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                finalize_recording_on_exit(app);
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory_db() -> Db {
+        Db::open(":memory:", Crypto::new(None, None)).unwrap()
+    }
+
+    #[test]
+    fn rematch_moves_a_speakers_buckets_together() {
+        let db = memory_db();
+        let (other, _) = db.insert_session("", None).unwrap();
+        let (session, _) = db.insert_session("", None).unwrap();
+        let target = db.insert_speaker(Some("Ann")).unwrap();
+        let shared = db.insert_speaker(Some("Speaker 1")).unwrap();
+        // Two diarization buckets of the session, both under the shared speaker and both
+        // closer to Ann.
+        let embeddings = [
+            (&target, &other, [1.0, 0.0, 0.0]),
+            (&shared, &other, [0.0, 1.0, 0.0]),
+            (&shared, &session, [1.0, 0.1, 0.0]),
+            (&shared, &session, [1.0, 0.0, 0.1]),
+        ];
+        for (speaker, source, vector) in embeddings {
+            db.insert_embedding(speaker, source, &vector).unwrap();
+        }
+        for start in [0, 1000, 2000] {
+            let label = Some("Speaker 1");
+            db.insert_segment(&session, start, start + 500, Some(&shared), label, "")
+                .unwrap();
+        }
+
+        let (own, others) = partition_embeddings(&db, &session).unwrap();
+        let metric = SimilarityMetric::Cosine;
+        let changes =
+            rematch_speakers(&db, &session, &own, &others, metric, 0.9, Some("Speaker")).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].embedding_ids.len(), 2);
+        assert_eq!(changes[0].segments_updated, 3);
+        assert_eq!(changes[0].to_label.as_deref(), Some("Ann"));
+        for segment in db.list_segments(&session).unwrap() {
+            assert_eq!(segment.speaker_id.as_ref(), Some(&target));
+        }
+    }
+
+    #[test]
+    fn remap_keeps_speakers_that_match_nobody() {
+        let db = memory_db();
+        let (other, _) = db.insert_session("", None).unwrap();
+        let (session, _) = db.insert_session("", None).unwrap();
+        let shared = db.insert_speaker(Some("Speaker 1")).unwrap();
+        db.insert_embedding(&shared, &other, &[0.0, 1.0]).unwrap();
+        db.insert_embedding(&shared, &session, &[1.0, 0.0]).unwrap();
+
+        let (own, others) = partition_embeddings(&db, &session).unwrap();
+        let metric = SimilarityMetric::Cosine;
+        let changes = rematch_speakers(&db, &session, &own, &others, metric, 0.9, None).unwrap();
+        assert!(changes.is_empty());
+        assert_eq!(db.list_speakers(false).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn undo_removes_only_speakers_the_session_introduced() {
+        let db = memory_db();
+        let (earlier, _) = db.insert_session("", None).unwrap();
+        let (session, _) = db.insert_session("", None).unwrap();
+        let known = db.insert_speaker(Some("Ann")).unwrap();
+        db.insert_embedding(&known, &earlier, &[1.0, 0.0]).unwrap();
+        db.insert_embedding(&known, &session, &[1.0, 0.1]).unwrap();
+        let added = db.insert_speaker(Some("Speaker 1")).unwrap();
+        db.insert_embedding(&added, &session, &[0.0, 1.0]).unwrap();
+        // Created after the session but unrelated to it, e.g. by an import.
+        let unrelated = db.insert_speaker(Some("Bob")).unwrap();
+
+        let summary = db.undo_session(&session).unwrap();
+        assert_eq!(summary.embeddings_removed, 2);
+        assert_eq!(summary.speakers_removed, 1);
+        let ids: Vec<String> = db
+            .list_speakers(false)
+            .unwrap()
+            .into_iter()
+            .map(|speaker| speaker.id)
+            .collect();
+        assert_eq!(ids.len(), 2);
+        assert!(ids.contains(&known) && ids.contains(&unrelated));
+    }
+
+    #[test]
+    fn import_joins_repeated_speakers_in_one_file() {
+        let db = memory_db();
+        let entry = |label: &str, vector: [f32; 2]| SpeakerDirectoryEntry {
+            label: Some(label.into()),
+            embeddings: vec![vector.to_vec()],
+        };
+        let entries = vec![
+            entry("Ann", [1.0, 0.0]),
+            entry("Ann", [1.0, 0.05]),
+            entry("Bob", [0.0, 1.0]),
+        ];
+
+        let summary = import_speaker_entries(&db, entries, SimilarityMetric::Cosine).unwrap();
+        assert_eq!(summary.created, 2);
+        assert_eq!(summary.merged, 1);
+        assert_eq!(summary.embeddings, 3);
+        assert_eq!(db.list_speakers(false).unwrap().len(), 2);
+        let embeddings = db.list_embeddings().unwrap();
+        assert!(embeddings.iter().all(|e| e.source_session_id.is_none()));
+    }
+
+    /// Writes `ms` of a quiet 16 kHz mono tone to `path`.
+    fn write_test_wav(path: &std::path::Path, ms: u32) {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 16_000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        for i in 0..16 * ms {
+            let t = i as f32 / 16_000.0;
+            let sample = (t * 440.0 * std::f32::consts::TAU).sin() * 3000.0;
+            writer.write_sample(sample as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    fn record_float_chunk(
+        path: &std::path::Path,
+        bit_depth: u16,
+        samples: &[f32],
+    ) -> hound::WavReader<std::io::BufReader<std::fs::File>> {
+        let spec = recording_wav_spec(SampleFormat::F32, 1, 16_000, bit_depth).unwrap();
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        write_chunk(&mut writer, SampleChunk::F32(samples.to_vec())).unwrap();
+        writer.finalize().unwrap();
+        hound::WavReader::open(path).unwrap()
+    }
+
+    #[test]
+    fn sixteen_bit_recordings_keep_the_waveform_amplitude() {
+        let sine: Vec<f32> = (0..1600)
+            .map(|i| 0.5 * (i as f32 / 16_000.0 * 440.0 * std::f32::consts::TAU).sin())
+            .collect();
+        let overdriven = [1.5, -1.5, 1.0, -1.0];
+        let input: Vec<f32> = sine.iter().chain(&overdriven).copied().collect();
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut reader = record_float_chunk(&dir.path().join("16.wav"), 16, &input);
+        let spec = reader.spec();
+        assert_eq!(spec.bits_per_sample, 16);
+        assert_eq!(spec.sample_format, hound::SampleFormat::Int);
+        let read: Vec<i16> = reader.samples::<i16>().map(Result::unwrap).collect();
+        assert_eq!(read.len(), input.len());
+        for (sample, expected) in read.iter().zip(&sine) {
+            let amplitude = *sample as f32 / i16::MAX as f32;
+            assert!((amplitude - expected).abs() <= 1.0 / i16::MAX as f32);
+        }
+        let peak = read[..sine.len()]
+            .iter()
+            .map(|s| s.unsigned_abs())
+            .max()
+            .unwrap();
+        assert_eq!(peak, (0.5 * i16::MAX as f32).round() as u16);
+        // Out-of-range input saturates rather than wrapping around.
+        assert_eq!(
+            read[sine.len()..],
+            [i16::MAX, -i16::MAX, i16::MAX, -i16::MAX]
+        );
+
+        let mut reader = record_float_chunk(&dir.path().join("32.wav"), 32, &input);
+        assert_eq!(reader.spec().sample_format, hound::SampleFormat::Float);
+        let read: Vec<f32> = reader.samples::<f32>().map(Result::unwrap).collect();
+        assert_eq!(read, input);
+    }
+
+    fn write_stereo_wav(path: &std::path::Path, frames: &[(i16, i16)]) {
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 16_000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        for &(left, right) in frames {
+            writer.write_sample(left).unwrap();
+            writer.write_sample(right).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    #[test]
+    fn read_audio_clip_scales_every_supported_depth() {
+        let dir = tempfile::tempdir().unwrap();
+        let sine: Vec<f32> = (0..1600)
+            .map(|i| 0.5 * (i as f32 / 16_000.0 * 440.0 * std::f32::consts::TAU).sin())
+            .collect();
+        for (bits, format) in [
+            (8, hound::SampleFormat::Int),
+            (16, hound::SampleFormat::Int),
+            (24, hound::SampleFormat::Int),
+            (32, hound::SampleFormat::Int),
+            (32, hound::SampleFormat::Float),
+        ] {
+            let path = dir.path().join(format!("{bits}-{format:?}.wav"));
+            let spec = hound::WavSpec {
+                channels: 1,
+                sample_rate: 16_000,
+                bits_per_sample: bits,
+                sample_format: format,
+            };
+            let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+            let scale = audio::full_scale(&spec).unwrap();
+            for &s in &sine {
+                match (format, bits) {
+                    (hound::SampleFormat::Float, _) => writer.write_sample(s).unwrap(),
+                    (_, 8) => writer.write_sample((s * scale).round() as i8).unwrap(),
+                    _ => writer.write_sample((s * scale).round() as i32).unwrap(),
+                }
+            }
+            writer.finalize().unwrap();
+
+            let clip = read_audio_clip(path.to_str().unwrap(), 0.0).unwrap();
+            assert_eq!(clip.sample_rate, 16_000);
+            assert_eq!(clip.samples.len(), sine.len());
+            let step = 1.0 / scale;
+            for (read, expected) in clip.samples.iter().zip(&sine) {
+                assert!(
+                    (read - expected).abs() <= step,
+                    "{bits}-bit {format:?}: {read} vs {expected}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn read_audio_clip_centres_unsigned_8_bit_samples() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("8-bit.wav");
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 8_000,
+            bits_per_sample: 8,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for s in [0i8, i8::MAX, i8::MIN] {
+            writer.write_sample(s).unwrap();
+        }
+        writer.finalize().unwrap();
+        // 8-bit PCM is unsigned on disk, with silence at 128.
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(bytes[bytes.len() - 3..], [128, 255, 0]);
+
+        let clip = read_audio_clip(path.to_str().unwrap(), 0.0).unwrap();
+        assert_eq!(clip.samples, [0.0, 127.0 / 128.0, -1.0]);
+    }
+
+    #[test]
+    fn read_audio_clip_rejects_unexpected_bit_depths() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("64-bit-float.wav");
+        // hound can't write 64-bit float, so the header is built by hand.
+        let data = [0u8; 16];
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&3u16.to_le_bytes()); // IEEE float
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&16_000u32.to_le_bytes());
+        wav.extend_from_slice(&(16_000u32 * 8).to_le_bytes());
+        wav.extend_from_slice(&8u16.to_le_bytes());
+        wav.extend_from_slice(&64u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        wav.extend_from_slice(&data);
+        std::fs::write(&path, wav).unwrap();
+        let error = read_audio_clip(path.to_str().unwrap(), 0.0).unwrap_err();
+        assert!(error.starts_with("Failed to open audio"), "{error}");
+    }
+
+    #[test]
+    fn open_mono_keeps_the_louder_of_phase_inverted_channels() {
+        let dir = tempfile::tempdir().unwrap();
+        let wav = dir.path().join("inverted.wav");
+        // Longer than the probe, so samples after it come from the stream itself.
+        let len = 16_000 * (DOWNMIX_PROBE_SECS + 2);
+        let frames: Vec<(i16, i16)> = (0..len)
+            .map(|i| {
+                let s = ((i as f32 * 0.05).sin() * 10_000.0) as i16;
+                (-s / 2, s)
+            })
+            .collect();
+        write_stereo_wav(&wav, &frames);
+        let flac = dir.path().join("inverted.flac");
+        flac::encode_wav(&wav, &flac).unwrap();
+
+        for path in [&wav, &flac] {
+            let (_, mono) = open_mono(path.to_str().unwrap(), 6.0).unwrap();
+            let mono = mono.collect::<Result<Vec<f32>, String>>().unwrap();
+            let expected: Vec<f32> = frames.iter().map(|&(_, r)| r as f32 / 32768.0).collect();
+            assert_eq!(mono, expected);
+        }
+    }
+
+    #[test]
+    fn open_mono_averages_channels_in_phase() {
+        let dir = tempfile::tempdir().unwrap();
+        let wav = dir.path().join("in-phase.wav");
+        let frames: Vec<(i16, i16)> = (0..16_000)
+            .map(|i| {
+                let s = ((i as f32 * 0.05).sin() * 10_000.0) as i16;
+                (s, s / 2)
+            })
+            .collect();
+        write_stereo_wav(&wav, &frames);
+
+        let (_, mono) = open_mono(wav.to_str().unwrap(), 6.0).unwrap();
+        let mono = mono.collect::<Result<Vec<f32>, String>>().unwrap();
+        let expected: Vec<f32> = frames
+            .iter()
+            .map(|&(l, r)| (l as f32 / 32768.0 + r as f32 / 32768.0) / 2.0)
+            .collect();
+        assert_eq!(mono, expected);
+    }
+
+    #[test]
+    fn read_speaker_audio_keeps_only_each_speakers_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let wav = dir.path().join("long.wav");
+        let minutes = 3;
+        write_test_wav(&wav, minutes * 60_000);
+        let flac = dir.path().join("long.flac");
+        flac::encode_wav(&wav, &flac).unwrap();
+        // Two speakers taking turns every 30 s for the whole recording.
+        let segments: Vec<ApiSegment> = (0..minutes as u64 * 2)
+            .map(|turn| ApiSegment {
+                speaker: format!("speaker_{}", turn % 2),
+                start_ms: turn * 30_000,
+                end_ms: (turn + 1) * 30_000,
+                text: String::new(),
+                exclude_from_embedding: false,
+            })
+            .collect();
+
+        let target = (16_000 * TARGET_SPEAKER_MS / 1000) as usize;
+        for path in [&wav, &flac] {
+            let audio = read_speaker_audio(path.to_str().unwrap(), &segments, 0, 6.0).unwrap();
+            assert_eq!(audio.by_speaker.len(), 2);
+            for samples in audio.by_speaker.values() {
+                assert_eq!(samples.len(), target);
+                assert!(samples.capacity() < 2 * target);
+            }
+        }
+    }
+
+    #[test]
+    fn fetch_model_part_gives_up_on_a_stalled_download() {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let url = Url::parse(&format!("http://{addr}/model.onnx")).unwrap();
+        // Sends the headers and part of the body, then goes quiet with the connection open.
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request);
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\n0123456789")
+                .unwrap();
+            thread::sleep(Duration::from_secs(3));
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let part = dir.path().join("model.onnx.part");
+        let cfg = AppConfig {
+            model_download_timeout_secs: Some(1),
+            ..AppConfig::default()
+        };
+        let client = http_client_with_timeout(&cfg, None).unwrap();
+
+        let started = std::time::Instant::now();
+        let err = fetch_model_part(&client, &url, &part, &cfg).unwrap_err();
+        assert!(err.contains("stalled"), "{err}");
+        assert!(started.elapsed() < Duration::from_secs(3));
+        assert_eq!(std::fs::read(&part).unwrap(), b"0123456789");
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn transcribing_without_onnx_runtime_warns() {
+        let dir = tempfile::tempdir().unwrap();
+        let wav = dir.path().join("no-runtime.wav");
+        write_test_wav(&wav, 1000);
+        let body = r#"{"transcript": "hi", "speakers": ["A"], "segments": [
+            {"speaker": "A", "start_ms": 0, "end_ms": 900, "text": "hi"}]}"#;
+        let transcriber = transcriber::CannedTranscriber::new(body);
+        let app_state = AppState::new(dir.path().to_path_buf(), Arc::new(transcriber));
+        *app_state.db.lock().unwrap() = Some(memory_db());
+        *app_state.embeddings_unavailable.lock().unwrap() = Some("no libonnxruntime".into());
+
+        let warnings = Mutex::new(Vec::new());
+        let options = TranscribeOptions {
+            keep_source: true,
+            skip_embeddings: false,
+        };
+        let transcript = transcribe_path(
+            &app_state,
+            wav.to_str().unwrap(),
+            Some("http://localhost".into()),
+            None,
+            options,
+            &TranscribeReport {
+                on_progress: &|_| {},
+                on_warning: &|warning| warnings.lock().unwrap().push(warning),
+            },
+            &Perf::disabled(),
+        )
+        .unwrap();
+        assert_eq!(transcript, "hi");
+        let warnings = warnings.into_inner().unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("no libonnxruntime"), "{}", warnings[0]);
+    }
+
+    #[test]
+    fn normalized_recording_is_appended_to_its_session() {
+        let dir = tempfile::tempdir().unwrap();
+        let wav = dir.path().join("recall-1.wav");
+        write_test_wav(&wav, 1000);
+        let body = r#"{"transcript": "second part", "speakers": ["A"], "segments": [
+            {"speaker": "A", "start_ms": 0, "end_ms": 900, "text": "second part"}]}"#;
+        let transcriber = transcriber::CannedTranscriber::new(body);
+        let app_state = AppState::new(dir.path().to_path_buf(), Arc::new(transcriber));
+        let db = memory_db();
+        let (session_id, _) = db.insert_session("first part", None).unwrap();
+        *app_state.db.lock().unwrap() = Some(db);
+
+        let cfg = AppConfig {
+            normalize_gain: true,
+            ..AppConfig::default()
+        };
+        let mut options = RecordingOptions::from_config(&cfg, app_state.logs.clone());
+        options.append_to = Some(session_id.clone());
+        let output = finish_recording(wav.clone(), &options, &app_state.append_targets).unwrap();
+        assert_ne!(output, wav);
+        assert!(!wav.exists());
+
+        transcribe_path(
+            &app_state,
+            output.to_str().unwrap(),
+            Some("http://localhost".into()),
+            None,
+            TranscribeOptions {
+                keep_source: true,
+                skip_embeddings: true,
+            },
+            &TranscribeReport {
+                on_progress: &|_| {},
+                on_warning: &|_| {},
+            },
+            &Perf::disabled(),
+        )
+        .unwrap();
+
+        let db_guard = app_state.db.lock().unwrap();
+        let sessions = db_guard.as_ref().unwrap().list_sessions().unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].id, session_id);
+        assert!(sessions[0].transcript.contains("first part"));
+        assert!(sessions[0].transcript.contains("second part"));
+        assert!(app_state.append_targets.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn failed_transcription_forgets_its_append_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let wav = dir.path().join("recall-2.wav");
+        write_test_wav(&wav, 1000);
+        let transcriber = transcriber::CannedTranscriber::new("not json");
+        let app_state = AppState::new(dir.path().to_path_buf(), Arc::new(transcriber));
+        *app_state.db.lock().unwrap() = Some(memory_db());
+        let options = RecordingOptions {
+            append_to: Some("session".into()),
+            ..RecordingOptions::default()
+        };
+        let output = finish_recording(wav, &options, &app_state.append_targets).unwrap();
+
+        let result = transcribe_path(
+            &app_state,
+            output.to_str().unwrap(),
+            Some("http://localhost".into()),
+            None,
+            TranscribeOptions {
+                keep_source: true,
+                skip_embeddings: true,
+            },
+            &TranscribeReport {
+                on_progress: &|_| {},
+                on_warning: &|_| {},
+            },
+            &Perf::disabled(),
+        );
+        assert!(result.is_err());
+        assert!(app_state.append_targets.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn cluster_centroids_follow_first_appearance() {
+        let embeddings = vec![
+            vec![0.0, 2.0],
+            vec![1.0, 0.0],
+            vec![0.0, 4.0],
+            vec![3.0, 0.0],
+        ];
+        let centroids = cluster_centroids(&embeddings, &[1, 0, 1, 0]);
+        assert_eq!(
+            centroids,
+            vec![
+                ("speaker_1".to_string(), vec![0.0, 3.0]),
+                ("speaker_0".to_string(), vec![2.0, 0.0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn forget_clears_only_the_matching_recording() {
+        let manager = RecordingManager::default();
+        let recorder = |path: &str| Recorder {
+            stop_tx: None,
+            handle: None,
+            path: PathBuf::from(path),
+            counters: Arc::new(RecordingCounters::default()),
+            sample_rate: 16_000,
+            discard: Arc::new(AtomicBool::new(false)),
+        };
+        *manager.current.lock().unwrap() = Some(recorder("current.wav"));
+        manager.forget(std::path::Path::new("older.wav"));
+        assert!(manager.status().unwrap().recording);
+
+        manager.forget(std::path::Path::new("current.wav"));
+        assert!(!manager.status().unwrap().recording);
+        assert_eq!(manager.stop().unwrap_err(), "No active recording");
+    }
+
+    /// Holds each transcription until the test lets it go, reporting when it starts.
+    struct GatedTranscriber {
+        body: String,
+        started: Mutex<std::sync::mpsc::Sender<()>>,
+        release: Mutex<std::sync::mpsc::Receiver<()>>,
+    }
+
+    impl transcriber::Transcriber for GatedTranscriber {
+        fn transcribe(
+            &self,
+            _cfg: &AppConfig,
+            _api_base: &str,
+            _path: &str,
+            _on_progress: &dyn Fn(f32),
+        ) -> Result<String, String> {
+            self.started.lock().unwrap().send(()).unwrap();
+            self.release.lock().unwrap().recv().unwrap();
+            Ok(self.body.clone())
+        }
+    }
+
+    #[test]
+    fn canned_response_is_normalized_and_stored() {
+        let dir = tempfile::tempdir().unwrap();
+        let wav = dir.path().join("canned.wav");
+        write_test_wav(&wav, 1500);
+        // Out of order, one without an end and one running past the audio.
+        let body = r#"{"transcript": "hi there bye", "speakers": ["A", "B"], "segments": [
+            {"speaker": "B", "start_ms": 700, "end_ms": 0, "text": "there"},
+            {"speaker": "A", "start_ms": 0, "end_ms": 600, "text": "hi"},
+            {"speaker": "A", "start_ms": 1200, "end_ms": 9000, "text": "bye"}]}"#;
+        let transcriber = transcriber::CannedTranscriber::new(body);
+        let app_state = AppState::new(dir.path().to_path_buf(), Arc::new(transcriber));
+        app_state.open_db(Crypto::new(None, None)).unwrap();
+
+        let options = TranscribeOptions {
+            keep_source: true,
+            skip_embeddings: true,
+        };
+        let transcript = transcribe_path(
+            &app_state,
+            wav.to_str().unwrap(),
+            Some("http://localhost".into()),
+            Some("canned-1"),
+            options,
+            &TranscribeReport {
+                on_progress: &|_| {},
+                on_warning: &|_| {},
+            },
+            &Perf::disabled(),
+        )
+        .unwrap();
+        assert_eq!(transcript, "hi there bye");
+
+        let db_guard = app_state.db.lock().unwrap();
+        let db = db_guard.as_ref().unwrap();
+        let sessions = db.list_sessions().unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].transcript, "hi there bye");
+        let undoable = app_state.undoable.lock().unwrap().clone().unwrap();
+        assert_eq!(undoable.session_id, sessions[0].id);
+        let segments: Vec<_> = db
+            .list_segments(&sessions[0].id)
+            .unwrap()
+            .into_iter()
+            .map(|seg| (seg.start_ms, seg.end_ms, seg.speaker_label, seg.text))
+            .collect();
+        assert_eq!(
+            segments,
+            vec![
+                (0, 600, Some("A".to_string()), "hi".to_string()),
+                (700, 1500, Some("B".to_string()), "there".to_string()),
+                (1200, 1500, Some("A".to_string()), "bye".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn transcribe_path_leaves_db_unlocked_during_upload() {
+        let dir = tempfile::tempdir().unwrap();
+        let wav = dir.path().join("upload.wav");
+        write_test_wav(&wav, 1000);
+        let (started_tx, started_rx) = std::sync::mpsc::channel();
+        let (release_tx, release_rx) = std::sync::mpsc::channel();
+        let transcriber = GatedTranscriber {
+            body: r#"{"transcript": "hello", "speakers": ["A"], "segments": [
+                {"speaker": "A", "start_ms": 0, "end_ms": 900, "text": "hello"}]}"#
+                .into(),
+            started: Mutex::new(started_tx),
+            release: Mutex::new(release_rx),
+        };
+        let app_state = AppState::new(dir.path().to_path_buf(), Arc::new(transcriber));
+        *app_state.db.lock().unwrap() = Some(memory_db());
+
+        std::thread::scope(|scope| {
+            let job = scope.spawn(|| {
+                let options = TranscribeOptions {
+                    keep_source: true,
+                    skip_embeddings: true,
+                };
+                transcribe_path(
+                    &app_state,
+                    wav.to_str().unwrap(),
+                    Some("http://localhost".into()),
+                    None,
+                    options,
+                    &TranscribeReport {
+                        on_progress: &|_| {},
+                        on_warning: &|_| {},
+                    },
+                    &Perf::disabled(),
+                )
+            });
+            started_rx.recv_timeout(Duration::from_secs(10)).unwrap();
+            // Another command can use the database while the upload is in flight.
+            let db_guard = app_state.db.try_lock().expect("DB locked during upload");
+            let sessions = db_guard.as_ref().unwrap().list_sessions().unwrap();
+            assert!(sessions.is_empty());
+            drop(db_guard);
+            release_tx.send(()).unwrap();
+            assert_eq!(job.join().unwrap().unwrap(), "hello");
+        });
+
+        let db_guard = app_state.db.lock().unwrap();
+        let sessions = db_guard.as_ref().unwrap().list_sessions().unwrap();
+        assert_eq!(sessions.len(), 1);
+    }
+
+    #[test]
+    fn global_speaker_numbering_continues_across_sessions() {
+        let db = memory_db();
+        let cfg = AppConfig::default();
+        assert_eq!(new_speaker_label(&db, &cfg, 0).unwrap(), "Speaker 1");
+        assert_eq!(new_speaker_label(&db, &cfg, 1).unwrap(), "Speaker 2");
+        // A new session still continues the global count.
+        assert_eq!(new_speaker_label(&db, &cfg, 0).unwrap(), "Speaker 3");
+    }
+
+    #[test]
+    fn session_speaker_numbering_restarts_per_session() {
+        let db = memory_db();
+        let cfg = AppConfig {
+            speaker_numbering: Some(config::SpeakerNumbering::Session),
+            speaker_label_prefix: Some("Guest".into()),
+            ..AppConfig::default()
+        };
+        assert_eq!(new_speaker_label(&db, &cfg, 0).unwrap(), "Guest 1");
+        assert_eq!(new_speaker_label(&db, &cfg, 1).unwrap(), "Guest 2");
+        assert_eq!(new_speaker_label(&db, &cfg, 0).unwrap(), "Guest 1");
+        // Session numbering leaves the global counter alone.
+        assert_eq!(db.next_speaker_label("Guest").unwrap(), "Guest 1");
+    }
+
+    #[test]
+    fn next_chunk_drains_queue_after_sender_drops() {
+        let (tx, rx) = mpsc::sync_channel(4);
+        let stop = AtomicBool::new(true);
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        drop(tx);
+        assert_eq!(next_chunk(&rx, &stop), Some(1));
+        assert_eq!(next_chunk(&rx, &stop), Some(2));
+        assert_eq!(next_chunk(&rx, &stop), None);
+    }
+
+    #[test]
+    fn next_chunk_returns_queued_chunks_before_stopping() {
+        let (tx, rx) = mpsc::sync_channel(4);
+        let stop = AtomicBool::new(true);
+        tx.send(7).unwrap();
+        // The sender stays alive, as with a backend that keeps its callback around.
+        assert_eq!(next_chunk(&rx, &stop), Some(7));
+        let started = Instant::now();
+        assert_eq!(next_chunk(&rx, &stop), None);
+        assert!(started.elapsed() >= STOP_DRAIN_TIMEOUT);
+        drop(tx);
+    }
+
+    #[test]
+    fn next_chunk_waits_while_recording() {
+        let (tx, rx) = mpsc::sync_channel(4);
+        let stop = Arc::new(AtomicBool::new(false));
+        let sender = thread::spawn(move || {
+            thread::sleep(STOP_DRAIN_TIMEOUT * 2);
+            tx.send(3).unwrap();
+        });
+        assert_eq!(next_chunk(&rx, &stop), Some(3));
+        sender.join().unwrap();
+        assert_eq!(next_chunk(&rx, &stop), None);
+    }
 }
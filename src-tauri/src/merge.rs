@@ -0,0 +1,263 @@
+//! Stitching transcripts of overlapping audio chunks back into one segment list.
+
+use serde::Deserialize;
+
+use crate::ApiSegment;
+
+/// Shortest run of identical words accepted as the same speech heard by two chunks.
+const MIN_MATCH_WORDS: usize = 3;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TranscriptChunk {
+    /// Where the chunk starts in the full recording.
+    pub offset_ms: u64,
+    /// Segments with times relative to the chunk start.
+    pub segments: Vec<ApiSegment>,
+}
+
+/// A word of a segment's text, with its normalized form used for matching.
+struct Word {
+    segment: usize,
+    index: usize,
+    key: String,
+}
+
+/// Merges chunk transcripts whose audio overlaps by `overlap_ms`.
+///
+/// Text both chunks transcribed is found by the longest common run of words inside the
+/// overlap window; the earlier chunk is kept up to the end of that run and the later
+/// chunk from there on, with the cut segments' timestamps shortened proportionally. If
+/// no run of `MIN_MATCH_WORDS` is found, the window is split at its midpoint instead.
+pub fn merge_transcripts(mut chunks: Vec<TranscriptChunk>, overlap_ms: u64) -> Vec<ApiSegment> {
+    chunks.sort_by_key(|c| c.offset_ms);
+    let mut merged: Vec<ApiSegment> = Vec::new();
+    for chunk in chunks {
+        let mut incoming: Vec<ApiSegment> = chunk
+            .segments
+            .into_iter()
+            .map(|mut seg| {
+                seg.start_ms += chunk.offset_ms;
+                seg.end_ms += chunk.offset_ms;
+                seg
+            })
+            .collect();
+        incoming.sort_by_key(|s| s.start_ms);
+        if merged.is_empty() {
+            merged = incoming;
+            continue;
+        }
+        let window_start = chunk.offset_ms;
+        let window_end = chunk.offset_ms + overlap_ms;
+        let tail_from = merged
+            .iter()
+            .position(|s| s.end_ms > window_start)
+            .unwrap_or(merged.len());
+        let head_to = incoming
+            .iter()
+            .position(|s| s.start_ms >= window_end)
+            .unwrap_or(incoming.len());
+
+        let tail_words = words(&merged[tail_from..], tail_from);
+        let head_words = words(&incoming[..head_to], 0);
+        match longest_common_run(&tail_words, &head_words) {
+            Some((a, b, len)) if len >= MIN_MATCH_WORDS => {
+                let keep_until = &tail_words[a + len - 1];
+                let resume_from = &head_words[b + len - 1];
+                truncate_after(&mut merged, keep_until.segment, keep_until.index + 1);
+                let mut rest = incoming.split_off(resume_from.segment);
+                trim_before(&mut rest, resume_from.index + 1);
+                if let (Some(last), Some(first)) = (merged.last(), rest.first_mut()) {
+                    first.start_ms = first.start_ms.max(last.end_ms).min(first.end_ms);
+                }
+                merged.extend(rest);
+            }
+            _ => {
+                let midpoint = window_start + overlap_ms / 2;
+                merged.retain(|s| s.start_ms < midpoint);
+                if let Some(last) = merged.last_mut() {
+                    last.end_ms = last.end_ms.min(midpoint).max(last.start_ms);
+                }
+                merged.extend(incoming.into_iter().filter(|s| s.start_ms >= midpoint));
+            }
+        }
+    }
+    merged.retain(|s| !s.text.trim().is_empty());
+    merged
+}
+
+fn words(segments: &[ApiSegment], first_segment: usize) -> Vec<Word> {
+    segments
+        .iter()
+        .enumerate()
+        .flat_map(|(offset, seg)| {
+            seg.text
+                .split_whitespace()
+                .enumerate()
+                .map(move |(index, word)| Word {
+                    segment: first_segment + offset,
+                    index,
+                    key: word
+                        .chars()
+                        .filter(|c| c.is_alphanumeric())
+                        .flat_map(char::to_lowercase)
+                        .collect(),
+                })
+        })
+        .collect()
+}
+
+/// `(start in a, start in b, length)` of the longest run of equal word keys.
+fn longest_common_run(a: &[Word], b: &[Word]) -> Option<(usize, usize, usize)> {
+    let mut best: Option<(usize, usize, usize)> = None;
+    let mut prev = vec![0usize; b.len() + 1];
+    for i in 1..=a.len() {
+        let mut row = vec![0usize; b.len() + 1];
+        for j in 1..=b.len() {
+            if !a[i - 1].key.is_empty() && a[i - 1].key == b[j - 1].key {
+                row[j] = prev[j - 1] + 1;
+                match best {
+                    Some((_, _, len)) if row[j] <= len => {}
+                    _ => best = Some((i - row[j], j - row[j], row[j])),
+                }
+            }
+        }
+        prev = row;
+    }
+    best
+}
+
+/// Keeps the first `words` words of `segments[segment]` and drops everything after it.
+fn truncate_after(segments: &mut Vec<ApiSegment>, segment: usize, words: usize) {
+    segments.truncate(segment + 1);
+    let seg = &mut segments[segment];
+    let all: Vec<&str> = seg.text.split_whitespace().collect();
+    if words < all.len() {
+        let span = seg.end_ms.saturating_sub(seg.start_ms);
+        seg.end_ms = seg.start_ms + span * words as u64 / all.len() as u64;
+        seg.text = all[..words].join(" ");
+    }
+}
+
+/// Drops the first `words` words of `segments[0]`.
+fn trim_before(segments: &mut [ApiSegment], words: usize) {
+    let Some(seg) = segments.first_mut() else {
+        return;
+    };
+    let all: Vec<&str> = seg.text.split_whitespace().collect();
+    let span = seg.end_ms.saturating_sub(seg.start_ms);
+    seg.start_ms += span * words.min(all.len()) as u64 / all.len().max(1) as u64;
+    seg.text = all[words.min(all.len())..].join(" ");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seg(start_ms: u64, end_ms: u64, text: &str) -> ApiSegment {
+        ApiSegment {
+            speaker: "A".into(),
+            start_ms,
+            end_ms,
+            text: text.into(),
+            exclude_from_embedding: false,
+        }
+    }
+
+    fn texts(segments: &[ApiSegment]) -> Vec<&str> {
+        segments.iter().map(|s| s.text.as_str()).collect()
+    }
+
+    fn assert_in_order(segments: &[ApiSegment]) {
+        for pair in segments.windows(2) {
+            assert!(pair[0].start_ms <= pair[0].end_ms);
+            assert!(pair[0].end_ms <= pair[1].start_ms, "{pair:?}");
+        }
+    }
+
+    fn first_chunk(last: &str) -> TranscriptChunk {
+        TranscriptChunk {
+            offset_ms: 0,
+            segments: vec![
+                seg(0, 10_000, "Welcome everyone to the weekly sync."),
+                seg(10_000, 20_000, "First item is the release schedule."),
+                seg(20_000, 30_000, last),
+            ],
+        }
+    }
+
+    #[test]
+    fn sentence_heard_by_both_chunks_is_kept_once() {
+        let second = TranscriptChunk {
+            offset_ms: 25_000,
+            segments: vec![
+                seg(0, 5_000, "ship the build on Friday."),
+                seg(5_000, 15_000, "Second item is hiring."),
+            ],
+        };
+        let merged = merge_transcripts(
+            vec![
+                second,
+                first_chunk("We agreed to ship the build on Friday."),
+            ],
+            5_000,
+        );
+        assert_eq!(
+            texts(&merged),
+            [
+                "Welcome everyone to the weekly sync.",
+                "First item is the release schedule.",
+                "We agreed to ship the build on Friday.",
+                "Second item is hiring.",
+            ]
+        );
+        assert_in_order(&merged);
+        assert_eq!(merged.last().unwrap().start_ms, 30_000);
+    }
+
+    #[test]
+    fn sentence_cut_by_the_chunk_boundary_is_joined() {
+        let second = TranscriptChunk {
+            offset_ms: 25_000,
+            segments: vec![
+                seg(0, 7_000, "agreed to ship the build on Friday."),
+                seg(7_000, 15_000, "Second item is hiring."),
+            ],
+        };
+        let merged = merge_transcripts(
+            vec![first_chunk("We agreed to ship the build"), second],
+            5_000,
+        );
+        let transcript = texts(&merged).join(" ");
+        assert_eq!(
+            transcript,
+            "Welcome everyone to the weekly sync. First item is the release schedule. \
+             We agreed to ship the build on Friday. Second item is hiring."
+        );
+        assert_in_order(&merged);
+        // The trimmed segment starts where its remaining words were spoken.
+        assert_eq!(merged[3].start_ms, 30_000);
+    }
+
+    #[test]
+    fn overlap_without_shared_words_is_split_at_its_midpoint() {
+        let second = TranscriptChunk {
+            offset_ms: 25_000,
+            segments: vec![
+                seg(0, 2_000, "Uh."),
+                seg(2_500, 10_000, "Second item is hiring."),
+            ],
+        };
+        let merged = merge_transcripts(vec![first_chunk("Okay."), second], 5_000);
+        assert_eq!(
+            texts(&merged),
+            [
+                "Welcome everyone to the weekly sync.",
+                "First item is the release schedule.",
+                "Okay.",
+                "Second item is hiring.",
+            ]
+        );
+        assert_eq!(merged[2].end_ms, 27_500);
+        assert_in_order(&merged);
+    }
+}
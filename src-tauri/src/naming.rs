@@ -0,0 +1,151 @@
+//! Heuristic speaker-name suggestions from self-introductions in transcript text.
+
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Phrases that introduce the speaker's name right after them, with their confidence.
+const LEADING_PATTERNS: &[(&[&str], f32)] = &[
+    (&["my", "name", "is"], 0.9),
+    (&["my", "name's"], 0.9),
+    (&["this", "is"], 0.7),
+    (&["call", "me"], 0.7),
+    (&["i", "am"], 0.5),
+    (&["i'm"], 0.5),
+];
+
+/// Words that follow the name and end the clause ("Alice here").
+const TRAILING_PATTERNS: &[(&str, f32)] = &[("here", 0.6), ("speaking", 0.7)];
+
+/// Capitalized words that are not names in these positions.
+const NOT_NAMES: &[&str] = &[
+    "I",
+    "A",
+    "An",
+    "The",
+    "It",
+    "This",
+    "That",
+    "We",
+    "You",
+    "He",
+    "She",
+    "They",
+    "Here",
+    "There",
+    "Everyone",
+    "Everybody",
+    "Just",
+    "Not",
+    "So",
+    "Okay",
+    "OK",
+    "Yes",
+    "No",
+    "Sorry",
+    "Hi",
+    "Hello",
+    "Hey",
+    "Good",
+    "Thanks",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NameSuggestion {
+    pub name: String,
+    /// Combined confidence in `[0, 1]` across all matching introductions.
+    pub confidence: f32,
+    pub occurrences: usize,
+}
+
+/// Scans `texts` (in speaking order) for self-introductions and ranks the names found.
+pub fn suggest_names(texts: &[String]) -> Vec<NameSuggestion> {
+    // name -> (probability that every match is wrong, occurrences)
+    let mut found: HashMap<String, (f32, usize)> = HashMap::new();
+    for text in texts {
+        let normalized = text.replace('\u{2019}', "'");
+        for clause in normalized.split(['.', '!', '?', ',', ';', ':']) {
+            let words: Vec<&str> = clause
+                .split_whitespace()
+                .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric() && c != '\'' && c != '-'))
+                .filter(|w| !w.is_empty())
+                .collect();
+            for (name, confidence) in clause_candidates(&words) {
+                let entry = found.entry(name).or_insert((1.0, 0));
+                entry.0 *= 1.0 - confidence;
+                entry.1 += 1;
+            }
+        }
+    }
+    let mut suggestions: Vec<NameSuggestion> = found
+        .into_iter()
+        .map(|(name, (miss, occurrences))| NameSuggestion {
+            name,
+            confidence: 1.0 - miss,
+            occurrences,
+        })
+        .collect();
+    suggestions.sort_by(|a, b| {
+        b.confidence
+            .total_cmp(&a.confidence)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    suggestions
+}
+
+fn clause_candidates(words: &[&str]) -> Vec<(String, f32)> {
+    let lower: Vec<String> = words.iter().map(|w| w.to_lowercase()).collect();
+    let mut candidates = Vec::new();
+    for (pattern, confidence) in LEADING_PATTERNS {
+        let n = pattern.len();
+        for start in 0..lower.len().saturating_sub(n) {
+            if lower[start..start + n]
+                .iter()
+                .zip(pattern.iter())
+                .all(|(w, p)| w == p)
+            {
+                if let Some(name) = name_at(words, start + n) {
+                    candidates.push((name, *confidence));
+                }
+            }
+        }
+    }
+    if let Some(last) = lower.last() {
+        for (word, confidence) in TRAILING_PATTERNS {
+            if last == word && words.len() >= 2 && words.len() <= 4 {
+                let name_start = words.len() - 2;
+                if let Some(name) = single_name(words[name_start]) {
+                    candidates.push((name, *confidence));
+                }
+            }
+        }
+    }
+    candidates
+}
+
+/// A capitalized first name at `index`, extended by one capitalized surname if present.
+fn name_at(words: &[&str], index: usize) -> Option<String> {
+    let first = single_name(words.get(index)?)?;
+    match words.get(index + 1).and_then(|w| single_name(w)) {
+        Some(last) => Some(format!("{first} {last}")),
+        None => Some(first),
+    }
+}
+
+fn single_name(word: &str) -> Option<String> {
+    let starts_upper = word.chars().next().is_some_and(|c| c.is_uppercase());
+    let alphabetic = word
+        .chars()
+        .all(|c| c.is_alphabetic() || c == '-' || c == '\'');
+    if starts_upper && alphabetic && word.len() > 1 && !NOT_NAMES.contains(&word) {
+        Some(word.trim_end_matches("'s").to_string())
+    } else {
+        None
+    }
+}
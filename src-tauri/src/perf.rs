@@ -0,0 +1,66 @@
+//! Optional timings, emitted as `perf:command` events while `perf_logging` is on.
+
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{ipc::Invoke, AppHandle, Emitter, Manager, Wry};
+
+use crate::state::AppState;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PerfTiming {
+    pub name: String,
+    pub ms: f64,
+}
+
+/// Reports timings to the UI; a disabled `Perf` emits nothing.
+#[derive(Clone, Copy)]
+pub struct Perf<'a> {
+    app: Option<&'a AppHandle>,
+}
+
+impl<'a> Perf<'a> {
+    pub fn new(app: &'a AppHandle, app_state: &AppState) -> Self {
+        Perf {
+            app: app_state.perf_logging().then_some(app),
+        }
+    }
+
+    pub fn disabled() -> Self {
+        Perf { app: None }
+    }
+
+    pub fn record(&self, name: &str, elapsed: Duration) {
+        if let Some(app) = self.app {
+            let timing = PerfTiming {
+                name: name.to_string(),
+                ms: elapsed.as_secs_f64() * 1000.0,
+            };
+            let _ = app.emit("perf:command", timing);
+        }
+    }
+}
+
+/// Wraps the command handler to time every command. Commands are synchronous, so the
+/// handler only returns once the command has run. When perf logging is off this costs a
+/// config lookup per call.
+pub fn timed<F>(handler: F) -> impl Fn(Invoke<Wry>) -> bool + Send + Sync + 'static
+where
+    F: Fn(Invoke<Wry>) -> bool + Send + Sync + 'static,
+{
+    move |invoke| {
+        let webview = invoke.message.webview_ref();
+        let enabled = webview
+            .try_state::<AppState>()
+            .is_some_and(|app_state| app_state.perf_logging());
+        if !enabled {
+            return handler(invoke);
+        }
+        let app = webview.app_handle().clone();
+        let name = invoke.message.command().to_string();
+        let started = Instant::now();
+        let handled = handler(invoke);
+        Perf { app: Some(&app) }.record(&name, started.elapsed());
+        handled
+    }
+}
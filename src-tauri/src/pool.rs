@@ -0,0 +1,117 @@
+//! A small pool of loaded embedders, so concurrent jobs don't queue on one ONNX session.
+
+use std::ops::{Deref, DerefMut};
+use std::sync::{Condvar, Mutex};
+
+use crate::embedding::Embedder;
+
+#[derive(Default)]
+struct Slots {
+    idle: Vec<Embedder>,
+    /// Embedders in existence, idle or checked out.
+    loaded: usize,
+    /// Bumped by [`EmbedderPool::clear`]; embedders from an older generation are dropped
+    /// instead of going back to the pool.
+    generation: u64,
+}
+
+#[derive(Default)]
+pub struct EmbedderPool {
+    slots: Mutex<Slots>,
+    returned: Condvar,
+}
+
+/// An embedder checked out of the pool; it goes back when dropped.
+pub struct PooledEmbedder<'a> {
+    pool: &'a EmbedderPool,
+    embedder: Option<Embedder>,
+    generation: u64,
+}
+
+impl EmbedderPool {
+    /// Hands out an idle embedder, loads another with `load` while fewer than `size` exist,
+    /// and otherwise waits for one to come back. Loading happens outside the pool's lock.
+    pub fn checkout(
+        &self,
+        size: usize,
+        load: impl FnOnce() -> Result<Embedder, String>,
+    ) -> Result<PooledEmbedder<'_>, String> {
+        let mut slots = self
+            .slots
+            .lock()
+            .map_err(|_| "embedder pool lock".to_string())?;
+        loop {
+            let generation = slots.generation;
+            if let Some(embedder) = slots.idle.pop() {
+                return Ok(self.wrap(embedder, generation));
+            }
+            if slots.loaded < size.max(1) {
+                slots.loaded += 1;
+                drop(slots);
+                return match load() {
+                    Ok(embedder) => Ok(self.wrap(embedder, generation)),
+                    Err(e) => {
+                        if let Ok(mut slots) = self.slots.lock() {
+                            if slots.generation == generation {
+                                slots.loaded -= 1;
+                            }
+                        }
+                        self.returned.notify_one();
+                        Err(e)
+                    }
+                };
+            }
+            slots = self
+                .returned
+                .wait(slots)
+                .map_err(|_| "embedder pool lock".to_string())?;
+        }
+    }
+
+    /// Drops the idle embedders, and those checked out once they come back, so the next
+    /// checkout loads one with the current model and settings.
+    pub fn clear(&self) {
+        if let Ok(mut slots) = self.slots.lock() {
+            slots.idle.clear();
+            slots.loaded = 0;
+            slots.generation += 1;
+        }
+        self.returned.notify_all();
+    }
+
+    fn wrap(&self, embedder: Embedder, generation: u64) -> PooledEmbedder<'_> {
+        PooledEmbedder {
+            pool: self,
+            embedder: Some(embedder),
+            generation,
+        }
+    }
+}
+
+impl Deref for PooledEmbedder<'_> {
+    type Target = Embedder;
+
+    fn deref(&self) -> &Embedder {
+        self.embedder.as_ref().expect("embedder present until drop")
+    }
+}
+
+impl DerefMut for PooledEmbedder<'_> {
+    fn deref_mut(&mut self) -> &mut Embedder {
+        self.embedder.as_mut().expect("embedder present until drop")
+    }
+}
+
+impl Drop for PooledEmbedder<'_> {
+    fn drop(&mut self) {
+        let Some(embedder) = self.embedder.take() else {
+            return;
+        };
+        if let Ok(mut slots) = self.pool.slots.lock() {
+            if slots.generation == self.generation {
+                slots.idle.push(embedder);
+            }
+        }
+        self.pool.returned.notify_one();
+    }
+}
@@ -1,19 +1,85 @@
+use std::collections::{HashMap, VecDeque};
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
+use chrono::{DateTime, Utc};
+
 use crate::config::AppConfig;
 use crate::db::{Crypto, Db};
+use crate::embedding::EmbeddingMismatch;
+use crate::pool::{EmbedderPool, PooledEmbedder};
+use crate::transcriber::Transcriber;
+
+/// A transcription `undo_last_transcription` can still revert.
+#[derive(Debug, Clone)]
+pub struct UndoableTranscription {
+    pub session_id: String,
+    pub finished_at: DateTime<Utc>,
+}
+
+/// Recordings waiting for background transcription, and how many workers are taking them.
+#[derive(Debug, Default)]
+pub struct TranscriptionQueue {
+    pending: VecDeque<PathBuf>,
+    workers: usize,
+}
+
+impl TranscriptionQueue {
+    /// Queues `path`; `true` when fewer than `limit` workers are running, so the caller
+    /// should start another.
+    pub fn push(&mut self, path: PathBuf, limit: usize) -> bool {
+        self.pending.push_back(path);
+        if self.workers < limit.max(1) {
+            self.workers += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// The next queued recording for a worker; `None` retires the worker.
+    pub fn next(&mut self) -> Option<PathBuf> {
+        let next = self.pending.pop_front();
+        if next.is_none() {
+            self.workers = self.workers.saturating_sub(1);
+        }
+        next
+    }
+}
+
+/// Waveform peaks by session id and bucket count.
+pub type WaveformCache = HashMap<(String, usize), Vec<f32>>;
 
 pub struct AppState {
     pub db: Arc<Mutex<Option<Db>>>,
     pub data_dir: PathBuf,
     pub config_path: PathBuf,
     pub config: Arc<Mutex<AppConfig>>,
-    pub embedder: Arc<Mutex<Option<crate::embedding::Embedder>>>,
+    pub embedders: Arc<EmbedderPool>,
+    pub logs: crate::logs::LogBuffer,
+    pub waveforms: Arc<Mutex<WaveformCache>>,
+    /// Session each pending recording is appended to when transcribed, keyed by the file that
+    /// gets transcribed (the normalized copy when gain normalization is on).
+    pub append_targets: Arc<Mutex<HashMap<PathBuf, String>>>,
+    /// Set while stored voiceprints don't fit the loaded model; speaker matching stays off
+    /// until `reembed_all` rebuilds them.
+    pub embedding_mismatch: Arc<Mutex<Option<EmbeddingMismatch>>>,
+    /// Why ONNX Runtime couldn't start, if it couldn't; transcription then goes ahead
+    /// without speaker matching until `retry_embeddings` succeeds.
+    pub embeddings_unavailable: Arc<Mutex<Option<String>>>,
+    /// Sends recordings for transcription; the HTTP client unless a canned response
+    /// stands in for the server.
+    pub transcriber: Arc<dyn Transcriber>,
+    /// Session the most recent transcription created and when it finished, until
+    /// `undo_last_transcription` reverts it; cleared when that transcription can't be
+    /// undone (it was appended to an existing session).
+    pub undoable: Arc<Mutex<Option<UndoableTranscription>>>,
+    /// Background transcriptions, capped at `max_background_transcriptions` at once.
+    pub transcriptions: Arc<Mutex<TranscriptionQueue>>,
 }
 
 impl AppState {
-    pub fn new(data_dir: PathBuf) -> Self {
+    pub fn new(data_dir: PathBuf, transcriber: Arc<dyn Transcriber>) -> Self {
         let config_path = data_dir.join("config.json");
         let config = AppConfig::load(&config_path);
         Self {
@@ -21,16 +87,53 @@ impl AppState {
             data_dir,
             config_path,
             config: Arc::new(Mutex::new(config)),
-            embedder: Arc::new(Mutex::new(None)),
+            embedders: Arc::new(EmbedderPool::default()),
+            logs: crate::logs::LogBuffer::default(),
+            waveforms: Arc::new(Mutex::new(HashMap::new())),
+            append_targets: Arc::new(Mutex::new(HashMap::new())),
+            embedding_mismatch: Arc::new(Mutex::new(None)),
+            embeddings_unavailable: Arc::new(Mutex::new(None)),
+            transcriber,
+            undoable: Arc::new(Mutex::new(None)),
+            transcriptions: Arc::new(Mutex::new(TranscriptionQueue::default())),
         }
     }
 
-    #[allow(dead_code)]
     pub fn save_config(&self) -> Result<(), String> {
         let cfg = self.config.lock().map_err(|_| "config lock".to_string())?.clone();
         cfg.save(&self.config_path)
     }
 
+    pub fn perf_logging(&self) -> bool {
+        self.config
+            .lock()
+            .map(|cfg| cfg.perf_logging)
+            .unwrap_or(false)
+    }
+
+    pub fn matching_disabled(&self) -> bool {
+        self.embedding_mismatch
+            .lock()
+            .map(|mismatch| mismatch.is_some())
+            .unwrap_or(false)
+    }
+
+    pub fn embeddings_unavailable(&self) -> Option<String> {
+        self.embeddings_unavailable
+            .lock()
+            .ok()
+            .and_then(|reason| reason.clone())
+    }
+
+    /// Checks that ONNX Runtime starts and records the outcome.
+    pub fn check_onnx_runtime(&self) -> Result<(), String> {
+        let result = crate::embedding::check_runtime();
+        if let Ok(mut reason) = self.embeddings_unavailable.lock() {
+            *reason = result.as_ref().err().cloned();
+        }
+        result
+    }
+
     pub fn db_path(&self) -> PathBuf {
         self.data_dir.join("recall.db")
     }
@@ -38,13 +141,75 @@ impl AppState {
     pub fn open_db(&self, crypto: Crypto) -> Result<(), String> {
         std::fs::create_dir_all(&self.data_dir).map_err(|e| e.to_string())?;
         let db_path = self.db_path();
-        let db = Db::open(db_path, crypto)?;
+        let mut db = Db::open(db_path, crypto)?;
+        let ann_metric = {
+            let cfg = self.config.lock().map_err(|_| "config lock".to_string())?;
+            db.compress_embeddings = cfg.compress_embeddings;
+            db.max_embeddings_per_speaker = cfg.max_embeddings_per_speaker();
+            db.embedding_cache_size = cfg.embedding_cache_size();
+            db.plaintext_search_index = cfg.plaintext_search_index;
+            cfg.ann_matching.then(|| cfg.similarity_metric())
+        };
+        db.ensure_search_index()?;
+        db.set_ann_matching(ann_metric)?;
         let mut guard = self.db.lock().map_err(|_| "db lock".to_string())?;
         *guard = Some(db);
         Ok(())
     }
 
+    /// Runs `f` against a read-only connection, so exports can't modify the database and
+    /// don't hold the main connection while they run.
+    pub fn with_readonly_db<T>(
+        &self,
+        f: impl FnOnce(&Db) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let readonly = {
+            let guard = self.db.lock().map_err(|_| "db lock".to_string())?;
+            let db = guard.as_ref().ok_or("Database not initialized")?;
+            db.open_readonly(self.db_path())?
+        };
+        f(&readonly)
+    }
+
+    pub fn model_path(&self) -> PathBuf {
+        self.data_dir
+            .join("models")
+            .join("spkrec-ecapa-voxceleb.onnx")
+    }
+
+    /// Hashes the model file and compares it with `model_sha256` from config, or the
+    /// bundled model's digest when unset.
+    pub fn verify_model(&self) -> Result<crate::embedding::ModelChecksum, String> {
+        let expected = self
+            .config
+            .lock()
+            .map_err(|_| "config lock".to_string())?
+            .model_sha256();
+        crate::embedding::ModelChecksum::compute(&self.model_path(), &expected)
+    }
+
     pub fn ensure_model_present(&self) -> Result<(), String> {
+        self.copy_model_if_missing()?;
+        let verify = self
+            .config
+            .lock()
+            .map_err(|_| "config lock".to_string())?
+            .verify_model_checksum;
+        if verify {
+            let checksum = self.verify_model()?;
+            if !checksum.matches {
+                return Err(format!(
+                    "Model checksum mismatch for {}: expected {}, got {}. The file is corrupted or was replaced; delete it to restore the bundled model, or set model_sha256 for a custom model.",
+                    checksum.path.display(),
+                    checksum.expected,
+                    checksum.actual
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn copy_model_if_missing(&self) -> Result<(), String> {
         let models_dir = self.data_dir.join("models");
         let dest = models_dir.join("spkrec-ecapa-voxceleb.onnx");
         if dest.exists() {
@@ -70,15 +235,61 @@ impl AppState {
         Err("ONNX model missing (expected in data dir or ./models)".into())
     }
 
-    pub fn load_embedder(&self) -> Result<(), String> {
+    pub fn load_embedder(&self) -> Result<crate::embedding::Embedder, String> {
         self.ensure_model_present()?;
-        let model_path = self
-            .data_dir
-            .join("models")
-            .join("spkrec-ecapa-voxceleb.onnx");
-        let embedder = crate::embedding::Embedder::new(model_path.to_string_lossy().as_ref())?;
-        let mut guard = self.embedder.lock().map_err(|_| "embedder lock".to_string())?;
-        *guard = Some(embedder);
-        Ok(())
+        let model_path = self.model_path();
+        let (layout, output, max_input_secs) = {
+            let cfg = self.config.lock().map_err(|_| "config lock".to_string())?;
+            (
+                cfg.embedding_input_layout,
+                cfg.embedding_output.clone(),
+                cfg.max_embedding_input_secs(),
+            )
+        };
+        crate::embedding::Embedder::new(
+            model_path.to_string_lossy().as_ref(),
+            layout,
+            output.as_ref(),
+            max_input_secs,
+        )
+    }
+
+    /// Takes an embedder from the pool, loading one if none is idle and the pool isn't
+    /// full, or waiting for another job to finish with one.
+    pub fn checkout_embedder(&self) -> Result<PooledEmbedder<'_>, String> {
+        if let Some(reason) = self.embeddings_unavailable() {
+            return Err(format!(
+                "ONNX Runtime is unavailable ({reason}); run retry_embeddings once it is fixed"
+            ));
+        }
+        let size = self
+            .config
+            .lock()
+            .map_err(|_| "config lock".to_string())?
+            .embedder_pool_size();
+        self.embedders.checkout(size, || self.load_embedder())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transcription_queue_caps_workers_and_keeps_order() {
+        let mut queue = TranscriptionQueue::default();
+        let started: Vec<bool> = ["a", "b", "c", "d"]
+            .into_iter()
+            .map(|p| queue.push(PathBuf::from(p), 2))
+            .collect();
+        assert_eq!(started, [true, true, false, false]);
+
+        // Two workers drain the queue in order, then both retire.
+        let taken: Vec<PathBuf> = std::iter::from_fn(|| queue.next()).collect();
+        assert_eq!(taken, ["a", "b", "c", "d"].map(PathBuf::from));
+        assert_eq!(queue.workers, 1);
+        assert_eq!(queue.next(), None);
+        assert_eq!(queue.workers, 0);
+        assert!(queue.push(PathBuf::from("e"), 2));
     }
 }
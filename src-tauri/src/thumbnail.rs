@@ -0,0 +1,85 @@
+//! Small grayscale spectrogram images of a recording, for browsing sessions at a glance.
+
+pub const WIDTH: usize = 128;
+pub const HEIGHT: usize = 64;
+const FFT_SIZE: usize = 512;
+/// Spectra averaged per column, spread evenly over the column's stretch of audio.
+const FRAMES_PER_COLUMN: usize = 4;
+/// Speech sits below this, so higher frequencies aren't drawn.
+const MAX_FREQ_HZ: f32 = 8_000.0;
+/// Levels this far below the loudest bin are drawn black.
+const DYNAMIC_RANGE_DB: f32 = 80.0;
+
+/// Renders a `WIDTH`×`HEIGHT` spectrogram PNG (low frequencies at the bottom) from mono
+/// samples, reading them once and keeping only one FFT window in memory.
+pub fn spectrogram_png(
+    samples: impl Iterator<Item = Result<f32, String>>,
+    total_frames: usize,
+    sample_rate: u32,
+) -> Result<Vec<u8>, String> {
+    if total_frames == 0 || sample_rate == 0 {
+        return Err("Audio is empty".into());
+    }
+    let frames = WIDTH * FRAMES_PER_COLUMN;
+    // Index of the last sample of each analysis window, in order.
+    let window_ends: Vec<usize> = (0..frames)
+        .map(|i| {
+            let start = i * total_frames / frames;
+            (start + FFT_SIZE - 1).min(total_frames - 1)
+        })
+        .collect();
+
+    let window = crate::fbank::hamming_window(FFT_SIZE);
+    let mut ring = vec![0f32; FFT_SIZE];
+    let mut power = vec![vec![0f32; FFT_SIZE / 2]; WIDTH];
+    let mut next = 0;
+    for (index, sample) in samples.enumerate() {
+        ring[index % FFT_SIZE] = sample?;
+        while next < frames && window_ends[next] == index {
+            let mut re: Vec<f32> = (0..FFT_SIZE)
+                .map(|k| ring[(index + 1 + k) % FFT_SIZE] * window[k])
+                .collect();
+            let mut im = vec![0f32; FFT_SIZE];
+            crate::fbank::fft(&mut re, &mut im);
+            for (bin, acc) in power[next / FRAMES_PER_COLUMN].iter_mut().enumerate() {
+                *acc += re[bin] * re[bin] + im[bin] * im[bin];
+            }
+            next += 1;
+        }
+        if next == frames {
+            break;
+        }
+    }
+
+    let nyquist = sample_rate as f32 / 2.0;
+    let max_bin = ((MAX_FREQ_HZ.min(nyquist) / nyquist) * (FFT_SIZE / 2) as f32) as usize;
+    let max_bin = max_bin.clamp(HEIGHT, FFT_SIZE / 2);
+    let mut levels = vec![0f32; WIDTH * HEIGHT];
+    for (x, column) in power.iter().enumerate() {
+        for y in 0..HEIGHT {
+            let lo = y * max_bin / HEIGHT;
+            let hi = ((y + 1) * max_bin / HEIGHT).max(lo + 1);
+            let mean = column[lo..hi].iter().sum::<f32>() / (hi - lo) as f32;
+            levels[(HEIGHT - 1 - y) * WIDTH + x] = 10.0 * mean.max(1e-12).log10();
+        }
+    }
+    let loudest = levels.iter().copied().fold(f32::MIN, f32::max);
+    let pixels: Vec<u8> = levels
+        .iter()
+        .map(|db| {
+            let level = 1.0 - ((loudest - db) / DYNAMIC_RANGE_DB).clamp(0.0, 1.0);
+            (level * 255.0).round() as u8
+        })
+        .collect();
+
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, WIDTH as u32, HEIGHT as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer
+        .write_image_data(&pixels)
+        .map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())?;
+    Ok(png)
+}
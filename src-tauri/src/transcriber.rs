@@ -0,0 +1,60 @@
+//! The call that turns a recording into a transcription server response, behind a trait so
+//! the rest of the pipeline (segment normalization, speaker matching, storage) can run
+//! against a canned response instead of the network.
+
+use crate::config::AppConfig;
+
+/// Environment variable naming a JSON file to answer every transcription with, in place of
+/// the server. Read in debug builds only.
+#[cfg(debug_assertions)]
+pub const CANNED_RESPONSE_ENV: &str = "RECALL_CANNED_RESPONSE";
+
+pub trait Transcriber: Send + Sync {
+    /// Sends the audio at `path` and returns the response body, an
+    /// `ApiTranscribeResponse` as JSON. Progress in percent goes to `on_progress` when the
+    /// server reports it.
+    fn transcribe(
+        &self,
+        cfg: &AppConfig,
+        api_base: &str,
+        path: &str,
+        on_progress: &dyn Fn(f32),
+    ) -> Result<String, String>;
+}
+
+/// Answers every request with the same body without reading the audio or contacting a
+/// server.
+#[cfg(any(test, debug_assertions))]
+pub struct CannedTranscriber {
+    body: String,
+}
+
+#[cfg(any(test, debug_assertions))]
+impl CannedTranscriber {
+    pub fn new(body: impl Into<String>) -> Self {
+        Self { body: body.into() }
+    }
+
+    #[cfg(debug_assertions)]
+    pub fn from_file(path: &std::path::Path) -> Result<Self, String> {
+        let body = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read canned response {}: {e}", path.display()))?;
+        serde_json::from_str::<serde_json::Value>(&body)
+            .map_err(|e| format!("Canned response {} is not JSON: {e}", path.display()))?;
+        Ok(Self::new(body))
+    }
+}
+
+#[cfg(any(test, debug_assertions))]
+impl Transcriber for CannedTranscriber {
+    fn transcribe(
+        &self,
+        _cfg: &AppConfig,
+        _api_base: &str,
+        _path: &str,
+        on_progress: &dyn Fn(f32),
+    ) -> Result<String, String> {
+        on_progress(100.0);
+        Ok(self.body.clone())
+    }
+}
@@ -12,6 +12,28 @@ if (!apiInput.value) {
   apiInput.value = "http://localhost:8787";
 }
 
+// recording:clipping repeats every second while the input clips; the note is added once
+// per recording.
+let clippingWarned = false;
+
+// When enabled the backend transcribes right after stop and reports via transcribe:* events.
+let autoTranscribe = false;
+invoke("get_config")
+  .then((config) => {
+    autoTranscribe = Boolean(config.auto_transcribe_on_stop);
+  })
+  .catch((err) => console.error("get_config error", err));
+
+invoke("app_status")
+  .then((status) => {
+    if (!status.embeddings_available) {
+      appendNote(
+        `ONNX Runtime could not start (${status.embeddings_error}). Transcripts will be saved without speaker matching until retry_embeddings succeeds.`
+      );
+    }
+  })
+  .catch((err) => console.error("app_status error", err));
+
 function setStatus(text) {
   statusEl.textContent = text;
 }
@@ -24,6 +46,7 @@ function appendNote(text) {
 async function startRecording() {
   appendNote("Start clicked");
   setStatus("Starting…");
+  clippingWarned = false;
   startBtn.disabled = true;
   try {
     await invoke("start_recording");
@@ -43,7 +66,9 @@ async function stopRecording() {
   try {
     const path = await invoke("stop_recording");
     setStatus(`Stopped. Saved at ${path}`);
-    await sendToApi(path);
+    if (!autoTranscribe) {
+      await sendToApi(path);
+    }
   } catch (err) {
     console.error("stop_recording error", err);
     appendNote(`Stop error: ${err}`);
@@ -79,4 +104,76 @@ listen("recording:stop", () => {
   startBtn.disabled = false;
 });
 
+listen("transcribe:start", () => {
+  setStatus("Transcribing…");
+});
+
+listen("recording:clipping", (event) => {
+  setStatus(`Recording is clipping (${event.payload.percent.toFixed(1)}%)`);
+  if (!clippingWarned) {
+    clippingWarned = true;
+    appendNote("Input is clipping. Lower the input gain for better transcripts.");
+  }
+});
+
+listen("transcribe:progress", (event) => {
+  setStatus(`Transcribing… ${Math.round(event.payload.percent)}%`);
+});
+
+listen("perf:command", (event) => {
+  console.debug(`${event.payload.name}: ${event.payload.ms.toFixed(1)} ms`);
+});
+
+listen("transcribe:warning", (event) => {
+  appendNote("Warning: " + event.payload.warning);
+});
+
+listen("transcribe:done", (event) => {
+  setStatus("Transcribed");
+  appendNote(event.payload.transcript);
+});
+
+listen("transcribe:error", (event) => {
+  setStatus("Transcription failed");
+  appendNote("API error: " + event.payload.error);
+});
+
+listen("schedule:started", () => {
+  setStatus("Scheduled recording started");
+});
+
+listen("schedule:stopped", () => {
+  setStatus("Scheduled recording stopped");
+});
+
+listen("schedule:error", (event) => {
+  setStatus("Scheduled recording failed");
+  appendNote("Scheduled recording: " + event.payload.error);
+});
+
+listen("db:needs-password", () => {
+  setStatus("Database locked — unlock to continue");
+});
+
+listen("db:unlocked", () => {
+  setStatus("Database unlocked");
+});
+
+listen("db:reinitialized", (event) => {
+  const { backup_path } = event.payload;
+  setStatus("Database reinitialized");
+  appendNote(
+    backup_path
+      ? `The database was corrupt and has been replaced with an empty one. The old file is at ${backup_path} for manual recovery.`
+      : "The database was corrupt and has been replaced with an empty one. The old file was deleted."
+  );
+});
+
+listen("model:embedding-mismatch", (event) => {
+  const { stored_dim, model_dim } = event.payload;
+  appendNote(
+    `Stored voiceprints have ${stored_dim} values but the model produces ${model_dim}. Speaker matching is off until voiceprints are rebuilt (reembed_all).`
+  );
+});
+
 appendNote("Ready.");